@@ -0,0 +1,70 @@
+// End-to-end throughput of reading and parsing kernel log entries from a
+// /dev/kmsg-formatted fixture file.
+//
+// Run with: cargo bench --bench throughput
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use criterion::{black_box, Criterion, Throughput};
+    use rmesg::kmsgfile;
+    use std::fs;
+    use std::path::Path;
+
+    const SAMPLE_KMSG: &str = "tests/fixtures/sample.kmsg";
+
+    // A line with every field populated, including the optional flags after the timestamp
+    const FULL_LINE: &str =
+        "6,3,91650777797,-,more,deets;x86/fpu: Supporting XSAVE; feature 0x002: 'SSE registers'";
+
+    pub fn throughput(c: &mut Criterion) {
+        let contents = fs::read_to_string(SAMPLE_KMSG).unwrap();
+        let entry_count = contents.lines().count() as u64;
+
+        for (name, throughput) in [
+            ("entries", Throughput::Elements(entry_count)),
+            ("bytes", Throughput::Bytes(contents.len() as u64)),
+        ] {
+            let mut group = c.benchmark_group(format!("kmsg_{}", name));
+            group.throughput(throughput);
+
+            // Baseline: just getting the bytes off disk
+            group.bench_function("read_file", |b| {
+                b.iter(|| fs::read_to_string(black_box(SAMPLE_KMSG)).unwrap())
+            });
+
+            group.bench_function("parse_only", |b| {
+                b.iter(|| {
+                    black_box(&contents)
+                        .lines()
+                        .map(kmsgfile::entry_from_line)
+                        .collect::<Result<Vec<_>, _>>()
+                        .unwrap()
+                })
+            });
+
+            group.bench_function("kmsg", |b| {
+                b.iter(|| {
+                    kmsgfile::kmsg_from_path(Some(Path::new(black_box(SAMPLE_KMSG)))).unwrap()
+                })
+            });
+
+            group.finish();
+        }
+
+        let mut group = c.benchmark_group("entry_from_line");
+        group.throughput(Throughput::Elements(1));
+        group.bench_function("full_line", |b| {
+            b.iter(|| kmsgfile::entry_from_line(black_box(FULL_LINE)).unwrap())
+        });
+        group.finish();
+    }
+}
+
+#[cfg(target_os = "linux")]
+criterion::criterion_group!(benches, linux::throughput);
+
+#[cfg(target_os = "linux")]
+criterion::criterion_main!(benches);
+
+#[cfg(not(target_os = "linux"))]
+fn main() {}
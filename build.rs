@@ -0,0 +1,27 @@
+use std::env;
+
+// Works out, from the target and the enabled features, which defaults the crate is built with.
+//
+// * `rmesg_embedded`: shorter poll intervals, and the hand-written parsers.
+//   On by default for ARM targets. Forced on by the `embedded-defaults` feature, and off by
+//   the `desktop-defaults` feature.
+// * `rmesg_regex_parser`: parse entries with regular expressions. Needs the `regex-parser`
+//   feature, and neither the `no-regex` feature nor embedded defaults.
+fn main() {
+    let feature = |name: &str| env::var_os(format!("CARGO_FEATURE_{}", name)).is_some();
+    let target_arch = env::var("CARGO_CFG_TARGET_ARCH").unwrap_or_default();
+
+    let embedded =
+        feature("EMBEDDED_DEFAULTS") || (target_arch == "arm" && !feature("DESKTOP_DEFAULTS"));
+    let regex_parser = feature("REGEX_PARSER") && !feature("NO_REGEX") && !embedded;
+
+    println!("cargo:rustc-check-cfg=cfg(rmesg_embedded)");
+    println!("cargo:rustc-check-cfg=cfg(rmesg_regex_parser)");
+
+    if embedded {
+        println!("cargo:rustc-cfg=rmesg_embedded");
+    }
+    if regex_parser {
+        println!("cargo:rustc-cfg=rmesg_regex_parser");
+    }
+}
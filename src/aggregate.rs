@@ -0,0 +1,271 @@
+use crate::entry::Entry;
+use crate::error::RMesgError;
+use crate::klogctl::{self, KLogEntries};
+use crate::kmsgfile::KMsgEntriesIter;
+use crate::EntriesIterator;
+
+use std::collections::{HashSet, VecDeque};
+use std::iter::Iterator;
+use std::time::Duration;
+
+/// Reads from both the klogctl and the /dev/kmsg backends, and emits the union
+/// of the two, skipping entries that were already emitted.
+///
+/// Entries read from /dev/kmsg carry a sequence number, and are deduplicated by it.
+/// The klogctl interface provides no sequence numbers, so an entry is also remembered by
+/// its timestamp and message, which is how klogctl entries are matched against /dev/kmsg
+/// entries (and vice versa). This requires kernel log timestamps to be enabled.
+///
+/// Both backends block when they have nothing new to offer, and this iterator alternates
+/// between them, so a call to `next()` blocks on whichever backend is up next.
+///
+/// IMPORTANT NOTE: Every emitted entry is remembered for the lifetime of the reader,
+/// so memory use grows with the number of entries read.
+///
+pub struct AggregatedReader {
+    pub klogctl: KLogEntries,
+    pub kmsgfile: KMsgEntriesIter,
+    pub seen_sequences: HashSet<u64>,
+    seen_contents: HashSet<(Duration, String)>,
+    read_klogctl_next: bool,
+}
+
+impl AggregatedReader {
+    /// Opens both backends. Fails if either is unavailable, or if kernel log timestamps are disabled.
+    pub fn new() -> Result<AggregatedReader, RMesgError> {
+        if !klogctl::klog_timestamps_enabled()? {
+            return Err(RMesgError::KLogTimestampsDisabled);
+        }
+
+        Ok(AggregatedReader {
+            klogctl: KLogEntries::with_options(false, klogctl::SUGGESTED_POLL_INTERVAL)?,
+            kmsgfile: KMsgEntriesIter::with_path(None, false)?,
+            seen_sequences: HashSet::new(),
+            seen_contents: HashSet::new(),
+            read_klogctl_next: false,
+        })
+    }
+
+    /// Records the entry as seen, returning whether it was seen before.
+    fn already_seen(&mut self, entry: &Entry) -> bool {
+        let mut seen = false;
+
+        if let Some(sequence_num) = entry.sequence_num {
+            seen |= !self.seen_sequences.insert(sequence_num);
+        }
+
+        if let Some(timestamp) = entry.timestamp_from_system_start {
+            // klogctl messages keep the space that follows the timestamp, /dev/kmsg ones don't
+            let content = (timestamp, entry.message.trim_start().to_owned());
+            seen |= !self.seen_contents.insert(content);
+        }
+
+        seen
+    }
+}
+
+/// Trait to iterate over lines of the kernel log buffer.
+impl Iterator for AggregatedReader {
+    type Item = Result<Entry, RMesgError>;
+
+    /// This is a blocking call, and will use the calling thread to wait on the backends.
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let next = match self.read_klogctl_next {
+                true => self.klogctl.next(),
+                false => self.kmsgfile.next(),
+            };
+            self.read_klogctl_next = !self.read_klogctl_next;
+
+            match next {
+                Some(Ok(entry)) => {
+                    if !self.already_seen(&entry) {
+                        return Some(Ok(entry));
+                    }
+                }
+                other => return other,
+            }
+        }
+    }
+}
+
+/// How many sequence numbers a MergedEntriesIterator remembers, to recognize duplicates
+const MERGED_SEEN_CAPACITY: usize = 1024;
+
+/// Merges the entries from several iterators, such as several readers of the same kernel log,
+/// into one. See `MergedEntriesIterator`.
+pub fn merge_entries(iters: Vec<EntriesIterator>) -> MergedEntriesIterator {
+    let heads = iters.iter().map(|_| None).collect();
+    MergedEntriesIterator {
+        iters: iters.into_iter().map(Some).collect(),
+        heads,
+        seen: HashSet::new(),
+        seen_order: VecDeque::new(),
+        next_index: 0,
+    }
+}
+
+/// Takes entries from each of its iterators in turn, holding one entry per iterator, and emits
+/// the held entry with the lowest sequence number first, skipping sequence numbers it already
+/// emitted (among the last `MERGED_SEEN_CAPACITY`).
+///
+/// Entries without a sequence number (such as those read through klogctl) can't be ordered or
+/// deduplicated, so they are emitted as soon as they're read. So are errors. An iterator is
+/// dropped once it's exhausted.
+///
+/// IMPORTANT NOTE: An entry is only emitted once every iterator that isn't exhausted holds one,
+/// so `next()` blocks on whichever iterator has nothing new to offer.
+///
+pub struct MergedEntriesIterator {
+    iters: Vec<Option<EntriesIterator>>,
+    heads: Vec<Option<Entry>>,
+    seen: HashSet<u64>,
+    seen_order: VecDeque<u64>,
+    next_index: usize,
+}
+
+impl MergedEntriesIterator {
+    /// Records the sequence number as emitted, forgetting the oldest one when there are too many.
+    fn remember(&mut self, sequence_num: u64) {
+        if self.seen.insert(sequence_num) {
+            self.seen_order.push_back(sequence_num);
+            if self.seen_order.len() > MERGED_SEEN_CAPACITY {
+                if let Some(oldest) = self.seen_order.pop_front() {
+                    self.seen.remove(&oldest);
+                }
+            }
+        }
+    }
+}
+
+impl Iterator for MergedEntriesIterator {
+    type Item = Result<Entry, RMesgError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let count = self.iters.len();
+
+        // Make sure every iterator left holds an entry, starting with the one up next
+        for offset in 0..count {
+            let index = (self.next_index + offset) % count;
+            while self.heads[index].is_none() {
+                let iter = match self.iters[index].as_mut() {
+                    Some(iter) => iter,
+                    None => break,
+                };
+
+                match iter.next() {
+                    None => self.iters[index] = None,
+                    Some(Err(e)) => {
+                        self.next_index = (index + 1) % count;
+                        return Some(Err(e));
+                    }
+                    Some(Ok(entry)) => match entry.sequence_num {
+                        None => {
+                            self.next_index = (index + 1) % count;
+                            return Some(Ok(entry));
+                        }
+                        Some(sequence_num) => {
+                            let held = self
+                                .heads
+                                .iter()
+                                .flatten()
+                                .any(|head| head.sequence_num == Some(sequence_num));
+                            if !held && !self.seen.contains(&sequence_num) {
+                                self.heads[index] = Some(entry);
+                            }
+                        }
+                    },
+                }
+            }
+        }
+
+        let lowest = self
+            .heads
+            .iter()
+            .enumerate()
+            .filter_map(|(index, head)| Some((head.as_ref()?.sequence_num, index)))
+            .min()
+            .map(|(_, index)| index)?;
+
+        self.next_index = (lowest + 1) % count;
+        let entry = self.heads[lowest].take()?;
+        if let Some(sequence_num) = entry.sequence_num {
+            self.remember(sequence_num);
+        }
+        Some(Ok(entry))
+    }
+}
+
+/**********************************************************************************/
+// Tests! Tests! Tests!
+
+#[cfg(all(test, target_os = "linux"))]
+mod test {
+    use super::*;
+    use crate::kmsgfile;
+
+    fn entries(lines: &[&str]) -> EntriesIterator {
+        let results: Vec<Result<Entry, RMesgError>> = lines
+            .iter()
+            .map(|line| Ok(kmsgfile::entry_from_line(line)?))
+            .collect();
+        EntriesIterator::from_dyn(Box::new(results.into_iter()))
+    }
+
+    #[test]
+    fn test_merge_entries() {
+        let merged = merge_entries(vec![
+            entries(&["6,1,100,-;one", "6,3,300,-;three", "6,4,400,-;four"]),
+            entries(&[
+                "6,2,200,-;two",
+                "6,3,300,-;three",
+                " no sequence",
+                "6,5,500,-;five",
+            ]),
+            entries(&[]),
+        ]);
+
+        let messages: Vec<String> = merged.map(|entry| entry.unwrap().message).collect();
+        assert_eq!(
+            messages,
+            vec!["one", "two", " no sequence", "three", "four", "five"]
+        );
+    }
+
+    #[test]
+    fn test_merge_entries_passes_errors_through() {
+        let failing: Vec<Result<Entry, RMesgError>> = vec![Err(RMesgError::ReadTimeout)];
+        let merged = merge_entries(vec![
+            entries(&["6,1,100,-;one"]),
+            EntriesIterator::from_dyn(Box::new(failing.into_iter())),
+        ]);
+
+        let results: Vec<Result<Entry, RMesgError>> = merged.collect();
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().any(|r| r.is_err()));
+        assert!(merge_entries(vec![]).next().is_none());
+    }
+
+    #[test]
+    fn test_aggregated_reader() {
+        let reader = match AggregatedReader::new() {
+            Ok(r) => r,
+            // Nothing to aggregate on a system where we can't follow klogctl.
+            Err(RMesgError::KLogTimestampsDisabled) => return,
+            Err(e) => panic!("Unable to open both backends: {}", e),
+        };
+
+        let mut seen = HashSet::new();
+
+        // Read 10 lines and quit
+        for (count, entry) in reader.enumerate() {
+            let entry = entry.unwrap();
+            if let Some(sequence_num) = entry.sequence_num {
+                assert!(seen.insert(sequence_num), "Sequence emitted twice");
+            }
+            if count > 10 {
+                break;
+            }
+        }
+    }
+}
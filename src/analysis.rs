@@ -0,0 +1,377 @@
+use crate::entry::Entry;
+use crate::error::RMesgError;
+
+use std::collections::{HashMap, VecDeque};
+use std::iter::Iterator;
+use std::time::{Duration, Instant};
+
+/// Passes entries through unchanged, while watching how often entries matching `predicate`
+/// arrive: whenever more than `max_count` of them arrive within `window`,
+/// `on_threshold_exceeded` is called with the number of matching entries in the window.
+///
+/// The window is measured by when entries are read, not by their kernel timestamps,
+/// so this detects bursts as they happen.
+pub struct SlidingWindowRateDetector<I, F>
+where
+    I: Iterator<Item = Result<Entry, RMesgError>>,
+    F: Fn(&Entry) -> bool,
+{
+    pub inner: I,
+    pub predicate: F,
+    pub max_count: usize,
+    pub window: Duration,
+    pub on_threshold_exceeded: Box<dyn Fn(usize)>,
+    timestamps: VecDeque<Instant>,
+}
+
+impl<I, F> SlidingWindowRateDetector<I, F>
+where
+    I: Iterator<Item = Result<Entry, RMesgError>>,
+    F: Fn(&Entry) -> bool,
+{
+    pub fn new(
+        inner: I,
+        predicate: F,
+        max_count: usize,
+        window: Duration,
+        on_threshold_exceeded: Box<dyn Fn(usize)>,
+    ) -> Self {
+        SlidingWindowRateDetector {
+            inner,
+            predicate,
+            max_count,
+            window,
+            on_threshold_exceeded,
+            timestamps: VecDeque::new(),
+        }
+    }
+
+    fn record(&mut self, now: Instant) {
+        while let Some(oldest) = self.timestamps.front() {
+            if now.duration_since(*oldest) <= self.window {
+                break;
+            }
+            self.timestamps.pop_front();
+        }
+
+        self.timestamps.push_back(now);
+
+        if self.timestamps.len() > self.max_count {
+            (self.on_threshold_exceeded)(self.timestamps.len());
+        }
+    }
+}
+
+impl<I, F> Iterator for SlidingWindowRateDetector<I, F>
+where
+    I: Iterator<Item = Result<Entry, RMesgError>>,
+    F: Fn(&Entry) -> bool,
+{
+    type Item = Result<Entry, RMesgError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let next = self.inner.next();
+
+        if let Some(Ok(entry)) = &next {
+            if (self.predicate)(entry) {
+                self.record(Instant::now());
+            }
+        }
+
+        next
+    }
+}
+
+/// Groups similar messages together by replacing hexadecimal numbers (`0x` followed by
+/// hex digits) and runs of decimal digits with `#`, so that messages differing only in
+/// addresses, PIDs, port numbers and such normalize to the same text.
+pub fn normalize_message(message: &str) -> String {
+    let mut normalized = String::with_capacity(message.len());
+    let mut chars = message.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if !c.is_ascii_digit() {
+            normalized.push(c);
+            continue;
+        }
+
+        if c == '0' && chars.peek() == Some(&'x') {
+            let mut lookahead = chars.clone();
+            lookahead.next();
+            if lookahead.peek().is_some_and(char::is_ascii_hexdigit) {
+                chars = lookahead;
+                while chars.next_if(char::is_ascii_hexdigit).is_some() {}
+                normalized.push('#');
+                continue;
+            }
+        }
+
+        while chars.next_if(char::is_ascii_digit).is_some() {}
+        normalized.push('#');
+    }
+
+    normalized
+}
+
+/// The `n` most common messages, after normalizing them with `normalize_message`,
+/// along with how many times each appeared. Most common first.
+pub fn top_n_by_frequency(entries: &[Entry], n: usize) -> Vec<(String, usize)> {
+    top_n_by_frequency_with(entries, n, normalize_message)
+}
+
+/// Like `top_n_by_frequency`, but groups messages by the text `normalizer` returns for them.
+/// Messages that are equally common are ordered by their normalized text.
+pub fn top_n_by_frequency_with<F>(
+    entries: &[Entry],
+    n: usize,
+    normalizer: F,
+) -> Vec<(String, usize)>
+where
+    F: Fn(&str) -> String,
+{
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for entry in entries {
+        *counts.entry(normalizer(&entry.message)).or_insert(0) += 1;
+    }
+
+    let mut counts: Vec<(String, usize)> = counts.into_iter().collect();
+    counts.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then_with(|| a.cmp(b)));
+    counts.truncate(n);
+    counts
+}
+
+/// Where `cluster_by_time` puts entries without a timestamp (such as continuation lines).
+#[derive(Debug, Default, PartialEq, Eq, Copy, Clone)]
+pub enum TimestampMissingPolicy {
+    /// In the same cluster as the entry before them
+    #[default]
+    Preceding,
+    /// All together, in a cluster of their own after every other cluster
+    Separate,
+}
+
+/// Groups entries into bursts: consecutive entries (in the order given) whose timestamps
+/// are no more than `max_gap` apart go into the same cluster.
+///
+/// Entries without a timestamp join the cluster of the entry before them,
+/// see `cluster_by_time_with_policy` for the alternative.
+pub fn cluster_by_time(entries: &[Entry], max_gap: Duration) -> Vec<Vec<&Entry>> {
+    cluster_by_time_with_policy(entries, max_gap, TimestampMissingPolicy::default())
+}
+
+/// Like `cluster_by_time`, with `timestamp_missing_policy` deciding where entries
+/// without a timestamp go.
+pub fn cluster_by_time_with_policy(
+    entries: &[Entry],
+    max_gap: Duration,
+    timestamp_missing_policy: TimestampMissingPolicy,
+) -> Vec<Vec<&Entry>> {
+    let mut clusters: Vec<Vec<&Entry>> = Vec::new();
+    let mut untimed = Vec::new();
+    let mut previous: Option<Duration> = None;
+
+    for entry in entries {
+        let timestamp = match entry.timestamp_from_system_start {
+            Some(timestamp) => timestamp,
+            None => {
+                match (timestamp_missing_policy, clusters.last_mut()) {
+                    (TimestampMissingPolicy::Preceding, Some(cluster)) => cluster.push(entry),
+                    (TimestampMissingPolicy::Preceding, None) => clusters.push(vec![entry]),
+                    (TimestampMissingPolicy::Separate, _) => untimed.push(entry),
+                }
+                continue;
+            }
+        };
+
+        let starts_cluster = match previous {
+            Some(previous) => {
+                let gap = match timestamp > previous {
+                    true => timestamp - previous,
+                    false => previous - timestamp,
+                };
+                gap > max_gap
+            }
+            // Untimed entries leading the way join the first burst
+            None => clusters.is_empty(),
+        };
+
+        match (starts_cluster, clusters.last_mut()) {
+            (false, Some(cluster)) => cluster.push(entry),
+            _ => clusters.push(vec![entry]),
+        }
+        previous = Some(timestamp);
+    }
+
+    if !untimed.is_empty() {
+        clusters.push(untimed);
+    }
+
+    clusters
+}
+
+/**********************************************************************************/
+// Tests! Tests! Tests!
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    #[test]
+    fn test_threshold_exceeded() {
+        let alerts = Rc::new(RefCell::new(vec![]));
+        let alerts_clone = alerts.clone();
+
+        let entries = vec![
+            Ok(Entry::builder().message("segfault").build()),
+            Ok(Entry::builder()
+                .message("docker0: port 2(veth98d5024) entered disabled state")
+                .build()),
+            Ok(Entry::builder().message("segfault").build()),
+            Ok(Entry::builder().message("segfault").build()),
+            Ok(Entry::builder().message("segfault").build()),
+        ];
+
+        let detector = SlidingWindowRateDetector::new(
+            entries.into_iter(),
+            |entry: &Entry| entry.message.contains("segfault"),
+            2,
+            Duration::from_secs(60),
+            Box::new(move |count| alerts_clone.borrow_mut().push(count)),
+        );
+
+        // Every entry passes through
+        assert_eq!(detector.count(), 5);
+        assert_eq!(*alerts.borrow(), vec![3, 4]);
+    }
+
+    #[test]
+    fn test_window_slides() {
+        let alerts = Rc::new(RefCell::new(vec![]));
+        let alerts_clone = alerts.clone();
+
+        let mut detector = SlidingWindowRateDetector::new(
+            vec![Ok(Entry::builder().message("x").build())].into_iter(),
+            |_: &Entry| true,
+            1,
+            Duration::from_secs(1),
+            Box::new(move |count| alerts_clone.borrow_mut().push(count)),
+        );
+
+        let start = Instant::now();
+        detector.record(start);
+        detector.record(start + Duration::from_secs(2));
+        assert!(alerts.borrow().is_empty());
+
+        detector.record(start + Duration::from_millis(2500));
+        assert_eq!(*alerts.borrow(), vec![2]);
+    }
+
+    #[test]
+    fn test_normalize_message() {
+        assert_eq!(
+            normalize_message("docker0: port 2(veth98d5024) entered disabled state"),
+            "docker#: port #(veth#d#) entered disabled state"
+        );
+        assert_eq!(
+            normalize_message("segfault at 0x7ffd3c2e ip 00007f sp 0xdeadBEEF"),
+            "segfault at # ip #f sp #"
+        );
+        assert_eq!(normalize_message("0x, 0xg and box"), "#x, #xg and box");
+        assert_eq!(normalize_message(""), "");
+    }
+
+    #[test]
+    fn test_top_n_by_frequency() {
+        let entries: Vec<Entry> = vec![
+            Entry::builder()
+                .message("Out of memory: Killed process 4054 (a.out)")
+                .build(),
+            Entry::builder()
+                .message("usb 1-1: new high-speed USB device number 2 using xhci_hcd")
+                .build(),
+            Entry::builder()
+                .message("Out of memory: Killed process 17 (b.out)")
+                .build(),
+            Entry::builder().message("segfault at 0x7ffd3c2e").build(),
+            Entry::builder()
+                .message("Out of memory: Killed process 99 (a.out)")
+                .build(),
+            Entry::builder().message("segfault at 0x0").build(),
+        ];
+
+        assert_eq!(
+            top_n_by_frequency(&entries, 2),
+            vec![
+                ("Out of memory: Killed process # (a.out)".to_owned(), 2),
+                ("segfault at #".to_owned(), 2),
+            ]
+        );
+        assert_eq!(top_n_by_frequency(&entries, 10).len(), 4);
+        assert!(top_n_by_frequency(&entries, 0).is_empty());
+
+        let by_first_word = top_n_by_frequency_with(&entries, 1, |message| {
+            message
+                .split_whitespace()
+                .next()
+                .unwrap_or_default()
+                .to_owned()
+        });
+        assert_eq!(by_first_word, vec![("Out".to_owned(), 3)]);
+    }
+
+    fn timed(millis: Option<u64>, message: &str) -> Entry {
+        Entry::builder()
+            .maybe_timestamp(millis.map(Duration::from_millis))
+            .message(message)
+            .build()
+    }
+
+    fn messages(clusters: Vec<Vec<&Entry>>) -> Vec<Vec<&str>> {
+        clusters
+            .into_iter()
+            .map(|c| c.into_iter().map(|e| e.message.as_str()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_cluster_by_time() {
+        let entries = vec![
+            timed(None, "leading"),
+            timed(Some(1000), "a1"),
+            timed(Some(1002), "a2"),
+            timed(None, " LINE2=continuation"),
+            timed(Some(1010), "a3"),
+            timed(Some(5000), "b1"),
+            timed(Some(9000), "c1"),
+            timed(Some(9001), "c2"),
+        ];
+        let max_gap = Duration::from_millis(10);
+
+        assert_eq!(
+            messages(cluster_by_time(&entries, max_gap)),
+            vec![
+                vec!["leading", "a1", "a2", " LINE2=continuation", "a3"],
+                vec!["b1"],
+                vec!["c1", "c2"],
+            ]
+        );
+
+        assert_eq!(
+            messages(cluster_by_time_with_policy(
+                &entries,
+                max_gap,
+                TimestampMissingPolicy::Separate
+            )),
+            vec![
+                vec!["a1", "a2", "a3"],
+                vec!["b1"],
+                vec!["c1", "c2"],
+                vec!["leading", " LINE2=continuation"],
+            ]
+        );
+
+        assert!(cluster_by_time(&[], max_gap).is_empty());
+    }
+}
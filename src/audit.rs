@@ -0,0 +1,163 @@
+use crate::entry::Entry;
+use crate::error::RMesgError;
+use crate::EntriesIterator;
+
+use std::collections::HashMap;
+use std::iter::Iterator;
+
+/// A message from the kernel audit subsystem, as found in the kernel log:
+/// `audit: type=1400 audit(1614000000.123:456): apparmor="DENIED" operation="open" ...`
+#[derive(Debug, PartialEq, Clone)]
+pub struct AuditEntry {
+    /// Seconds since the epoch at which the event was audited
+    pub timestamp: f64,
+    /// Identifies the event: all records of the same event share a serial
+    pub serial: u64,
+    /// The key=value pairs of the record (including `type`), with quotes removed from values
+    pub fields: HashMap<String, String>,
+}
+
+/// Parses the entry's message as an audit record, or returns `None` if it isn't one.
+pub fn entry_from_audit_message(entry: &Entry) -> Option<AuditEntry> {
+    let message = entry.message.as_str();
+
+    let start = message.find("audit(")?;
+    let (header, rest) = message[start + "audit(".len()..].split_once("):")?;
+    let (timestamp, serial) = header.split_once(':')?;
+
+    let mut fields = parse_fields(&message[..start]);
+    fields.extend(parse_fields(rest));
+
+    Some(AuditEntry {
+        timestamp: timestamp.parse().ok()?,
+        serial: serial.parse().ok()?,
+        fields,
+    })
+}
+
+/// Parses whitespace-separated key=value pairs, where values may be double- or single-quoted
+/// (and then contain whitespace, or key=value pairs of their own, as `msg='...'` does).
+/// Words that aren't key=value pairs are skipped.
+fn parse_fields(s: &str) -> HashMap<String, String> {
+    let mut fields = HashMap::new();
+    let mut rest = s.trim_start();
+
+    while !rest.is_empty() {
+        let word_end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let equals = match rest[..word_end].find('=') {
+            Some(equals) => equals,
+            None => {
+                rest = rest[word_end..].trim_start();
+                continue;
+            }
+        };
+
+        let key = &rest[..equals];
+        let after_equals = &rest[equals + 1..];
+
+        let (value, remaining) = match after_equals.chars().next() {
+            Some(quote @ '"') | Some(quote @ '\'') => {
+                let quoted = &after_equals[1..];
+                match quoted.find(quote) {
+                    Some(end) => (&quoted[..end], &quoted[end + 1..]),
+                    None => (quoted, ""),
+                }
+            }
+            _ => {
+                let end = after_equals
+                    .find(char::is_whitespace)
+                    .unwrap_or(after_equals.len());
+                (&after_equals[..end], &after_equals[end..])
+            }
+        };
+
+        fields.insert(key.to_owned(), value.to_owned());
+        rest = remaining.trim_start();
+    }
+
+    fields
+}
+
+impl EntriesIterator {
+    /// Keeps only audit records, parsed into AuditEntries. Errors are passed through.
+    pub fn filter_audit_entries(self) -> impl Iterator<Item = Result<AuditEntry, RMesgError>> {
+        self.filter_map(|entry| match entry {
+            Ok(entry) => entry_from_audit_message(&entry).map(Ok),
+            Err(e) => Some(Err(e)),
+        })
+    }
+}
+
+/**********************************************************************************/
+// Tests! Tests! Tests!
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_apparmor_denial() {
+        let audit_entry = entry_from_audit_message(
+            &Entry::builder()
+                .message(
+                    r#"audit: type=1400 audit(1614000000.123:456): apparmor="DENIED" operation="open" profile="/usr/sbin/cupsd" name="/etc/ssl/openssl.cnf" pid=1234 comm="cupsd" requested_mask="r" denied_mask="r" fsuid=0 ouid=0"#,
+                )
+                .build(),
+        )
+        .unwrap();
+
+        assert_eq!(audit_entry.timestamp, 1614000000.123);
+        assert_eq!(audit_entry.serial, 456);
+        assert_eq!(audit_entry.fields["type"], "1400");
+        assert_eq!(audit_entry.fields["apparmor"], "DENIED");
+        assert_eq!(audit_entry.fields["profile"], "/usr/sbin/cupsd");
+        assert_eq!(audit_entry.fields["pid"], "1234");
+        assert_eq!(audit_entry.fields["ouid"], "0");
+        assert_eq!(audit_entry.fields.len(), 11);
+    }
+
+    #[test]
+    fn test_quoted_whitespace() {
+        let audit_entry = entry_from_audit_message(
+            &Entry::builder()
+                .message(
+                    r#"audit: type=1130 audit(1.5:7): pid=1 msg='unit=foo comm="systemd" exe="/usr/lib/systemd/systemd" res=success' comm="a b"  exe=/bin/x"#,
+                )
+                .build(),
+        )
+        .unwrap();
+
+        assert_eq!(audit_entry.fields["comm"], "a b");
+        assert_eq!(
+            audit_entry.fields["msg"],
+            r#"unit=foo comm="systemd" exe="/usr/lib/systemd/systemd" res=success"#
+        );
+        assert_eq!(audit_entry.fields["exe"], "/bin/x");
+        assert!(!audit_entry.fields.contains_key("unit"));
+        assert!(!audit_entry.fields.contains_key("res"));
+    }
+
+    #[test]
+    fn test_not_audit() {
+        assert_eq!(
+            entry_from_audit_message(
+                &Entry::builder()
+                    .message("docker0: port 2(veth98d5024) entered disabled state")
+                    .build()
+            ),
+            None
+        );
+        assert_eq!(
+            entry_from_audit_message(
+                &Entry::builder()
+                    .message("audit(notatimestamp:1): a=b")
+                    .build()
+            ),
+            None
+        );
+        assert_eq!(
+            entry_from_audit_message(&Entry::builder().message("audit(1.5:7").build()),
+            None
+        );
+    }
+}
@@ -0,0 +1,204 @@
+use crate::entry::{Entry, EntryCategory, LogFacility, LogLevel};
+use crate::error::RMesgError;
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::iter::Iterator;
+use std::time::Duration;
+
+/// Marks a field of a CompactEntry that the original Entry didn't have
+pub const NONE_U16: u16 = u16::MAX;
+/// Marks a field of a CompactEntry that the original Entry didn't have
+pub const NONE_U32: u32 = u32::MAX;
+
+/// A time-contiguous run of entries, stored compactly: timestamps and sequence numbers
+/// are kept as offsets from those of the batch, in 32 bits each.
+///
+/// Timestamp offsets cover a little over an hour, which is the most a batch can span.
+#[derive(Debug, PartialEq, Clone)]
+pub struct EntryBatch {
+    pub base_timestamp_us: u64,
+    pub base_sequence_num: u64,
+    pub entries: Vec<CompactEntry>,
+}
+
+/// An entry of an EntryBatch, relative to the batch
+#[derive(Debug, PartialEq, Clone)]
+pub struct CompactEntry {
+    /// Microseconds since the batch's base timestamp, or NONE_U32
+    pub timestamp_offset_us: u32,
+    /// Entries since the batch's base sequence number, or NONE_U32
+    pub sequence_offset: u32,
+    /// Facility and level, as in `Entry::to_faclev`, or NONE_U16
+    pub facility_level: u16,
+    pub category: EntryCategory,
+    pub message: String,
+}
+
+impl EntryBatch {
+    /// Packs the entries into a batch, failing when their timestamps or sequence numbers
+    /// are too far apart to be stored as offsets.
+    pub fn from_entries(entries: &[Entry]) -> Result<Self, RMesgError> {
+        let base_timestamp_us = entries
+            .iter()
+            .filter_map(|e| e.timestamp_from_system_start)
+            .map(|ts| ts.as_micros() as u64)
+            .min()
+            .unwrap_or(0);
+        let base_sequence_num = entries
+            .iter()
+            .filter_map(|e| e.sequence_num)
+            .min()
+            .unwrap_or(0);
+
+        let compact_entries: Result<Vec<CompactEntry>, RMesgError> = entries
+            .iter()
+            .map(|entry| {
+                let timestamp_offset_us = match entry.timestamp_from_system_start {
+                    Some(ts) => offset("timestamp", ts.as_micros() as u64 - base_timestamp_us)?,
+                    None => NONE_U32,
+                };
+                let sequence_offset = match entry.sequence_num {
+                    Some(seq) => offset("sequence number", seq - base_sequence_num)?,
+                    None => NONE_U32,
+                };
+
+                Ok(CompactEntry {
+                    timestamp_offset_us,
+                    sequence_offset,
+                    // At most 255 * 8 + 7, so it fits
+                    facility_level: entry.to_faclev().map_or(NONE_U16, |faclev| faclev as u16),
+                    category: entry.category,
+                    message: entry.message.clone(),
+                })
+            })
+            .collect();
+
+        Ok(EntryBatch {
+            base_timestamp_us,
+            base_sequence_num,
+            entries: compact_entries?,
+        })
+    }
+
+    /// Unpacks the entries on the fly.
+    pub fn iter(&self) -> impl Iterator<Item = Entry> + '_ {
+        self.entries.iter().map(move |compact| {
+            let (facility, level) = match compact.facility_level {
+                NONE_U16 => (None, None),
+                faclev => (
+                    Some(LogFacility::from_u8((faclev >> 3) as u8)),
+                    LogLevel::from_u8((faclev & 0b111) as u8),
+                ),
+            };
+
+            Entry {
+                facility,
+                level,
+                sequence_num: match compact.sequence_offset {
+                    NONE_U32 => None,
+                    offset => Some(self.base_sequence_num + offset as u64),
+                },
+                timestamp_from_system_start: match compact.timestamp_offset_us {
+                    NONE_U32 => None,
+                    offset => Some(Duration::from_micros(
+                        self.base_timestamp_us + offset as u64,
+                    )),
+                },
+                message: compact.message.clone(),
+                category: compact.category,
+                // The flags aren't stored
+                raw_flags: None,
+                dict: HashMap::new(),
+                source_backend: None,
+            }
+        })
+    }
+}
+
+fn offset(field: &str, offset: u64) -> Result<u32, RMesgError> {
+    match u32::try_from(offset) {
+        Ok(offset) if offset != NONE_U32 => Ok(offset),
+        _ => Err(RMesgError::IntegerOutOfBound(format!(
+            "The {} offset {} is too large for an EntryBatch",
+            field, offset
+        ))),
+    }
+}
+
+/**********************************************************************************/
+// Tests! Tests! Tests!
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let entries = vec![
+            Entry::builder()
+                .facility(LogFacility::Kern)
+                .level(LogLevel::Warning)
+                .sequence_num(1000)
+                .timestamp(Duration::from_micros(91650777797))
+                .message("first")
+                .build(),
+            Entry::builder().message(" LINE2=continuation").build(),
+            Entry::builder()
+                .facility(LogFacility::Kern)
+                .level(LogLevel::Warning)
+                .sequence_num(1001)
+                .timestamp(Duration::from_micros(91650777900))
+                .message("second")
+                .build(),
+            Entry::builder()
+                .facility(LogFacility::Kern)
+                .level(LogLevel::Warning)
+                .sequence_num(1002)
+                .timestamp(Duration::from_micros(91650777900))
+                .message("third")
+                .build(),
+            Entry::builder()
+                .facility(LogFacility::Kern)
+                .level(LogLevel::Warning)
+                .sequence_num(1003)
+                .timestamp(Duration::from_micros(91650778000))
+                .category(EntryCategory::OomKill)
+                .message("Out of memory: Killed process 4054")
+                .build(),
+            // The largest facility and level
+            Entry::builder()
+                .facility(LogFacility::Other(255))
+                .level(LogLevel::Debug)
+                .message("from userspace")
+                .build(),
+        ];
+
+        let batch = EntryBatch::from_entries(&entries).unwrap();
+        assert_eq!(batch.base_timestamp_us, 91650777797);
+        assert_eq!(batch.base_sequence_num, 1000);
+        assert_eq!(batch.entries[2].timestamp_offset_us, 103);
+        assert_eq!(batch.entries[2].sequence_offset, 1);
+        assert_eq!(batch.entries[1].facility_level, NONE_U16);
+
+        let unpacked: Vec<Entry> = batch.iter().collect();
+        assert_eq!(unpacked, entries);
+    }
+
+    #[test]
+    fn test_span_too_large() {
+        let entries = vec![
+            Entry::builder()
+                .sequence_num(1)
+                .timestamp(Duration::from_micros(0))
+                .message("first")
+                .build(),
+            Entry::builder()
+                .sequence_num(2)
+                .timestamp(Duration::from_micros(u32::MAX as u64))
+                .message("too late")
+                .build(),
+        ];
+        assert!(EntryBatch::from_entries(&entries).is_err());
+    }
+}
@@ -0,0 +1,74 @@
+use crate::error::RMesgError;
+
+use std::cell::OnceCell;
+use std::time::{Duration, SystemTime};
+
+thread_local! {
+    static BOOT_TIME: OnceCell<SystemTime> = const { OnceCell::new() };
+}
+
+/// Returns the wall-clock time at which the system booted, reading it once per thread.
+///
+/// The boot time does not change while the system is up, so the cached value is valid
+/// for the lifetime of the process. Use this rather than `boot_time()` when converting the
+/// `timestamp_from_system_start` of many entries into wall-clock times.
+pub fn boot_time_cached() -> Result<SystemTime, RMesgError> {
+    BOOT_TIME.with(|cell| {
+        if let Some(boot_time) = cell.get() {
+            return Ok(*boot_time);
+        }
+
+        let boot_time = boot_time()?;
+        let _ = cell.set(boot_time);
+        Ok(boot_time)
+    })
+}
+
+/// Computes the wall-clock time at which the system booted, by subtracting the time
+/// since boot (`CLOCK_BOOTTIME`, which includes time spent suspended) from the current time.
+pub fn boot_time() -> Result<SystemTime, RMesgError> {
+    let uptime = time_since_boot()?;
+
+    match SystemTime::now().checked_sub(uptime) {
+        Some(boot_time) => Ok(boot_time),
+        None => Err(RMesgError::UnableToAddDurationToSystemTime),
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn time_since_boot() -> Result<Duration, RMesgError> {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+
+    if unsafe { libc::clock_gettime(libc::CLOCK_BOOTTIME, &mut ts) } != 0 {
+        return Err(RMesgError::UnableToObtainSystemTime);
+    }
+
+    Ok(Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn time_since_boot() -> Result<Duration, RMesgError> {
+    Err(RMesgError::NotImplementedForThisPlatform)
+}
+
+/**********************************************************************************/
+// Tests! Tests! Tests!
+
+#[cfg(all(test, target_os = "linux"))]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_boot_time_cached() {
+        let first = boot_time_cached().unwrap();
+        let second = boot_time_cached().unwrap();
+        assert_eq!(first, second, "Cached boot time should not change");
+        assert!(
+            first < SystemTime::now(),
+            "System should have booted in the past"
+        );
+    }
+}
@@ -0,0 +1,127 @@
+use crate::entry::Entry;
+use crate::error::RMesgError;
+use crate::EntriesIterator;
+
+#[cfg(feature = "tokio")]
+use std::iter::Iterator;
+use std::sync::mpsc::{self, Receiver as StdReceiver, SyncSender};
+use std::thread::{self, JoinHandle};
+#[cfg(feature = "tokio")]
+use tokio::sync::mpsc::Receiver;
+
+/// Iterates over entries received from a tokio channel, letting synchronous code consume
+/// entries produced by asynchronous code (or entries fed in by a test).
+///
+/// IMPORTANT NOTE: Each call to `next()` blocks the calling thread until an entry arrives,
+/// so it must not be called from within an asynchronous context. The iterator ends once
+/// every sender has been dropped.
+#[cfg(feature = "tokio")]
+#[derive(Debug)]
+pub struct MpscReceiverEntriesIter {
+    pub receiver: Receiver<Entry>,
+}
+
+#[cfg(feature = "tokio")]
+impl Iterator for MpscReceiverEntriesIter {
+    type Item = Result<Entry, RMesgError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.receiver.blocking_recv().map(Ok)
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl EntriesIterator {
+    pub fn from_mpsc_receiver(rx: Receiver<Entry>) -> EntriesIterator {
+        EntriesIterator::from_dyn(Box::new(MpscReceiverEntriesIter { receiver: rx }))
+    }
+}
+
+impl EntriesIterator {
+    /// Drives the iterator on a thread of its own, sending each item through a channel
+    /// holding at most `buffer` items, so reading entries and processing them don't hold
+    /// each other up.
+    ///
+    /// The thread exits once the iterator is exhausted, or once the receiver is dropped.
+    /// Joining the returned handle waits for that. The returned sender can be used to send
+    /// more items to the same receiver; the receiver only ends once it's dropped too.
+    #[allow(clippy::type_complexity)]
+    pub fn into_channel(
+        self,
+        buffer: usize,
+    ) -> (
+        JoinHandle<()>,
+        SyncSender<Result<Entry, RMesgError>>,
+        StdReceiver<Result<Entry, RMesgError>>,
+    ) {
+        let (tx, rx) = mpsc::sync_channel(buffer);
+
+        let thread_tx = tx.clone();
+        let handle = thread::spawn(move || {
+            for item in self {
+                if thread_tx.send(item).is_err() {
+                    break;
+                }
+            }
+        });
+
+        (handle, tx, rx)
+    }
+}
+
+/**********************************************************************************/
+// Tests! Tests! Tests!
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    #[cfg(feature = "tokio")]
+    use tokio::sync::mpsc;
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn test_from_mpsc_receiver() {
+        let (tx, rx) = mpsc::channel(1);
+
+        let sender = thread::spawn(move || {
+            for message in ["first", "second", "third"] {
+                let entry = Entry::builder().message(message).build();
+                tx.blocking_send(entry).unwrap();
+            }
+        });
+
+        let messages: Vec<String> = EntriesIterator::from_mpsc_receiver(rx)
+            .map(|e| e.unwrap().message)
+            .collect();
+        assert_eq!(messages, vec!["first", "second", "third"]);
+
+        sender.join().unwrap();
+    }
+
+    #[test]
+    fn test_into_channel() {
+        let entries: Vec<Result<Entry, RMesgError>> = (0..20)
+            .map(|i| Ok(Entry::builder().message(&i.to_string()).build()))
+            .collect();
+
+        let (handle, tx, rx) =
+            EntriesIterator::from_dyn(Box::new(entries.into_iter())).into_channel(4);
+        drop(tx);
+
+        let messages: Vec<String> = rx.iter().map(|e| e.unwrap().message).collect();
+        assert_eq!(messages, (0..20).map(|i| i.to_string()).collect::<Vec<_>>());
+
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn test_into_channel_stops_when_receiver_dropped() {
+        let entries = std::iter::repeat_with(|| Ok(Entry::builder().message("forever").build()));
+
+        let (handle, _tx, rx) = EntriesIterator::from_dyn(Box::new(entries)).into_channel(1);
+        assert_eq!(rx.recv().unwrap().unwrap().message, "forever");
+        drop(rx);
+
+        handle.join().unwrap();
+    }
+}
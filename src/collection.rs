@@ -0,0 +1,95 @@
+use crate::entry::Entry;
+
+use std::iter::Iterator;
+
+/// Entries sorted into buckets by level, for post-analysis.
+///
+/// `per_level[0]` holds Emergency entries, through to `per_level[7]` for Debug entries.
+/// Entries without a level (such as continuation lines) go into `unleveled`.
+/// Entries keep their relative order within each bucket.
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct PartitionedEntries {
+    pub per_level: [Vec<Entry>; 8],
+    pub unleveled: Vec<Entry>,
+}
+
+impl PartitionedEntries {
+    pub fn from_entries(entries: Vec<Entry>) -> Self {
+        let mut partitioned = PartitionedEntries::default();
+
+        for entry in entries {
+            match entry.level {
+                Some(level) => partitioned.per_level[level as usize].push(entry),
+                None => partitioned.unleveled.push(entry),
+            }
+        }
+
+        partitioned
+    }
+
+    /// Emergency, Alert, Critical and Error entries, in that order.
+    pub fn errors_and_above(&self) -> impl Iterator<Item = &Entry> {
+        self.per_level[..4].iter().flatten()
+    }
+}
+
+/**********************************************************************************/
+// Tests! Tests! Tests!
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::entry::LogLevel;
+
+    #[test]
+    fn test_partition_by_level() {
+        let partitioned = PartitionedEntries::from_entries(vec![
+            Entry::builder()
+                .level(LogLevel::Info)
+                .message("info 1")
+                .build(),
+            Entry::builder()
+                .level(LogLevel::Error)
+                .message("error")
+                .build(),
+            Entry::builder().message(" LINE2=continuation").build(),
+            Entry::builder()
+                .level(LogLevel::Emergency)
+                .message("emergency")
+                .build(),
+            Entry::builder()
+                .level(LogLevel::Info)
+                .message("info 2")
+                .build(),
+            Entry::builder()
+                .level(LogLevel::Warning)
+                .message("warning")
+                .build(),
+        ]);
+
+        assert_eq!(
+            partitioned.per_level[LogLevel::Info as usize],
+            vec![
+                Entry::builder()
+                    .level(LogLevel::Info)
+                    .message("info 1")
+                    .build(),
+                Entry::builder()
+                    .level(LogLevel::Info)
+                    .message("info 2")
+                    .build()
+            ]
+        );
+        assert_eq!(partitioned.per_level[7], vec![]);
+        assert_eq!(
+            partitioned.unleveled,
+            vec![Entry::builder().message(" LINE2=continuation").build()]
+        );
+
+        let errors: Vec<&str> = partitioned
+            .errors_and_above()
+            .map(|e| e.message.as_str())
+            .collect();
+        assert_eq!(errors, vec!["emergency", "error"]);
+    }
+}
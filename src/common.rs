@@ -1,62 +1,288 @@
 use crate::entry::{EntryParsingError, LogFacility, LogLevel};
 use num::FromPrimitive;
-use std::any::type_name;
-use std::fmt::Display;
+use std::collections::HashMap;
 use std::str::FromStr;
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 const LEVEL_MASK: u32 = (1 << 3) - 1;
 
+/// Timestamps beyond 100 years of uptime come from buggy kernel clocks, not real uptime.
+pub const MAX_PLAUSIBLE_UPTIME_MICROSECS: u64 = 100 * 365 * 24 * 60 * 60 * 1_000_000;
+
 pub fn parse_favlecstr(
     faclevstr: &str,
     line: &str,
 ) -> Result<(Option<LogFacility>, Option<LogLevel>), EntryParsingError> {
-    let faclev = parse_fragment::<u32>(faclevstr, line)?;
+    parse_faclev(
+        parse_fragment::<u32>(faclevstr, Field::FacilityLevel, line)?,
+        line,
+    )
+}
+
+pub fn parse_faclev(
+    faclev: u32,
+    line: &str,
+) -> Result<(Option<LogFacility>, Option<LogLevel>), EntryParsingError> {
     // facility is top 28 bits, log level is bottom 3 bits
     match (
         LogFacility::from_u32(faclev >> 3),
         LogLevel::from_u32(faclev & LEVEL_MASK),
     ) {
         (Some(facility), Some(level)) => Ok((Some(facility), Some(level))),
-        _ => Err(EntryParsingError::Generic(format!(
-            "Unable to parse {} into log facility and level. Line: {}",
-            faclev, line
-        ))),
+        _ => Err(EntryParsingError::InvalidFacilityLevel {
+            raw: faclev.to_string(),
+            line: line.to_owned(),
+        }),
     }
 }
 
+/// The KEY=VALUE pairs amongst the comma-separated flags of a /dev/kmsg line,
+/// such as `-,SUBSYSTEM=usb,DEVICE=+usb:1-1`. Other flags are skipped.
+pub fn parse_flags_dict(raw_flags: &str) -> HashMap<String, String> {
+    raw_flags
+        .split(',')
+        .filter_map(|flag| flag.split_once('='))
+        .map(|(key, value)| (key.trim().to_owned(), value.trim().to_owned()))
+        .collect()
+}
+
 pub fn parse_timestamp_secs(
     timestampstr: &str,
     line: &str,
 ) -> Result<Option<Duration>, EntryParsingError> {
     Ok(Some(Duration::from_secs_f64(parse_fragment::<f64>(
         timestampstr,
+        Field::Timestamp,
         line,
     )?)))
 }
 
+#[cfg(rmesg_regex_parser)]
 pub fn parse_timestamp_microsecs(
     timestampstr: &str,
     line: &str,
 ) -> Result<Option<Duration>, EntryParsingError> {
-    Ok(Some(Duration::from_micros(parse_fragment::<u64>(
+    Ok(timestamp_from_microsecs(parse_fragment::<u64>(
         timestampstr,
+        Field::Timestamp,
         line,
-    )?)))
+    )?))
 }
 
-pub fn parse_fragment<N: FromStr>(frag: &str, line: &str) -> Result<N, EntryParsingError>
-where
-    N::Err: Display,
-{
+/// Timestamps past `MAX_PLAUSIBLE_UPTIME_MICROSECS` are treated as absent, with a warning.
+pub fn timestamp_from_microsecs(microsecs: u64) -> Option<Duration> {
+    if microsecs > MAX_PLAUSIBLE_UPTIME_MICROSECS {
+        eprintln!(
+            "WARNING: Ignoring implausible kernel log timestamp of {} microseconds since system start",
+            microsecs
+        );
+        return None;
+    }
+
+    Some(Duration::from_micros(microsecs))
+}
+
+/// Matches the characters in the POSIX `[[:space:]]` class, which the regex-based parsers skip.
+pub fn is_posix_space(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\n' | '\x0B' | '\x0C' | '\r')
+}
+
+/// Whether the fragment is made up of nothing but ASCII digits (`[[:digit:]]*`)
+pub fn is_digits(frag: &str) -> bool {
+    frag.bytes().all(|b| b.is_ascii_digit())
+}
+
+/// The header field a fragment of a line holds, which says which EntryParsingError to
+/// return when it doesn't parse.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Field {
+    FacilityLevel,
+    SequenceNum,
+    Timestamp,
+}
+
+pub fn parse_fragment<N: FromStr>(
+    frag: &str,
+    field: Field,
+    line: &str,
+) -> Result<N, EntryParsingError> {
     match frag.trim().parse() {
         Ok(f) => Ok(f),
-        Err(e) => Err(EntryParsingError::Generic(format!(
-            "Unable to parse {} into a {} due to error: {}\nLine: {}",
-            frag,
-            type_name::<N>(),
-            e,
-            line,
-        ))),
+        Err(_) => {
+            let (raw, line) = (frag.to_owned(), line.to_owned());
+            Err(match field {
+                Field::FacilityLevel => EntryParsingError::InvalidFacilityLevel { raw, line },
+                Field::SequenceNum => EntryParsingError::InvalidSequenceNum { raw, line },
+                Field::Timestamp => EntryParsingError::InvalidTimestamp { raw, line },
+            })
+        }
+    }
+}
+
+/// Formats a time as an RFC 3339 UTC timestamp with microseconds, such as
+/// `2021-02-22T11:37:54.123456Z`. Returns `None` for times before the Unix epoch.
+pub fn format_rfc3339(time: SystemTime) -> Option<String> {
+    let since_epoch = time.duration_since(UNIX_EPOCH).ok()?;
+    let secs = since_epoch.as_secs();
+    let (days, secs_of_day) = (secs / 86_400, secs % 86_400);
+
+    // Days since the epoch to a (proleptic Gregorian) date, after Howard Hinnant's
+    // civil_from_days: http://howardhinnant.github.io/date_algorithms.html
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+
+    Some(format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:06}Z",
+        year,
+        month,
+        day,
+        secs_of_day / 3_600,
+        secs_of_day % 3_600 / 60,
+        secs_of_day % 60,
+        since_epoch.subsec_micros()
+    ))
+}
+
+/// A file in the temporary directory for a test, removed when dropped so that it isn't left
+/// behind when an assertion fails. Dereferences to its path.
+#[cfg(test)]
+pub struct TempFile {
+    path: std::path::PathBuf,
+}
+
+#[cfg(test)]
+impl TempFile {
+    /// The file isn't created, and any left over from an earlier run is removed. `name` tells
+    /// tests' files apart, and the process id concurrent test runs'.
+    pub fn new(name: &str) -> TempFile {
+        let path = std::env::temp_dir().join(format!("rmesg-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        TempFile { path }
+    }
+
+    pub fn with_contents<C: AsRef<[u8]>>(name: &str, contents: C) -> TempFile {
+        let file = TempFile::new(name);
+        std::fs::write(&file.path, contents).unwrap();
+        file
+    }
+}
+
+#[cfg(test)]
+impl std::ops::Deref for TempFile {
+    type Target = std::path::PathBuf;
+
+    fn deref(&self) -> &std::path::PathBuf {
+        &self.path
+    }
+}
+
+#[cfg(test)]
+impl AsRef<std::path::Path> for TempFile {
+    fn as_ref(&self) -> &std::path::Path {
+        &self.path
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/**********************************************************************************/
+// Tests! Tests! Tests!
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_errors() {
+        let line = "99999999999,1,100,-;message";
+        assert!(matches!(
+            parse_favlecstr("99999999999", line),
+            Err(EntryParsingError::InvalidFacilityLevel { raw, line: l }) if raw == "99999999999" && l == line
+        ));
+        // A number, but the facility is above 255
+        assert!(matches!(
+            parse_favlecstr("2048", line),
+            Err(EntryParsingError::InvalidFacilityLevel { raw, .. }) if raw == "2048"
+        ));
+        // Facilities without a name are kept
+        assert_eq!(
+            parse_favlecstr("999", line).unwrap(),
+            (Some(LogFacility::Other(124)), Some(LogLevel::Debug))
+        );
+        assert!(matches!(
+            parse_fragment::<u64>("-1", Field::SequenceNum, line),
+            Err(EntryParsingError::InvalidSequenceNum { raw, .. }) if raw == "-1"
+        ));
+        assert!(matches!(
+            parse_timestamp_secs("12.x", line),
+            Err(EntryParsingError::InvalidTimestamp { raw, .. }) if raw == "12.x"
+        ));
+        assert_eq!(
+            parse_fragment::<u64>(" 42 ", Field::SequenceNum, line).unwrap(),
+            42
+        );
+    }
+
+    #[test]
+    fn test_implausible_timestamp() {
+        assert_eq!(
+            timestamp_from_microsecs(91650777797),
+            Some(Duration::from_micros(91650777797))
+        );
+        assert_eq!(
+            timestamp_from_microsecs(MAX_PLAUSIBLE_UPTIME_MICROSECS),
+            Some(Duration::from_micros(MAX_PLAUSIBLE_UPTIME_MICROSECS))
+        );
+        assert_eq!(
+            timestamp_from_microsecs(MAX_PLAUSIBLE_UPTIME_MICROSECS + 1),
+            None
+        );
+        assert_eq!(timestamp_from_microsecs(u64::MAX), None);
+    }
+
+    #[test]
+    fn test_parse_flags_dict() {
+        let dict = parse_flags_dict("-,SUBSYSTEM=usb,DEVICE=+usb:1-1");
+        assert_eq!(dict.len(), 2);
+        assert_eq!(dict["SUBSYSTEM"], "usb");
+        assert_eq!(dict["DEVICE"], "+usb:1-1");
+
+        assert!(parse_flags_dict("-").is_empty());
+        assert!(parse_flags_dict("").is_empty());
+    }
+
+    #[test]
+    fn test_format_rfc3339() {
+        assert_eq!(
+            format_rfc3339(UNIX_EPOCH).unwrap(),
+            "1970-01-01T00:00:00.000000Z"
+        );
+        assert_eq!(
+            format_rfc3339(UNIX_EPOCH + Duration::from_micros(1_614_000_000_123_456)).unwrap(),
+            "2021-02-22T13:20:00.123456Z"
+        );
+        // Leap day
+        assert_eq!(
+            format_rfc3339(UNIX_EPOCH + Duration::from_secs(951_782_400)).unwrap(),
+            "2000-02-29T00:00:00.000000Z"
+        );
+        assert_eq!(format_rfc3339(UNIX_EPOCH - Duration::from_secs(1)), None);
     }
 }
@@ -1,13 +1,24 @@
 // Copyright (c) 2019 Polyverse Corporation
 
+use crate::common;
+use crate::error::RMesgError;
+use crate::{Backend, EntriesIterator};
 use num_derive::FromPrimitive;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
 use std::error::Error;
 use std::fmt::{Display, Error as FmtError, Formatter, Result as FmtResult, Write};
-use std::time::Duration;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
 use strum_macros::{Display, EnumString};
 
 /// A parsed/structured entry from kernel log buffer
-#[derive(PartialEq, Debug, Clone)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Entry {
     // Log facility
     pub facility: Option<LogFacility>,
@@ -15,24 +26,115 @@ pub struct Entry {
     // Log level
     pub level: Option<LogLevel>,
 
-    // Log sequence number
-    pub sequence_num: Option<usize>,
+    // Log sequence number (64-bit, as in the kernel ABI)
+    pub sequence_num: Option<u64>,
 
     // The amount of time since system bootstrapped
+    #[cfg_attr(feature = "serde", serde(with = "timestamp_secs"))]
     pub timestamp_from_system_start: Option<Duration>,
 
     // Log message
     pub message: String,
+
+    // What kind of event the entry reports, set by the parsers, see `classify`
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub category: EntryCategory,
+
+    // The flags field of a /dev/kmsg line (between the timestamp and the semicolon), as is
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub raw_flags: Option<String>,
+
+    // The KEY=VALUE pairs amongst the flags
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub dict: HashMap<String, String>,
+
+    // The backend the entry was read from. None for entries parsed from a line
+    // or built by hand. Ignored when comparing entries.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub source_backend: Option<Backend>,
+}
+
+// RFC 5424 header fields can't be empty: NILVALUE stands in for them.
+fn nil_if_empty(field: &str) -> &str {
+    if field.is_empty() {
+        "-"
+    } else {
+        field
+    }
+}
+
+/// Serializes timestamps as a (floating-point) number of seconds, for human-readable output.
+#[cfg(feature = "serde")]
+mod timestamp_secs {
+    use serde::{de, Deserialize, Deserializer, Serializer};
+    use std::time::Duration;
+
+    pub fn serialize<S: Serializer>(
+        timestamp: &Option<Duration>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match timestamp {
+            Some(timestamp) => serializer.serialize_some(&timestamp.as_secs_f64()),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    /// Kernel timestamps have microsecond precision, so the seconds are rounded to
+    /// the nearest microsecond, which undoes any floating-point error.
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Duration>, D::Error> {
+        match Option::<f64>::deserialize(deserializer)? {
+            Some(secs) if secs.is_finite() && secs >= 0.0 => Ok(Some(Duration::from_micros(
+                (secs * 1_000_000.0).round() as u64,
+            ))),
+            Some(secs) => Err(de::Error::custom(format!(
+                "Invalid timestamp {}, expected a non-negative number of seconds",
+                secs
+            ))),
+            None => Ok(None),
+        }
+    }
 }
 
+// Sequence numbers used to be usize, and anything indexed by one must still fit into a u64.
+const _: () = assert!(u64::MAX as u128 >= usize::MAX as u128);
+
 impl Entry {
-    pub fn to_faclev(&self) -> Option<u8> {
+    /// Same as `EntryBuilder::new()`.
+    pub fn builder() -> EntryBuilder {
+        EntryBuilder::new()
+    }
+
+    /// The facility and level, as they're combined in the kernel log: facility * 8 + level.
+    pub fn to_faclev(&self) -> Option<u32> {
         match (self.facility, self.level) {
-            (Some(facility), Some(level)) => Some(((facility as u8) << 3) + (level as u8)),
+            (Some(facility), Some(level)) => {
+                Some((u32::from(facility.as_u8()) << 3) + level as u32)
+            }
             _ => None,
         }
     }
 
+    /// The timestamp in (fractional) seconds since the system started.
+    pub fn timestamp_as_secs_f64(&self) -> Option<f64> {
+        self.timestamp_from_system_start
+            .map(|timestamp| timestamp.as_secs_f64())
+    }
+
+    /// Whether this is a continuation line of a multi-line record, such as ` LINE2=foobar`:
+    /// a line without a header, whose message starts with a space.
+    ///
+    /// An entry doesn't know which backend it came from, so a klogctl line without a priority
+    /// prefix that happens to start with a space counts as a continuation line too.
+    pub fn is_continuation_line(&self) -> bool {
+        self.facility.is_none()
+            && self.level.is_none()
+            && self.sequence_num.is_none()
+            && self.timestamp_from_system_start.is_none()
+            && self.message.starts_with(' ')
+    }
+
     // Like so:
     // <5>a.out[4054]: segfault at 7ffd5503d358 ip 00007ffd5503d358 sp 00007ffd5503d258 error 15
     // OR
@@ -81,60 +183,586 @@ impl Entry {
             Ok(self.message.to_string())
         }
     }
+
+    // Like so, for forwarding to a syslog server:
+    // <6>1 2021-02-22T13:20:00.123456Z myhost kernel - 779 - docker0: port 2(veth98d5024) entered disabled state
+    //
+    // The timestamp is `boot_time` plus the time since system start, and is `-` (nil) when that's
+    // unknown. Entries without a facility or level are sent as kern and info.
+    pub fn to_rfc5424_str(&self, hostname: &str, app_name: &str, boot_time: SystemTime) -> String {
+        let priority = (u32::from(self.facility.unwrap_or(LogFacility::Kern).as_u8()) << 3)
+            + self.level.unwrap_or(LogLevel::Info) as u32;
+
+        let timestamp = self
+            .timestamp_from_system_start
+            .and_then(|ts| boot_time.checked_add(ts))
+            .and_then(common::format_rfc3339)
+            .unwrap_or_else(|| "-".to_owned());
+
+        let msgid = match self.sequence_num {
+            Some(sequence_num) => sequence_num.to_string(),
+            None => "-".to_owned(),
+        };
+
+        format!(
+            "<{}>1 {} {} {} - {} - {}",
+            priority,
+            timestamp,
+            nil_if_empty(hostname),
+            nil_if_empty(app_name),
+            msgid,
+            self.message
+        )
+    }
+
+    /// Parses a single /dev/kmsg line, the reverse of `to_kmsg_str`.
+    /// Same as `kmsgfile::entry_from_line`.
+    pub fn from_kmsg_str(s: &str) -> Result<Entry, EntryParsingError> {
+        crate::kmsgfile::entry_from_line(s)
+    }
+
+    /// Cleans up formatting quirks in the message: null bytes become spaces, ASCII control
+    /// characters other than newlines are removed, runs of spaces are collapsed into one,
+    /// and leading/trailing whitespace is trimmed.
+    pub fn normalize(&mut self) {
+        let mut normalized = String::with_capacity(self.message.len());
+
+        for c in self.message.chars() {
+            let c = match c {
+                '\0' => ' ',
+                c => c,
+            };
+
+            if (c.is_ascii_control() && c != '\n') || (c == ' ' && normalized.ends_with(' ')) {
+                continue;
+            }
+
+            normalized.push(c);
+        }
+
+        self.message = normalized.trim().to_owned();
+    }
+
+    /// Estimates the memory the entry takes up, counting the bytes of the message, the raw
+    /// flags and the dict's keys and values, but not any spare capacity in their allocations.
+    pub fn estimated_size_bytes(&self) -> usize {
+        std::mem::size_of::<Entry>()
+            + self.message.len()
+            + self.raw_flags.as_ref().map_or(0, String::len)
+            + self
+                .dict
+                .iter()
+                .map(|(key, value)| key.len() + value.len())
+                .sum::<usize>()
+    }
+
+    /// Formats the entry like `Display` does, with the given options:
+    ///
+    /// ```
+    /// use rmesg::entry::{DisplayOptions, Entry};
+    ///
+    /// let entry: Entry = "6,1,15000,-;Linux version 5.15.0".parse().unwrap();
+    /// assert_eq!(entry.to_string(), "[    0.015000] Linux version 5.15.0");
+    ///
+    /// let opts = DisplayOptions { verbose: true };
+    /// assert_eq!(
+    ///     entry.display_with(&opts).to_string(),
+    ///     "<kern.info> [    0.015000] Linux version 5.15.0"
+    /// );
+    /// ```
+    pub fn display_with(&self, opts: &DisplayOptions) -> EntryDisplay<'_> {
+        EntryDisplay {
+            entry: self,
+            opts: *opts,
+        }
+    }
+
+    /// Same as the `Display` output (like dmesg), with the message colored by level
+    /// using ANSI escape sequences - but only when stdout is a terminal.
+    pub fn to_dmesg_str_colored(&self) -> String {
+        let stdout_is_terminal = unsafe { libc::isatty(libc::STDOUT_FILENO) } == 1;
+        self.to_dmesg_str_with_color(stdout_is_terminal)
+    }
+
+    fn to_dmesg_str_with_color(&self, color: bool) -> String {
+        let escape = match (color, self.level) {
+            (true, Some(level)) => level.ansi_color(),
+            _ => None,
+        };
+
+        match escape {
+            Some(escape) => {
+                let mut retstr = String::with_capacity(35 + self.message.len());
+                if let Some(ts) = self.timestamp_from_system_start {
+                    // Writing to a String doesn't fail
+                    let _ = write_dmesg_timestamp(&mut retstr, ts);
+                }
+                retstr.push_str(escape);
+                retstr.push_str(&self.message);
+                retstr.push_str(ANSI_RESET);
+                retstr
+            }
+            None => self.to_string(),
+        }
+    }
+}
+
+const ANSI_RESET: &str = "\x1b[0m";
+
+/// Writes a timestamp the way dmesg does: right-aligned seconds, then six-digit microseconds.
+fn write_dmesg_timestamp<W: Write>(w: &mut W, ts: Duration) -> FmtResult {
+    write!(w, "[{:>5}.{:06}] ", ts.as_secs(), ts.subsec_micros())
 }
 
+/// Like dmesg's default output, such as `[    0.000000] Linux version 5.15.0 ...`.
+/// Entries without a timestamp are just the message.
 impl Display for Entry {
     fn fmt(&self, f: &mut Formatter) -> FmtResult {
-        if let Some(ts) = self.timestamp_from_system_start {
-            write!(f, "[{: >16.6}] ", ts.as_secs_f64())?
+        self.display_with(&DisplayOptions::default()).fmt(f)
+    }
+}
+
+/// How `Entry::display_with` formats an entry.
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub struct DisplayOptions {
+    /// Prefix the entry with its facility and level, like `<kern.info> `
+    /// (entries without both, such as continuation lines, get no prefix).
+    pub verbose: bool,
+}
+
+/// An entry formatted with DisplayOptions, see `Entry::display_with`.
+#[derive(Debug, Clone, Copy)]
+pub struct EntryDisplay<'a> {
+    entry: &'a Entry,
+    opts: DisplayOptions,
+}
+
+impl Display for EntryDisplay<'_> {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        if self.opts.verbose {
+            if let (Some(facility), Some(level)) = (self.entry.facility, self.entry.level) {
+                write!(f, "<{}.{}> ", facility, level)?;
+            }
+        }
+
+        if let Some(ts) = self.entry.timestamp_from_system_start {
+            write_dmesg_timestamp(f, ts)?;
         }
 
-        write!(f, "{}", self.message)
+        write!(f, "{}", self.entry.message)
+    }
+}
+
+/// Every field but `source_backend`, so the same entry read from either backend is equal.
+impl PartialEq for Entry {
+    fn eq(&self, other: &Self) -> bool {
+        self.facility == other.facility
+            && self.level == other.level
+            && self.sequence_num == other.sequence_num
+            && self.timestamp_from_system_start == other.timestamp_from_system_start
+            && self.message == other.message
+            && self.category == other.category
+            && self.raw_flags == other.raw_flags
+            && self.dict == other.dict
+    }
+}
+
+impl Eq for Entry {}
+
+/// Orders entries by sequence number (entries without one come first), then by timestamp
+/// (likewise), then by message. The remaining fields (but `source_backend`) only break ties,
+/// so that two entries are ordered as equal exactly when they're equal.
+impl Ord for Entry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.sequence_num
+            .cmp(&other.sequence_num)
+            .then_with(|| {
+                self.timestamp_from_system_start
+                    .cmp(&other.timestamp_from_system_start)
+            })
+            .then_with(|| self.message.cmp(&other.message))
+            .then_with(|| {
+                let facility = |e: &Entry| e.facility.map(|facility| facility.as_u8());
+                facility(self).cmp(&facility(other))
+            })
+            .then_with(|| {
+                let level = |e: &Entry| e.level.map(|level| level.as_u8());
+                level(self).cmp(&level(other))
+            })
+            .then_with(|| (self.category as u8).cmp(&(other.category as u8)))
+            .then_with(|| self.raw_flags.cmp(&other.raw_flags))
+            .then_with(|| {
+                let dict: BTreeMap<_, _> = self.dict.iter().collect();
+                let other_dict: BTreeMap<_, _> = other.dict.iter().collect();
+                dict.cmp(&other_dict)
+            })
+    }
+}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Hashes the fields that identify an entry: facility, level, sequence number, timestamp
+/// (in microseconds, as the kernel reports it) and message. Equal entries always match in
+/// these, so they hash the same.
+impl Hash for Entry {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.facility.map(|facility| facility.as_u8()).hash(state);
+        self.level.map(|level| level.as_u8()).hash(state);
+        self.sequence_num.hash(state);
+        self.timestamp_from_system_start
+            .map(|ts| ts.as_micros())
+            .hash(state);
+        self.message.hash(state);
+    }
+}
+
+/// Parses a single /dev/kmsg line, same as `Entry::from_kmsg_str`.
+impl FromStr for Entry {
+    type Err = EntryParsingError;
+
+    fn from_str(s: &str) -> Result<Entry, EntryParsingError> {
+        crate::kmsgfile::entry_from_line(s)
+    }
+}
+
+/// An entry whose message borrows from the line it was parsed out of, for parsing
+/// without allocating. See `kmsgfile::entry_from_line_ref`.
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct EntryRef<'a> {
+    pub facility: Option<LogFacility>,
+    pub level: Option<LogLevel>,
+    pub sequence_num: Option<u64>,
+    pub timestamp_from_system_start: Option<Duration>,
+    pub message: &'a str,
+    pub raw_flags: Option<&'a str>,
+}
+
+impl EntryRef<'_> {
+    /// Copies the message and flags into an owned Entry, parsing the dict out of the flags.
+    pub fn to_owned(&self) -> Entry {
+        Entry {
+            facility: self.facility,
+            level: self.level,
+            sequence_num: self.sequence_num,
+            timestamp_from_system_start: self.timestamp_from_system_start,
+            message: self.message.to_owned(),
+            category: classify_message(self.message),
+            raw_flags: self.raw_flags.map(str::to_owned),
+            dict: self
+                .raw_flags
+                .map(common::parse_flags_dict)
+                .unwrap_or_default(),
+            source_backend: None,
+        }
+    }
+}
+
+/// Builds an entry by hand, such as a test fixture or one for `kmsgfile::kmsg_write_entry`.
+/// Everything left unset is `None`, except the message, which defaults to empty:
+///
+/// ```
+/// use rmesg::entry::{Entry, LogLevel};
+///
+/// let entry = Entry::builder()
+///     .message("eth0: link up")
+///     .level(LogLevel::Info)
+///     .sequence_num(42)
+///     .build();
+/// assert_eq!(entry.sequence_num, Some(42));
+/// assert_eq!(entry.facility, None);
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct EntryBuilder {
+    facility: Option<LogFacility>,
+    level: Option<LogLevel>,
+    sequence_num: Option<u64>,
+    timestamp: Option<Duration>,
+    message: String,
+    category: EntryCategory,
+    raw_flags: Option<String>,
+    dict: HashMap<String, String>,
+}
+
+impl EntryBuilder {
+    pub fn new() -> EntryBuilder {
+        EntryBuilder::default()
+    }
+
+    pub fn message(mut self, msg: &str) -> EntryBuilder {
+        self.message = msg.to_owned();
+        self
+    }
+
+    pub fn facility(mut self, f: LogFacility) -> EntryBuilder {
+        self.facility = Some(f);
+        self
+    }
+
+    pub fn level(mut self, l: LogLevel) -> EntryBuilder {
+        self.level = Some(l);
+        self
+    }
+
+    pub fn sequence_num(mut self, n: u64) -> EntryBuilder {
+        self.sequence_num = Some(n);
+        self
+    }
+
+    /// The time since the system started
+    pub fn timestamp(mut self, t: Duration) -> EntryBuilder {
+        self.timestamp = Some(t);
+        self
+    }
+
+    /// Defaults to `EntryCategory::Normal`
+    pub fn category(mut self, c: EntryCategory) -> EntryBuilder {
+        self.category = c;
+        self
+    }
+
+    /// The flags of a /dev/kmsg line, such as `-` or `-,SUBSYSTEM=usb`
+    pub fn raw_flags(mut self, flags: &str) -> EntryBuilder {
+        self.raw_flags = Some(flags.to_owned());
+        self
+    }
+
+    /// Adds a `KEY=VALUE` pair from the flags to the entry's `dict`
+    pub fn dict_entry(mut self, key: &str, value: &str) -> EntryBuilder {
+        self.dict.insert(key.to_owned(), value.to_owned());
+        self
+    }
+
+    /// Same as `facility`, but clears the facility for `None`
+    pub fn maybe_facility(mut self, f: Option<LogFacility>) -> EntryBuilder {
+        self.facility = f;
+        self
+    }
+
+    /// Same as `level`, but clears the level for `None`
+    pub fn maybe_level(mut self, l: Option<LogLevel>) -> EntryBuilder {
+        self.level = l;
+        self
+    }
+
+    /// Same as `sequence_num`, but clears the sequence number for `None`
+    pub fn maybe_sequence_num(mut self, n: Option<u64>) -> EntryBuilder {
+        self.sequence_num = n;
+        self
+    }
+
+    /// Same as `timestamp`, but clears the timestamp for `None`
+    pub fn maybe_timestamp(mut self, t: Option<Duration>) -> EntryBuilder {
+        self.timestamp = t;
+        self
+    }
+
+    pub fn build(self) -> Entry {
+        Entry {
+            facility: self.facility,
+            level: self.level,
+            sequence_num: self.sequence_num,
+            timestamp_from_system_start: self.timestamp,
+            message: self.message,
+            category: self.category,
+            raw_flags: self.raw_flags,
+            dict: self.dict,
+            source_backend: None,
+        }
     }
 }
 
 /// Linux kmesg (kernel message buffer) Log Facility.
-#[derive(EnumString, Debug, PartialEq, Display, Copy, Clone, FromPrimitive)]
+///
+/// Userspace can log to /dev/kmsg with any facility up to 255 (`<96>...` is facility 12, for
+/// instance), so those without a name are kept as `Other`. Use `from_u8` rather than building
+/// an `Other` by hand, since it never holds the value of a named facility (0 through 23).
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum LogFacility {
-    #[strum(serialize = "kern")]
-    Kern = 0,
-
-    #[strum(serialize = "user")]
+    Kern,
     User,
-
-    #[strum(serialize = "mail")]
     Mail,
-
-    #[strum(serialize = "daemon")]
     Daemon,
-
-    #[strum(serialize = "auth")]
     Auth,
-
-    #[strum(serialize = "syslog")]
     Syslog,
-
-    #[strum(serialize = "lpr")]
     Lpr,
-
-    #[strum(serialize = "news")]
     News,
-
-    #[strum(serialize = "uucp")]
     UUCP,
-
-    #[strum(serialize = "cron")]
     Cron,
-
-    #[strum(serialize = "authpriv")]
     AuthPriv,
-
-    #[strum(serialize = "ftp")]
     FTP,
+    Ntp,
+    Security,
+    Console,
+    SolarisCron,
+    Local0,
+    Local1,
+    Local2,
+    Local3,
+    Local4,
+    Local5,
+    Local6,
+    Local7,
+    /// A facility above 23 (local7)
+    Other(u8),
+}
+
+/// The named facilities, indexed by their kernel value
+const NAMED_FACILITIES: [(LogFacility, &str); 24] = [
+    (LogFacility::Kern, "kern"),
+    (LogFacility::User, "user"),
+    (LogFacility::Mail, "mail"),
+    (LogFacility::Daemon, "daemon"),
+    (LogFacility::Auth, "auth"),
+    (LogFacility::Syslog, "syslog"),
+    (LogFacility::Lpr, "lpr"),
+    (LogFacility::News, "news"),
+    (LogFacility::UUCP, "uucp"),
+    (LogFacility::Cron, "cron"),
+    (LogFacility::AuthPriv, "authpriv"),
+    (LogFacility::FTP, "ftp"),
+    (LogFacility::Ntp, "ntp"),
+    (LogFacility::Security, "security"),
+    (LogFacility::Console, "console"),
+    (LogFacility::SolarisCron, "solaris-cron"),
+    (LogFacility::Local0, "local0"),
+    (LogFacility::Local1, "local1"),
+    (LogFacility::Local2, "local2"),
+    (LogFacility::Local3, "local3"),
+    (LogFacility::Local4, "local4"),
+    (LogFacility::Local5, "local5"),
+    (LogFacility::Local6, "local6"),
+    (LogFacility::Local7, "local7"),
+];
+
+impl LogFacility {
+    /// The facility with the given kernel value: named from 0 (kern) to 23 (local7), `Other`
+    /// above that.
+    pub fn from_u8(v: u8) -> Self {
+        match NAMED_FACILITIES.get(v as usize) {
+            Some((facility, _)) => *facility,
+            None => LogFacility::Other(v),
+        }
+    }
+
+    /// The facility's kernel value, from 0 (kern) to 255.
+    pub fn as_u8(&self) -> u8 {
+        match self {
+            LogFacility::Kern => 0,
+            LogFacility::User => 1,
+            LogFacility::Mail => 2,
+            LogFacility::Daemon => 3,
+            LogFacility::Auth => 4,
+            LogFacility::Syslog => 5,
+            LogFacility::Lpr => 6,
+            LogFacility::News => 7,
+            LogFacility::UUCP => 8,
+            LogFacility::Cron => 9,
+            LogFacility::AuthPriv => 10,
+            LogFacility::FTP => 11,
+            LogFacility::Ntp => 12,
+            LogFacility::Security => 13,
+            LogFacility::Console => 14,
+            LogFacility::SolarisCron => 15,
+            LogFacility::Local0 => 16,
+            LogFacility::Local1 => 17,
+            LogFacility::Local2 => 18,
+            LogFacility::Local3 => 19,
+            LogFacility::Local4 => 20,
+            LogFacility::Local5 => 21,
+            LogFacility::Local6 => 22,
+            LogFacility::Local7 => 23,
+            LogFacility::Other(v) => *v,
+        }
+    }
+}
+
+/// The conventional short name, such as `kern`, or the kernel value for `Other` facilities.
+impl Display for LogFacility {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match NAMED_FACILITIES.get(self.as_u8() as usize) {
+            Some((_, name)) => f.pad(name),
+            None => f.pad(&self.as_u8().to_string()),
+        }
+    }
+}
+
+/// Parses what `Display` outputs: a short name, or a kernel value.
+impl FromStr for LogFacility {
+    type Err = strum::ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match NAMED_FACILITIES.iter().find(|(_, name)| *name == s) {
+            Some((facility, _)) => Ok(*facility),
+            None => s
+                .parse()
+                .map(LogFacility::from_u8)
+                .map_err(|_| strum::ParseError::VariantNotFound),
+        }
+    }
+}
+
+impl num::FromPrimitive for LogFacility {
+    fn from_i64(n: i64) -> Option<Self> {
+        u8::try_from(n).ok().map(LogFacility::from_u8)
+    }
+
+    fn from_u64(n: u64) -> Option<Self> {
+        u8::try_from(n).ok().map(LogFacility::from_u8)
+    }
+}
+
+/// A set of log facilities, checked against in constant time: bit N (of the 256 bits, in
+/// four words of 64) is set when the facility with value N is included.
+///
+/// ```
+/// use rmesg::entry::{FacilityMask, LogFacility};
+///
+/// let mask = FacilityMask::from_facilities(&[LogFacility::Kern, LogFacility::Daemon]);
+/// assert!(mask.matches(Some(LogFacility::Kern)));
+/// assert!(!mask.matches(Some(LogFacility::User)));
+/// ```
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct FacilityMask(pub [u64; 4]);
+
+impl FacilityMask {
+    pub fn from_facilities(facilities: &[LogFacility]) -> Self {
+        let mut mask = [0; 4];
+        for facility in facilities {
+            let v = facility.as_u8();
+            mask[v as usize / 64] |= 1 << (v % 64);
+        }
+
+        Self(mask)
+    }
+
+    pub fn all() -> Self {
+        Self([u64::MAX; 4])
+    }
+
+    pub fn none() -> Self {
+        Self([0; 4])
+    }
+
+    /// Entries without a facility (such as continuation lines) never match.
+    pub fn matches(&self, facility: Option<LogFacility>) -> bool {
+        match facility {
+            Some(facility) => {
+                let v = facility.as_u8();
+                self.0[v as usize / 64] & (1 << (v % 64)) != 0
+            }
+            None => false,
+        }
+    }
 }
 
 /// Linux kmesg (kernel message buffer) Log Level.
-#[derive(EnumString, Debug, PartialEq, Display, Copy, Clone, FromPrimitive)]
+#[derive(EnumString, Debug, PartialEq, Eq, Display, Copy, Clone, FromPrimitive)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum LogLevel {
     #[strum(serialize = "emerg")]
     Emergency = 0,
@@ -161,11 +789,239 @@ pub enum LogLevel {
     Debug,
 }
 
+/// A set of log levels, checked against in constant time: bit N is set
+/// when the level with value N is included.
+///
+/// ```
+/// use rmesg::entry::{LevelMask, LogLevel};
+///
+/// // Everything from notice up, except warnings
+/// let mask = LevelMask::from_levels(&[LogLevel::Notice])
+///     .union(LevelMask::ERRORS_AND_ABOVE)
+///     .intersection(LevelMask::from_levels(&[LogLevel::Warning]).complement());
+/// assert!(mask.matches(Some(LogLevel::Notice)));
+/// assert!(!mask.matches(Some(LogLevel::Warning)));
+/// ```
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct LevelMask(pub u8);
+
+impl LevelMask {
+    pub const ALL: LevelMask = LevelMask(u8::MAX);
+    pub const NONE: LevelMask = LevelMask(0);
+    /// Emergency, Alert, Critical and Error
+    pub const ERRORS_AND_ABOVE: LevelMask = LevelMask(0b0000_1111);
+
+    pub fn from_levels(levels: &[LogLevel]) -> Self {
+        Self(
+            levels
+                .iter()
+                .fold(0, |mask, level| mask | (1 << *level as u8)),
+        )
+    }
+
+    pub fn union(self, other: LevelMask) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    pub fn intersection(self, other: LevelMask) -> Self {
+        Self(self.0 & other.0)
+    }
+
+    pub fn complement(self) -> Self {
+        Self(!self.0)
+    }
+
+    /// Entries without a level (such as continuation lines) never match.
+    pub fn matches(&self, level: Option<LogLevel>) -> bool {
+        match level {
+            Some(level) => self.0 & (1 << level as u8) != 0,
+            None => false,
+        }
+    }
+}
+
+impl LogLevel {
+    /// The level with the given kernel value, from 0 (Emergency) to 7 (Debug).
+    pub fn from_u8(v: u8) -> Option<Self> {
+        num::FromPrimitive::from_u8(v)
+    }
+
+    /// The level's kernel value, from 0 (Emergency) to 7 (Debug).
+    pub fn as_u8(&self) -> u8 {
+        *self as u8
+    }
+
+    /// The ANSI escape sequence that starts this level's color, if it has one
+    fn ansi_color(self) -> Option<&'static str> {
+        match self {
+            // bold red
+            Self::Emergency | Self::Alert | Self::Critical => Some("\x1b[1;31m"),
+            // red
+            Self::Error => Some("\x1b[31m"),
+            // yellow
+            Self::Warning => Some("\x1b[33m"),
+            // cyan
+            Self::Notice => Some("\x1b[36m"),
+            Self::Info => None,
+            // dim
+            Self::Debug => Some("\x1b[2m"),
+        }
+    }
+}
+
+/// What kind of event an entry reports, for routing entries downstream.
+#[derive(EnumString, Debug, PartialEq, Eq, Display, Copy, Clone, Default, FromPrimitive)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum EntryCategory {
+    #[default]
+    #[strum(serialize = "normal")]
+    Normal,
+
+    #[strum(serialize = "oom_kill")]
+    OomKill,
+
+    #[strum(serialize = "kernel_panic")]
+    KernelPanic,
+
+    #[strum(serialize = "kernel_oops")]
+    KernelOops,
+
+    #[strum(serialize = "lockup")]
+    Lockup,
+
+    #[strum(serialize = "hardware_error")]
+    HardwareError,
+}
+
+/// Prefixes of messages, checked in order, so when a message starts with several of them,
+/// the first one wins. Panics are checked first since an oops, lockup or OOM often leads to one.
+const CATEGORY_PATTERNS: &[(&str, EntryCategory)] = &[
+    ("Kernel panic - not syncing", EntryCategory::KernelPanic),
+    ("Oops:", EntryCategory::KernelOops),
+    ("BUG: unable to handle", EntryCategory::KernelOops),
+    (
+        "BUG: kernel NULL pointer dereference",
+        EntryCategory::KernelOops,
+    ),
+    ("kernel BUG at", EntryCategory::KernelOops),
+    ("general protection fault", EntryCategory::KernelOops),
+    ("BUG: soft lockup", EntryCategory::Lockup),
+    ("watchdog: BUG: soft lockup", EntryCategory::Lockup),
+    ("NMI watchdog: BUG: soft lockup", EntryCategory::Lockup),
+    ("Watchdog detected hard LOCKUP", EntryCategory::Lockup),
+    (
+        "watchdog: Watchdog detected hard LOCKUP",
+        EntryCategory::Lockup,
+    ),
+    ("INFO: rcu_", EntryCategory::Lockup),
+    ("rcu: INFO: rcu_", EntryCategory::Lockup),
+    // Hung tasks: "INFO: task <comm>:<pid> blocked for more than <n> seconds."
+    ("INFO: task ", EntryCategory::Lockup),
+    ("Out of memory: Kill", EntryCategory::OomKill),
+    ("Memory cgroup out of memory: Kill", EntryCategory::OomKill),
+    ("oom-kill:", EntryCategory::OomKill),
+    ("[Hardware Error]", EntryCategory::HardwareError),
+    ("mce: [Hardware Error]", EntryCategory::HardwareError),
+    ("EDAC ", EntryCategory::HardwareError),
+];
+
+/// Classifies the entry by the well-known prefix its message starts with, if any. The
+/// parsers classify the entries they return.
+pub fn classify(entry: &Entry) -> EntryCategory {
+    classify_message(&entry.message)
+}
+
+pub(crate) fn classify_message(message: &str) -> EntryCategory {
+    let message = strip_message_prefixes(message);
+    for (pattern, category) in CATEGORY_PATTERNS {
+        if message.starts_with(pattern) {
+            return *category;
+        }
+    }
+
+    EntryCategory::Normal
+}
+
+/// The message without what may come before the text `CATEGORY_PATTERNS` match: the printk
+/// caller id (`[T123]` or `[C1]`, with CONFIG_PRINTK_CALLER), then a CPU number (`CPU: 1` or
+/// `CPU1:`).
+fn strip_message_prefixes(message: &str) -> &str {
+    let mut message = message.trim_start();
+
+    if let Some((id, rest)) = message
+        .strip_prefix('[')
+        .and_then(|rest| rest.split_once(']'))
+    {
+        let id = id.trim_start();
+        if id.len() > 1
+            && (id.starts_with('T') || id.starts_with('C'))
+            && id[1..].bytes().all(|b| b.is_ascii_digit())
+        {
+            message = rest.trim_start();
+        }
+    }
+
+    if let Some(rest) = message.strip_prefix("CPU") {
+        let rest = rest.strip_prefix(": ").unwrap_or(rest);
+        let number = rest.trim_start_matches(|c: char| c.is_ascii_digit());
+        if number.len() < rest.len() {
+            if let Some(rest) = number
+                .strip_prefix(": ")
+                .or_else(|| number.strip_prefix(' '))
+            {
+                message = rest;
+            }
+        }
+    }
+
+    message
+}
+
+/// Sets the category of each entry, see `classify`. The parsers already classify the entries
+/// they return, so this is for entries from other sources, such as `EntriesIterator::from_dyn`.
+pub struct ClassifiedEntriesIter {
+    inner: EntriesIterator,
+}
+
+impl EntriesIterator {
+    pub fn with_classification(self) -> ClassifiedEntriesIter {
+        ClassifiedEntriesIter { inner: self }
+    }
+}
+
+impl Iterator for ClassifiedEntriesIter {
+    type Item = Result<Entry, RMesgError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|entry| {
+            entry.map(|mut entry| {
+                entry.category = classify(&entry);
+                entry
+            })
+        })
+    }
+}
+
+/// Why a line (or a kernel log buffer) couldn't be parsed into entries.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum EntryParsingError {
+    /// There is nothing left to parse.
     Completed,
+    /// The entry is older than entries already parsed, judging by its timestamp or sequence number.
     EventTooOld,
-    Generic(String),
+    /// The facility and level field `raw` isn't a number, or not one standing for a known
+    /// facility and level.
+    InvalidFacilityLevel { raw: String, line: String },
+    /// The sequence number field `raw` isn't a number.
+    InvalidSequenceNum { raw: String, line: String },
+    /// The timestamp field `raw` isn't a number.
+    InvalidTimestamp { raw: String, line: String },
+    /// The line is empty, so there is nothing to parse. Readers of whole buffers skip empty
+    /// lines instead.
+    EmptyLine,
+    /// The line is in no format that can be parsed. Describes the problem.
+    UnrecognizedFormat(String),
 }
 impl Error for EntryParsingError {}
 impl Display for EntryParsingError {
@@ -174,10 +1030,19 @@ impl Display for EntryParsingError {
             f,
             "KMsgParsingError:: {}",
             match self {
-                Self::Completed => "Completed Parsing",
+                Self::Completed => "Completed Parsing".to_owned(),
                 Self::EventTooOld =>
-                    "Event too old due to timestamp or sequence number (we've parsed newer messages than these)",
-                Self::Generic(s) => s,
+                    "Event too old due to timestamp or sequence number (we've parsed newer messages than these)".to_owned(),
+                Self::InvalidFacilityLevel { raw, line } => format!(
+                    "Unable to parse {} into log facility and level. Line: {}",
+                    raw, line
+                ),
+                Self::InvalidSequenceNum { raw, line } =>
+                    format!("Unable to parse {} into a sequence number. Line: {}", raw, line),
+                Self::InvalidTimestamp { raw, line } =>
+                    format!("Unable to parse {} into a timestamp. Line: {}", raw, line),
+                Self::EmptyLine => "Empty line".to_owned(),
+                Self::UnrecognizedFormat(s) => s.to_owned(),
             }
         )
     }
@@ -187,15 +1052,57 @@ impl Display for EntryParsingError {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_to_rfc5424_str() {
+        let boot_time = SystemTime::UNIX_EPOCH + Duration::from_secs(1_614_000_000);
+
+        let entry =
+            Entry::from_kmsg_str("30,779,91650777797,-;docker0: port 2 entered disabled state")
+                .unwrap();
+        assert_eq!(
+            entry.to_rfc5424_str("myhost", "kernel", boot_time),
+            "<30>1 2021-02-23T14:47:30.777797Z myhost kernel - 779 - docker0: port 2 entered disabled state"
+        );
+
+        let continuation = Entry::from_kmsg_str(" LINE2=foobar").unwrap();
+        assert_eq!(
+            continuation.to_rfc5424_str("", "kernel", boot_time),
+            "<6>1 - - kernel - - -  LINE2=foobar"
+        );
+    }
+
+    #[test]
+    fn test_timestamp_as_secs_f64() {
+        let entry = Entry::from_kmsg_str("6,779,91650777797,-;docker0").unwrap();
+        assert_eq!(entry.timestamp_as_secs_f64(), Some(91650.777797));
+
+        let continuation = Entry::from_kmsg_str(" LINE2=foobar").unwrap();
+        assert_eq!(continuation.timestamp_as_secs_f64(), None);
+    }
+
+    #[test]
+    fn test_is_continuation_line() {
+        let full = Entry::from_kmsg_str("6,2,0,-;x86/fpu: Supporting XSAVE feature 0x001").unwrap();
+        assert!(!full.is_continuation_line());
+
+        let continuation = Entry::from_kmsg_str(" LINE2=foobar").unwrap();
+        assert!(continuation.is_continuation_line());
+
+        // Indistinguishable from a /dev/kmsg continuation line once parsed
+        let klog_unprefixed = crate::klogctl::entry_from_line(" no priority prefix").unwrap();
+        assert_eq!(klog_unprefixed.message, " no priority prefix");
+        assert!(klog_unprefixed.is_continuation_line());
+    }
+
     #[test]
     fn test_serialize_to_klog() {
-        let entry_struct = Entry {
-            timestamp_from_system_start: Some(Duration::from_secs_f64(24241.325252)),
-            facility: Some(LogFacility::Kern),
-            level: Some(LogLevel::Info),
-            sequence_num: Some(10),
-            message: "Test message".to_owned(),
-        };
+        let entry_struct = Entry::builder()
+            .timestamp(Duration::from_secs_f64(24241.325252))
+            .facility(LogFacility::Kern)
+            .level(LogLevel::Info)
+            .sequence_num(10)
+            .message("Test message")
+            .build();
         let expected_serialization = "<6>[    24241.325252]Test message";
 
         let boxed_entry_struct = Box::new(entry_struct.clone());
@@ -209,13 +1116,13 @@ mod tests {
 
     #[test]
     fn test_serialize_to_kmsg() {
-        let entry_struct = Entry {
-            timestamp_from_system_start: Some(Duration::from_secs_f64(24241.325252)),
-            facility: Some(LogFacility::Kern),
-            level: Some(LogLevel::Info),
-            sequence_num: Some(23),
-            message: "Test message".to_owned(),
-        };
+        let entry_struct = Entry::builder()
+            .timestamp(Duration::from_secs_f64(24241.325252))
+            .facility(LogFacility::Kern)
+            .level(LogLevel::Info)
+            .sequence_num(23)
+            .message("Test message")
+            .build();
         let expected_serialization = "6,23,24241325252,-;Test message";
 
         let boxed_entry_struct = Box::new(entry_struct.clone());
@@ -227,16 +1134,309 @@ mod tests {
         assert_eq!(printed_boxed_entry_struct, expected_serialization);
     }
 
+    fn normalized(message: &str) -> String {
+        let mut entry = Entry::builder().message(message).build();
+        entry.normalize();
+        entry.message
+    }
+
     #[test]
-    fn test_display() {
-        let entry_struct = Entry {
-            timestamp_from_system_start: Some(Duration::from_secs_f64(24241.325252)),
-            facility: Some(LogFacility::Kern),
-            level: Some(LogLevel::Info),
-            sequence_num: Some(15),
-            message: "Test message".to_owned(),
+    fn test_normalize() {
+        assert_eq!(normalized("Test message"), "Test message");
+        // Leading and trailing whitespace
+        assert_eq!(normalized("  Test message \t\n"), "Test message");
+        // Null bytes
+        assert_eq!(normalized("Test\0message\0"), "Test message");
+        // Consecutive spaces
+        assert_eq!(normalized("Test    message"), "Test message");
+        assert_eq!(normalized("Test \0 message"), "Test message");
+        // Control characters, except newlines
+        assert_eq!(normalized("Test\x07 mess\x1bage\x7f"), "Test message");
+        assert_eq!(normalized("Test\tmessage"), "Testmessage");
+        assert_eq!(normalized("Test\nmessage"), "Test\nmessage");
+        // Everything else is left alone
+        assert_eq!(normalized("Tést méssage ✓"), "Tést méssage ✓");
+        assert_eq!(normalized(" \0 \x01 "), "");
+    }
+
+    #[test]
+    fn test_sort() {
+        let entry = |sequence_num: Option<u64>, micros: Option<u64>, message: &str| {
+            Entry::builder()
+                .maybe_sequence_num(sequence_num)
+                .maybe_timestamp(micros.map(Duration::from_micros))
+                .facility(LogFacility::Kern)
+                .level(LogLevel::Info)
+                .message(message)
+                .build()
         };
-        let expected_serialization = "[    24241.325252] Test message";
+
+        let mut entries = [
+            entry(Some(3), Some(300), "third"),
+            entry(None, Some(50), "no sequence, later"),
+            entry(Some(1), Some(100), "first, b"),
+            entry(None, None, " LINE2=continuation"),
+            entry(Some(1), Some(100), "first, a"),
+            entry(Some(2), Some(200), "second"),
+            entry(None, Some(10), "no sequence, earlier"),
+        ];
+        entries.sort();
+
+        let messages: Vec<&str> = entries.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                " LINE2=continuation",
+                "no sequence, earlier",
+                "no sequence, later",
+                "first, a",
+                "first, b",
+                "second",
+                "third",
+            ]
+        );
+
+        // Ordered as equal exactly when equal
+        let a = entry(Some(1), Some(100), "first, a");
+        let mut b = a.clone();
+        assert_eq!(a.cmp(&b), Ordering::Equal);
+        b.level = Some(LogLevel::Error);
+        assert_ne!(a, b);
+        assert_ne!(a.cmp(&b), Ordering::Equal);
+        b.level = a.level;
+        b.dict.insert("SUBSYSTEM".to_owned(), "usb".to_owned());
+        assert_ne!(a.cmp(&b), Ordering::Equal);
+
+        let set: std::collections::BTreeSet<Entry> =
+            vec![a.clone(), a.clone(), b.clone()].into_iter().collect();
+        assert_eq!(set.len(), 2);
+    }
+
+    #[test]
+    fn test_source_backend_ignored_in_comparisons() {
+        let parsed: Entry = "6,1,100,-;first".parse().unwrap();
+        assert_eq!(parsed.source_backend, None);
+
+        let mut read = parsed.clone();
+        read.source_backend = Some(Backend::DevKMsg);
+        assert_eq!(read, parsed);
+        assert_eq!(read.cmp(&parsed), Ordering::Equal);
+    }
+
+    #[test]
+    fn test_dedup_with_hash_set() {
+        let lines = [
+            "6,1,100,-;first",
+            "6,2,200,-,SUBSYSTEM=usb;second",
+            " LINE2=continuation",
+        ];
+
+        // The same entries, read twice
+        let entries: std::collections::HashSet<Entry> = lines
+            .iter()
+            .chain(lines.iter())
+            .map(|line| line.parse().unwrap())
+            .collect();
+        assert_eq!(entries.len(), 3);
+
+        let third: Entry = "6,3,300,-;third".parse().unwrap();
+        assert!(!entries.contains(&third));
+        assert!(entries.contains(&"6,1,100,-;first".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_dmesg_str_colored() {
+        let mut entry_struct = Entry::builder()
+            .timestamp(Duration::from_micros(24241325252))
+            .facility(LogFacility::Kern)
+            .level(LogLevel::Error)
+            .sequence_num(15)
+            .message("Test message")
+            .build();
+
+        assert_eq!(
+            entry_struct.to_dmesg_str_with_color(true),
+            "[24241.325252] \x1b[31mTest message\x1b[0m"
+        );
+        assert_eq!(
+            entry_struct.to_dmesg_str_with_color(false),
+            "[24241.325252] Test message"
+        );
+
+        entry_struct.level = Some(LogLevel::Critical);
+        assert!(entry_struct
+            .to_dmesg_str_with_color(true)
+            .contains("\x1b[1;31m"));
+
+        // Info and unknown levels are never colored
+        entry_struct.level = Some(LogLevel::Info);
+        assert!(!entry_struct.to_dmesg_str_with_color(true).contains('\x1b'));
+        entry_struct.level = None;
+        assert!(!entry_struct.to_dmesg_str_with_color(true).contains('\x1b'));
+
+        // Only colored when stdout is a terminal
+        entry_struct.level = Some(LogLevel::Error);
+        let stdout_is_terminal = unsafe { libc::isatty(libc::STDOUT_FILENO) } == 1;
+        assert_eq!(
+            entry_struct.to_dmesg_str_colored().contains('\x1b'),
+            stdout_is_terminal
+        );
+    }
+
+    #[test]
+    fn test_facility_mask() {
+        let mask = FacilityMask::from_facilities(&[LogFacility::Kern, LogFacility::FTP]);
+        assert_eq!(mask, FacilityMask([0b1000_0000_0001, 0, 0, 0]));
+        assert!(mask.matches(Some(LogFacility::Kern)));
+        assert!(mask.matches(Some(LogFacility::FTP)));
+        assert!(!mask.matches(Some(LogFacility::User)));
+        assert!(!mask.matches(None));
+
+        assert!(FacilityMask::all().matches(Some(LogFacility::AuthPriv)));
+        assert!(FacilityMask::all().matches(Some(LogFacility::Other(255))));
+        let other = FacilityMask::from_facilities(&[LogFacility::from_u8(200)]);
+        assert!(other.matches(Some(LogFacility::Other(200))));
+        assert!(!other.matches(Some(LogFacility::Other(201))));
+        assert!(!other.matches(Some(LogFacility::Local0)));
+        assert!(!FacilityMask::all().matches(None));
+        assert!(!FacilityMask::none().matches(Some(LogFacility::Kern)));
+        assert_eq!(FacilityMask::from_facilities(&[]), FacilityMask::none());
+    }
+
+    #[test]
+    fn test_level_mask() {
+        assert_eq!(
+            LevelMask::ERRORS_AND_ABOVE,
+            LevelMask::from_levels(&[
+                LogLevel::Emergency,
+                LogLevel::Alert,
+                LogLevel::Critical,
+                LogLevel::Error
+            ])
+        );
+        assert!(LevelMask::ERRORS_AND_ABOVE.matches(Some(LogLevel::Error)));
+        assert!(!LevelMask::ERRORS_AND_ABOVE.matches(Some(LogLevel::Warning)));
+        assert!(!LevelMask::ALL.matches(None));
+        assert!(LevelMask::ALL.matches(Some(LogLevel::Debug)));
+        assert!(!LevelMask::NONE.matches(Some(LogLevel::Emergency)));
+
+        let warnings = LevelMask::from_levels(&[LogLevel::Warning]);
+        let notices = LevelMask::from_levels(&[LogLevel::Notice]);
+        let mask = LevelMask::ERRORS_AND_ABOVE.union(notices);
+        assert!(mask.matches(Some(LogLevel::Notice)));
+        assert!(!mask.matches(Some(LogLevel::Warning)));
+        assert_eq!(mask.intersection(notices), notices);
+        assert_eq!(mask.intersection(warnings), LevelMask::NONE);
+        assert!(warnings.complement().matches(Some(LogLevel::Info)));
+        assert!(!warnings.complement().matches(Some(LogLevel::Warning)));
+        assert_eq!(LevelMask::NONE.complement(), LevelMask::ALL);
+    }
+
+    #[test]
+    fn test_classify() {
+        let category_of = |message: &str| {
+            let mut entry = Entry::builder().message(message).build();
+            entry.category = classify(&entry);
+            entry.category
+        };
+
+        assert_eq!(
+            category_of("docker0: port 2(veth98d5024) entered disabled state"),
+            EntryCategory::Normal
+        );
+        assert_eq!(
+            category_of("Kernel panic - not syncing: Fatal exception"),
+            EntryCategory::KernelPanic
+        );
+        assert_eq!(
+            category_of("Kernel panic - not syncing: Out of memory: Kill process"),
+            EntryCategory::KernelPanic
+        );
+        assert_eq!(
+            category_of("Oops: 0002 [#1] SMP PTI"),
+            EntryCategory::KernelOops
+        );
+        assert_eq!(
+            category_of("BUG: kernel NULL pointer dereference, address: 0000000000000008"),
+            EntryCategory::KernelOops
+        );
+        assert_eq!(
+            category_of("watchdog: BUG: soft lockup - CPU#3 stuck for 23s! [kworker/3:1:123]"),
+            EntryCategory::Lockup
+        );
+        assert_eq!(
+            category_of("INFO: task jbd2/sda1-8:311 blocked for more than 120 seconds."),
+            EntryCategory::Lockup
+        );
+        assert_eq!(
+            category_of("Out of memory: Killed process 4054 (a.out) total-vm:123kB"),
+            EntryCategory::OomKill
+        );
+        // Starts with the process name: the report's "Out of memory" line is the one classified
+        assert_eq!(
+            category_of(
+                "a.out invoked oom-killer: gfp_mask=0x100cca(GFP_HIGHUSER_MOVABLE), order=0"
+            ),
+            EntryCategory::Normal
+        );
+        assert_eq!(
+            category_of("mce: [Hardware Error]: Machine check events logged"),
+            EntryCategory::HardwareError
+        );
+        assert_eq!(
+            category_of("EDAC MC0: 1 CE memory read error on CPU_SrcID#0_Ha#0"),
+            EntryCategory::HardwareError
+        );
+
+        // Only prefixes count, not mentions of them
+        assert_eq!(
+            category_of("usb 1-1: EDAC driver probed, no Oops: nothing blocked for more than 1s"),
+            EntryCategory::Normal
+        );
+
+        // Caller ids and CPU numbers come before the prefix
+        assert_eq!(
+            category_of("[  T123] Oops: 0002 [#1] SMP PTI"),
+            EntryCategory::KernelOops
+        );
+        assert_eq!(
+            category_of("[C1] CPU: 1 Kernel panic - not syncing: Fatal exception"),
+            EntryCategory::KernelPanic
+        );
+        assert_eq!(
+            category_of("CPU3: EDAC MC0: 1 CE memory read error"),
+            EntryCategory::HardwareError
+        );
+        assert_eq!(
+            category_of("CPU: 1 PID: 2 Comm: kworker Not tainted"),
+            EntryCategory::Normal
+        );
+
+        // The parsers classify the entries they return
+        assert_eq!(
+            crate::kmsgfile::entry_from_line("0,1,100,-;Oops: 0002 [#1] SMP PTI")
+                .unwrap()
+                .category,
+            EntryCategory::KernelOops
+        );
+        assert_eq!(
+            crate::klogctl::entry_from_line("<0>[    1.000000] Oops: 0002 [#1] SMP PTI")
+                .unwrap()
+                .category,
+            EntryCategory::KernelOops
+        );
+    }
+
+    #[test]
+    fn test_display() {
+        let entry_struct = Entry::builder()
+            .timestamp(Duration::from_micros(24241325252))
+            .facility(LogFacility::Kern)
+            .level(LogLevel::Info)
+            .sequence_num(15)
+            .message("Test message")
+            .build();
+        let expected_serialization = "[24241.325252] Test message";
 
         let boxed_entry_struct = Box::new(entry_struct.clone());
 
@@ -245,5 +1445,126 @@ mod tests {
 
         let printed_boxed_entry_struct = format!("{}", boxed_entry_struct);
         assert_eq!(printed_boxed_entry_struct, expected_serialization);
+
+        let mut entry_struct = entry_struct;
+        entry_struct.timestamp_from_system_start = Some(Duration::from_micros(1_000_042));
+        assert_eq!(entry_struct.to_string(), "[    1.000042] Test message");
+
+        let verbose = DisplayOptions { verbose: true };
+        assert_eq!(
+            entry_struct.display_with(&verbose).to_string(),
+            "<kern.info> [    1.000042] Test message"
+        );
+        assert_eq!(
+            entry_struct
+                .display_with(&DisplayOptions::default())
+                .to_string(),
+            entry_struct.to_string()
+        );
+
+        // No timestamp, no brackets. No facility or level, no verbose prefix
+        entry_struct.timestamp_from_system_start = None;
+        entry_struct.facility = None;
+        assert_eq!(entry_struct.to_string(), "Test message");
+        assert_eq!(
+            entry_struct.display_with(&verbose).to_string(),
+            "Test message"
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let entries = vec![
+            Entry::builder()
+                .timestamp(Duration::from_micros(24241325252))
+                .facility(LogFacility::Kern)
+                .level(LogLevel::Info)
+                .sequence_num(10)
+                .message("Test message")
+                .build(),
+            Entry::builder()
+                .timestamp(Duration::from_micros(100000000))
+                .facility(LogFacility::User)
+                .level(LogLevel::Emergency)
+                .sequence_num(u64::MAX - 1)
+                .message("Kernel panic - not syncing: Fatal exception")
+                .category(EntryCategory::KernelPanic)
+                .build(),
+            Entry::builder().message(" LINE2=foobar").build(),
+        ];
+
+        for entry in entries {
+            let json = serde_json::to_string(&entry).unwrap();
+            let deserialized: Entry = serde_json::from_str(&json).unwrap();
+            assert_eq!(deserialized, entry, "JSON: {}", json);
+        }
+
+        let json = serde_json::to_string(
+            &Entry::builder()
+                .timestamp(Duration::from_micros(1500000))
+                .build(),
+        )
+        .unwrap();
+        assert!(
+            json.contains(r#""timestamp_from_system_start":1.5"#),
+            "JSON: {}",
+            json
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_rejects_negative_timestamp() {
+        let json = r#"{"facility":null,"level":null,"sequence_num":null,"timestamp_from_system_start":-1.0,"message":""}"#;
+        assert!(serde_json::from_str::<Entry>(json).is_err());
+    }
+
+    #[test]
+    fn test_log_level_u8() {
+        assert_eq!(LogLevel::from_u8(0), Some(LogLevel::Emergency));
+        assert_eq!(LogLevel::from_u8(6), Some(LogLevel::Info));
+        assert_eq!(LogLevel::from_u8(7), Some(LogLevel::Debug));
+        assert_eq!(LogLevel::from_u8(8), None);
+        assert_eq!(LogLevel::from_u8(255), None);
+
+        for v in 0..=7 {
+            assert_eq!(LogLevel::from_u8(v).unwrap().as_u8(), v);
+        }
+
+        assert_eq!(LogLevel::Emergency.to_string(), "emerg");
+        assert_eq!(LogLevel::Debug.to_string(), "debug");
+    }
+
+    #[test]
+    fn test_log_facility_u8() {
+        for v in 0..=255 {
+            let facility = LogFacility::from_u8(v);
+            assert_eq!(facility.as_u8(), v);
+            assert_eq!(
+                facility.to_string().parse::<LogFacility>().unwrap(),
+                facility
+            );
+            assert_eq!(matches!(facility, LogFacility::Other(_)), v > 23);
+        }
+
+        assert_eq!(LogFacility::from_u8(12), LogFacility::Ntp);
+        assert_eq!(LogFacility::SolarisCron.to_string(), "solaris-cron");
+        assert_eq!(LogFacility::from_u8(24), LogFacility::Other(24));
+        assert_eq!(LogFacility::Other(200).to_string(), "200");
+        assert!("bogus".parse::<LogFacility>().is_err());
+        assert_eq!(
+            <LogFacility as num::FromPrimitive>::from_u32(255),
+            Some(LogFacility::Other(255))
+        );
+        assert_eq!(<LogFacility as num::FromPrimitive>::from_u32(256), None);
+
+        assert_eq!(LogFacility::Kern.to_string(), "kern");
+        assert_eq!(LogFacility::Local0.as_u8(), 16);
+        assert_eq!(LogFacility::Local7.to_string(), "local7");
+        assert_eq!(
+            "local3".parse::<LogFacility>().unwrap(),
+            LogFacility::Local3
+        );
     }
 }
@@ -1,63 +1,285 @@
 use crate::entry;
 use std::convert::From;
 use std::error::Error;
-use std::fmt::{Display, Formatter, Result as FmtResult};
 use std::time::SystemTimeError;
 
-#[derive(Debug)]
+/// The error an RMesgError was caused by, kept so the chain of errors can be followed
+/// (through `Error::source`).
+pub type ErrorSource = Box<dyn Error + Send + Sync>;
+
+#[derive(Debug, thiserror::Error)]
 pub enum RMesgError {
+    #[error("RMesgError:: RMesg not implemented for this platform.")]
     NotImplementedForThisPlatform,
+    #[error("RMesgError:: Failed to get SystemTime.")]
     UnableToObtainSystemTime,
+    #[error("RMesgError:: Failed to add a Duration to SystemTime")]
     UnableToAddDurationToSystemTime,
+    #[error("RMesgError:: Kernel Log timestamps are disabled")]
     KLogTimestampsDisabled,
+    #[error("RMesgError:: IntegerOutOfBound: {0}")]
     IntegerOutOfBound(String),
-    Utf8StringConversionError(String),
-    IOError(String),
+    #[error("RMesgError:: Utf8StringConversionError: {message}")]
+    Utf8StringConversionError {
+        message: String,
+        source: Option<ErrorSource>,
+    },
+    #[error("RMesgError:: std::io::Error: {message}")]
+    IOError {
+        message: String,
+        source: Option<ErrorSource>,
+    },
+    #[error("RMesgError:: InternalError: {0}")]
     InternalError(String),
-    EntryParsingError(String),
-    UnableToObtainElapsedTime(SystemTimeError),
+    #[error("RMesgError:: EntryParsingError: {message}")]
+    EntryParsingError {
+        message: String,
+        source: Option<ErrorSource>,
+    },
+    #[error("RMesgError:: UnableToObtainElapsedTime: {0}")]
+    UnableToObtainElapsedTime(#[source] SystemTimeError),
+    #[error("RMesgError:: {0}")]
     DevKMsgFileOpenError(String),
+    #[error("RMesgError:: OperationNotPermitted: {0}")]
     OperationNotPermitted(String),
+    #[error("RMesgError:: WireFormatError: {0}")]
+    WireFormatError(String),
+    #[error("RMesgError:: OpenTimeout: {0}")]
+    OpenTimeout(String),
+    #[error("RMesgError:: SequenceNotFound: Entry {0} is no longer in the kernel log buffer")]
+    SequenceNotFound(u64),
+    #[error("RMesgError:: ReadTimeout: No kernel log entry arrived in time")]
+    ReadTimeout,
+    #[error(
+        "RMesgError:: KLogError: klogctl failed with errno={0} ({})",
+        std::io::Error::from_raw_os_error(*.0)
+    )]
+    KLogError(i32),
+    #[error("RMesgError:: TaskPanicked: {0}")]
+    TaskPanicked(String),
 }
-impl Error for RMesgError {}
-impl Display for RMesgError {
-    fn fmt(&self, f: &mut Formatter) -> FmtResult {
-        write!(
-            f,
-            "RMesgError:: {}",
-            match self {
-                Self::NotImplementedForThisPlatform =>
-                    "RMesg not implemented for this platform.".to_owned(),
-                Self::IntegerOutOfBound(s) => format!("IntegerOutOfBound: {}", s),
-                Self::Utf8StringConversionError(s) => format!("Utf8StringConversionError: {}", s),
-                Self::IOError(s) => format!("std::io::Error: {}", s),
-                Self::InternalError(s) => format!("InternalError: {}", s),
-                Self::EntryParsingError(s) => format!("EntryParsingError: {}", s),
-                Self::UnableToObtainElapsedTime(s) => format!("UnableToObtainElapsedTime: {}", s),
-                Self::UnableToObtainSystemTime => "Failed to get SystemTime.".to_owned(),
-                Self::UnableToAddDurationToSystemTime =>
-                    "Failed to add a Duration to SystemTime".to_owned(),
-                Self::KLogTimestampsDisabled => "Kernel Log timestamps are disabled".to_owned(),
-                Self::DevKMsgFileOpenError(s) => s.to_owned(),
-                Self::OperationNotPermitted(s) => format!("OperationNotPermitted: {}", s),
-            }
-        )
+
+impl RMesgError {
+    /// Whether retrying the operation that failed could succeed: true for an interrupted or
+    /// would-block IOError (or klogctl failing with EINTR or EAGAIN), false for everything else,
+    /// such as missing privileges, disabled timestamps, unparseable entries or a missing device file.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::IOError {
+                source: Some(source),
+                ..
+            } => match source.downcast_ref::<std::io::Error>() {
+                Some(err) => matches!(
+                    err.kind(),
+                    std::io::ErrorKind::WouldBlock | std::io::ErrorKind::Interrupted
+                ),
+                None => false,
+            },
+            Self::KLogError(errno) => *errno == libc::EINTR || *errno == libc::EAGAIN,
+            _ => false,
+        }
+    }
+
+    /// An IOError saying `message`, followed by the error it was caused by, which is kept
+    /// as its source.
+    pub(crate) fn io_error<E: Error + Send + Sync + 'static>(message: &str, err: E) -> RMesgError {
+        RMesgError::IOError {
+            message: format!("{}: {}", message, err),
+            source: Some(Box::new(err)),
+        }
     }
 }
+
+// Written by hand rather than with #[from], since these variants keep a message alongside
+// their source, which #[from] doesn't allow.
 impl From<std::string::FromUtf8Error> for RMesgError {
     fn from(err: std::string::FromUtf8Error) -> RMesgError {
-        RMesgError::Utf8StringConversionError(format!("{:?}", err))
+        RMesgError::Utf8StringConversionError {
+            message: format!("{:?}", err),
+            source: Some(Box::new(err)),
+        }
     }
 }
 
 impl From<std::io::Error> for RMesgError {
     fn from(err: std::io::Error) -> RMesgError {
-        RMesgError::IOError(format!("{:?}", err))
+        RMesgError::IOError {
+            message: format!("{:?}", err),
+            source: Some(Box::new(err)),
+        }
     }
 }
 
 impl From<entry::EntryParsingError> for RMesgError {
     fn from(err: entry::EntryParsingError) -> RMesgError {
-        RMesgError::EntryParsingError(format!("{:?}", err))
+        RMesgError::EntryParsingError {
+            message: format!("{:?}", err),
+            source: Some(Box::new(err)),
+        }
+    }
+}
+
+/**********************************************************************************/
+// Tests! Tests! Tests!
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_source() {
+        let io_err = std::io::Error::other("disk on fire");
+        let display = format!("std::io::Error: {:?}", io_err);
+        let err: RMesgError = io_err.into();
+        assert_eq!(err.to_string(), format!("RMesgError:: {}", display));
+        assert_eq!(err.source().unwrap().to_string(), "disk on fire");
+
+        let err = RMesgError::io_error(
+            "Unable to seek",
+            std::io::Error::from_raw_os_error(libc::ESPIPE),
+        );
+        assert!(err
+            .to_string()
+            .starts_with("RMesgError:: std::io::Error: Unable to seek: "));
+        let source = err.source().unwrap();
+        let source = source.downcast_ref::<std::io::Error>().unwrap();
+        assert_eq!(source.raw_os_error(), Some(libc::ESPIPE));
+
+        let err: RMesgError = String::from_utf8(vec![0xff]).unwrap_err().into();
+        assert!(err.source().is_some());
+
+        assert!(RMesgError::ReadTimeout.source().is_none());
+    }
+
+    #[test]
+    fn test_display() {
+        let message = |message: &str| message.to_owned();
+        let elapsed_err = std::time::UNIX_EPOCH
+            .duration_since(std::time::SystemTime::now())
+            .unwrap_err();
+
+        let snapshots = vec![
+            (
+                RMesgError::NotImplementedForThisPlatform,
+                "RMesgError:: RMesg not implemented for this platform.",
+            ),
+            (
+                RMesgError::UnableToObtainSystemTime,
+                "RMesgError:: Failed to get SystemTime.",
+            ),
+            (
+                RMesgError::UnableToAddDurationToSystemTime,
+                "RMesgError:: Failed to add a Duration to SystemTime",
+            ),
+            (
+                RMesgError::KLogTimestampsDisabled,
+                "RMesgError:: Kernel Log timestamps are disabled",
+            ),
+            (
+                RMesgError::IntegerOutOfBound(message("300 > 255")),
+                "RMesgError:: IntegerOutOfBound: 300 > 255",
+            ),
+            (
+                RMesgError::Utf8StringConversionError {
+                    message: message("invalid utf-8"),
+                    source: None,
+                },
+                "RMesgError:: Utf8StringConversionError: invalid utf-8",
+            ),
+            (
+                RMesgError::IOError {
+                    message: message("broken pipe"),
+                    source: None,
+                },
+                "RMesgError:: std::io::Error: broken pipe",
+            ),
+            (
+                RMesgError::InternalError(message("oops")),
+                "RMesgError:: InternalError: oops",
+            ),
+            (
+                RMesgError::EntryParsingError {
+                    message: message("bad line"),
+                    source: None,
+                },
+                "RMesgError:: EntryParsingError: bad line",
+            ),
+            (
+                RMesgError::UnableToObtainElapsedTime(elapsed_err),
+                "RMesgError:: UnableToObtainElapsedTime: second time provided was later than self",
+            ),
+            (
+                RMesgError::DevKMsgFileOpenError(message("Unable to open file /dev/kmsg")),
+                "RMesgError:: Unable to open file /dev/kmsg",
+            ),
+            (
+                RMesgError::OperationNotPermitted(message("Open File /dev/kmsg")),
+                "RMesgError:: OperationNotPermitted: Open File /dev/kmsg",
+            ),
+            (
+                RMesgError::WireFormatError(message("truncated")),
+                "RMesgError:: WireFormatError: truncated",
+            ),
+            (
+                RMesgError::OpenTimeout(message("took too long")),
+                "RMesgError:: OpenTimeout: took too long",
+            ),
+            (
+                RMesgError::SequenceNotFound(42),
+                "RMesgError:: SequenceNotFound: Entry 42 is no longer in the kernel log buffer",
+            ),
+            (
+                RMesgError::ReadTimeout,
+                "RMesgError:: ReadTimeout: No kernel log entry arrived in time",
+            ),
+            (
+                RMesgError::KLogError(libc::EPERM),
+                "RMesgError:: KLogError: klogctl failed with errno=1 (Operation not permitted (os error 1))",
+            ),
+            (
+                RMesgError::TaskPanicked(message("task panicked")),
+                "RMesgError:: TaskPanicked: task panicked",
+            ),
+        ];
+
+        for (err, expected) in snapshots {
+            assert_eq!(err.to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_is_transient() {
+        let io_err = |kind| RMesgError::from(std::io::Error::from(kind));
+        assert!(io_err(std::io::ErrorKind::WouldBlock).is_transient());
+        assert!(io_err(std::io::ErrorKind::Interrupted).is_transient());
+        assert!(RMesgError::io_error(
+            "Error reading next line",
+            std::io::Error::from_raw_os_error(libc::EAGAIN)
+        )
+        .is_transient());
+        assert!(!io_err(std::io::ErrorKind::NotFound).is_transient());
+        assert!(!RMesgError::IOError {
+            message: "no source".to_owned(),
+            source: None
+        }
+        .is_transient());
+
+        assert!(RMesgError::KLogError(libc::EINTR).is_transient());
+        assert!(RMesgError::KLogError(libc::EAGAIN).is_transient());
+        assert!(!RMesgError::KLogError(libc::EPERM).is_transient());
+
+        assert!(
+            !RMesgError::OperationNotPermitted("Open File /dev/kmsg".to_owned()).is_transient()
+        );
+        assert!(!RMesgError::KLogTimestampsDisabled.is_transient());
+        assert!(!RMesgError::from(entry::EntryParsingError::EmptyLine).is_transient());
+        assert!(
+            !RMesgError::from(entry::EntryParsingError::UnrecognizedFormat(
+                "bad".to_owned()
+            ))
+            .is_transient()
+        );
+        assert!(!RMesgError::from(String::from_utf8(vec![0xff]).unwrap_err()).is_transient());
+        assert!(!RMesgError::DevKMsgFileOpenError("Unable to open file".to_owned()).is_transient());
     }
 }
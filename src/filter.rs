@@ -0,0 +1,426 @@
+use crate::entry::Entry;
+use crate::error::RMesgError;
+
+use std::iter::Iterator;
+use std::time::Duration;
+
+/// Whether a filter keeps the entries that don't have the field it checks.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum NonePolicy {
+    Include,
+    Exclude,
+}
+
+/// Keeps the entries `keep` returns true for. Errors are passed through.
+fn keep_entries<I, F>(iter: I, keep: F) -> impl Iterator<Item = Result<Entry, RMesgError>>
+where
+    I: Iterator<Item = Result<Entry, RMesgError>>,
+    F: Fn(&Entry) -> bool,
+{
+    iter.filter(move |result| match result {
+        Ok(entry) => keep(entry),
+        Err(_) => true,
+    })
+}
+
+/// Keeps entries at least as severe as `max`, that is with a numerically lower or equal
+/// level (0 is the most severe). Entries without a level are dropped.
+pub fn by_max_level<I>(iter: I, max: u8) -> impl Iterator<Item = Result<Entry, RMesgError>>
+where
+    I: Iterator<Item = Result<Entry, RMesgError>>,
+{
+    keep_entries(iter, move |entry| {
+        entry.level.is_some_and(|level| level.as_u8() <= max)
+    })
+}
+
+/// Keeps entries with the facility whose kernel value is `facility`, from 0 (kern) to 255.
+/// Entries without a facility are dropped.
+pub fn by_facility<I>(iter: I, facility: u8) -> impl Iterator<Item = Result<Entry, RMesgError>>
+where
+    I: Iterator<Item = Result<Entry, RMesgError>>,
+{
+    keep_entries(iter, move |entry| {
+        entry.facility.is_some_and(|f| f.as_u8() == facility)
+    })
+}
+
+/// Keeps entries whose message matches the regular expression `pattern` anywhere.
+/// Fails if `pattern` isn't a valid regular expression.
+#[cfg(feature = "regex")]
+pub fn by_message_regex<I>(
+    iter: I,
+    pattern: &str,
+) -> Result<impl Iterator<Item = Result<Entry, RMesgError>>, regex::Error>
+where
+    I: Iterator<Item = Result<Entry, RMesgError>>,
+{
+    let re = regex::Regex::new(pattern)?;
+    Ok(keep_entries(iter, move |entry| re.is_match(&entry.message)))
+}
+
+/// Keeps entries with a sequence number from `start` to `end`, both included.
+/// Entries without a sequence number are dropped.
+pub fn by_sequence_range<I>(
+    iter: I,
+    start: u64,
+    end: u64,
+) -> impl Iterator<Item = Result<Entry, RMesgError>>
+where
+    I: Iterator<Item = Result<Entry, RMesgError>>,
+{
+    keep_entries(iter, move |entry| {
+        entry
+            .sequence_num
+            .is_some_and(|seq| start <= seq && seq <= end)
+    })
+}
+
+/// Keeps entries timestamped from `start` to `end` after the system started, both included.
+/// `none_policy` decides whether entries without a timestamp are kept.
+pub fn by_timestamp_range<I: Iterator<Item = Result<Entry, RMesgError>>>(
+    iter: I,
+    start: Duration,
+    end: Duration,
+    none_policy: NonePolicy,
+) -> impl Iterator<Item = Result<Entry, RMesgError>> {
+    keep_entries(iter, move |entry| match entry.timestamp_from_system_start {
+        Some(timestamp) => start <= timestamp && timestamp <= end,
+        None => none_policy == NonePolicy::Include,
+    })
+}
+
+/// Several conditions on entries, combined into a single predicate by `build()`.
+///
+/// Each condition set must hold. Entries missing the field a condition checks
+/// (such as continuation lines, which have no level) don't match it.
+///
+/// ```
+/// use rmesg::entry::Entry;
+/// use rmesg::filter::EntryFilter;
+///
+/// let usb_warnings = EntryFilter::new().max_level(4).message_contains("usb").build();
+/// assert!(usb_warnings(&"4,1,100,-;usb 1-1: reset high-speed USB device".parse::<Entry>().unwrap()));
+/// assert!(!usb_warnings(&"6,2,200,-;usb 1-1: new high-speed USB device".parse::<Entry>().unwrap()));
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct EntryFilter {
+    max_level: Option<u8>,
+    facility: Option<u8>,
+    message_contains: Option<String>,
+    timestamp_range: Option<(Duration, Duration)>,
+    any_of: Option<Vec<EntryFilter>>,
+}
+
+impl EntryFilter {
+    /// A filter that matches every entry, until conditions are added.
+    pub fn new() -> EntryFilter {
+        EntryFilter::default()
+    }
+
+    /// A filter that matches entries matched by at least one of `filters` (so none, if
+    /// `filters` is empty). More conditions can be added, which must hold as well.
+    pub fn any_of(filters: Vec<EntryFilter>) -> EntryFilter {
+        EntryFilter {
+            any_of: Some(filters),
+            ..EntryFilter::default()
+        }
+    }
+
+    /// Entries at least as severe as `max`, as in `by_max_level`
+    pub fn max_level(mut self, max: u8) -> EntryFilter {
+        self.max_level = Some(max);
+        self
+    }
+
+    /// Entries with the facility whose kernel value is `facility`, as in `by_facility`
+    pub fn facility(mut self, facility: u8) -> EntryFilter {
+        self.facility = Some(facility);
+        self
+    }
+
+    /// Entries whose message contains `pattern`
+    pub fn message_contains(mut self, pattern: &str) -> EntryFilter {
+        self.message_contains = Some(pattern.to_owned());
+        self
+    }
+
+    /// Entries timestamped from `start` to `end`, both included, as in `by_timestamp_range`
+    /// with `NonePolicy::Exclude`
+    pub fn timestamp_range(mut self, start: Duration, end: Duration) -> EntryFilter {
+        self.timestamp_range = Some((start, end));
+        self
+    }
+
+    /// Whether `entry` meets every condition.
+    pub fn matches(&self, entry: &Entry) -> bool {
+        if let Some(max) = self.max_level {
+            if !entry.level.is_some_and(|level| level.as_u8() <= max) {
+                return false;
+            }
+        }
+
+        if let Some(facility) = self.facility {
+            if !entry.facility.is_some_and(|f| f.as_u8() == facility) {
+                return false;
+            }
+        }
+
+        if let Some(pattern) = &self.message_contains {
+            if !entry.message.contains(pattern.as_str()) {
+                return false;
+            }
+        }
+
+        if let Some((start, end)) = self.timestamp_range {
+            if !entry
+                .timestamp_from_system_start
+                .is_some_and(|timestamp| start <= timestamp && timestamp <= end)
+            {
+                return false;
+            }
+        }
+
+        match &self.any_of {
+            Some(filters) => filters.iter().any(|filter| filter.matches(entry)),
+            None => true,
+        }
+    }
+
+    /// The predicate, such as for `EntriesIterator::filter_entries`.
+    pub fn build(self) -> impl Fn(&Entry) -> bool + Send + Sync + 'static {
+        move |entry| self.matches(entry)
+    }
+}
+
+/**********************************************************************************/
+// Tests! Tests! Tests!
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::entry::{LogFacility, LogLevel};
+    use crate::EntriesIterator;
+
+    fn entries() -> Vec<Result<Entry, RMesgError>> {
+        vec![
+            Ok(Entry::builder()
+                .facility(LogFacility::Kern)
+                .level(LogLevel::Error)
+                .sequence_num(1)
+                .message("usb 1-1: device descriptor read/64, error -71")
+                .build()),
+            Ok(Entry::builder().message(" LINE2=continuation").build()),
+            Err(RMesgError::InternalError("broken".to_owned())),
+            Ok(Entry::builder()
+                .facility(LogFacility::Daemon)
+                .level(LogLevel::Info)
+                .sequence_num(2)
+                .message("systemd[1]: Started Journal Service.")
+                .build()),
+            Ok(Entry::builder()
+                .facility(LogFacility::Kern)
+                .level(LogLevel::Warning)
+                .sequence_num(3)
+                .message("usb 1-1: new high-speed USB device number 2")
+                .build()),
+        ]
+    }
+
+    fn messages(iter: impl Iterator<Item = Result<Entry, RMesgError>>) -> Vec<String> {
+        iter.map(|result| match result {
+            Ok(entry) => entry.message,
+            Err(_) => "error".to_owned(),
+        })
+        .collect()
+    }
+
+    #[test]
+    fn test_by_max_level() {
+        let filtered = messages(by_max_level(entries().into_iter(), LogLevel::Warning as u8));
+        assert_eq!(
+            filtered,
+            vec![
+                "usb 1-1: device descriptor read/64, error -71",
+                "error",
+                "usb 1-1: new high-speed USB device number 2",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_by_facility() {
+        let filtered = messages(by_facility(
+            entries().into_iter(),
+            LogFacility::Daemon.as_u8(),
+        ));
+        assert_eq!(
+            filtered,
+            vec!["error", "systemd[1]: Started Journal Service."]
+        );
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_by_message_regex() {
+        let filtered = messages(by_message_regex(entries().into_iter(), r"^usb \d-\d:").unwrap());
+        assert_eq!(
+            filtered,
+            vec![
+                "usb 1-1: device descriptor read/64, error -71",
+                "error",
+                "usb 1-1: new high-speed USB device number 2",
+            ]
+        );
+
+        assert!(by_message_regex(entries().into_iter(), "(unclosed").is_err());
+    }
+
+    #[test]
+    fn test_by_sequence_range() {
+        let filtered = messages(by_sequence_range(entries().into_iter(), 2, 3));
+        assert_eq!(
+            filtered,
+            vec![
+                "error",
+                "systemd[1]: Started Journal Service.",
+                "usb 1-1: new high-speed USB device number 2",
+            ]
+        );
+
+        assert_eq!(
+            messages(by_sequence_range(entries().into_iter(), 3, 2)),
+            vec!["error"]
+        );
+    }
+
+    #[test]
+    fn test_by_timestamp_range() {
+        let entry = |millis: Option<u64>, message: &str| {
+            Ok(Entry::builder()
+                .maybe_timestamp(millis.map(Duration::from_millis))
+                .message(message)
+                .build())
+        };
+        let entries = || {
+            vec![
+                entry(Some(500), "before"),
+                entry(Some(1000), "start"),
+                entry(None, " LINE2=continuation"),
+                entry(Some(1500), "within"),
+                Err(RMesgError::InternalError("broken".to_owned())),
+                entry(Some(2000), "end"),
+                entry(Some(2001), "after"),
+            ]
+        };
+        let (start, end) = (Duration::from_secs(1), Duration::from_secs(2));
+
+        let included = messages(by_timestamp_range(
+            entries().into_iter(),
+            start,
+            end,
+            NonePolicy::Include,
+        ));
+        assert_eq!(
+            included,
+            vec!["start", " LINE2=continuation", "within", "error", "end"]
+        );
+
+        let excluded = messages(by_timestamp_range(
+            entries().into_iter(),
+            start,
+            end,
+            NonePolicy::Exclude,
+        ));
+        assert_eq!(excluded, vec!["start", "within", "error", "end"]);
+    }
+
+    #[test]
+    fn test_entry_filter() {
+        let entry = |facility: LogFacility, level: LogLevel, secs: u64, message: &str| {
+            Entry::builder()
+                .facility(facility)
+                .level(level)
+                .timestamp(Duration::from_secs(secs))
+                .message(message)
+                .build()
+        };
+        let fixtures = vec![
+            entry(
+                LogFacility::Kern,
+                LogLevel::Warning,
+                5,
+                "usb 1-1: USB disconnect",
+            ),
+            entry(
+                LogFacility::Kern,
+                LogLevel::Info,
+                6,
+                "usb 1-1: new USB device",
+            ),
+            entry(
+                LogFacility::Kern,
+                LogLevel::Error,
+                50,
+                "usb 1-1: USB reset failed",
+            ),
+            entry(
+                LogFacility::Daemon,
+                LogLevel::Error,
+                7,
+                "USB daemon crashed",
+            ),
+            entry(
+                LogFacility::Kern,
+                LogLevel::Critical,
+                8,
+                "Out of memory: Killed process 42",
+            ),
+            entry(LogFacility::User, LogLevel::Debug, 9, "debugging USB"),
+            Entry::builder().message(" LINE2=USB continuation").build(),
+        ];
+
+        // Kernel USB warnings (or worse) during the first 10 seconds, any out-of-memory kill,
+        // or debug messages from users
+        let usb_warnings = EntryFilter::new()
+            .max_level(LogLevel::Warning as u8)
+            .facility(LogFacility::Kern.as_u8())
+            .message_contains("USB")
+            .timestamp_range(Duration::from_secs(0), Duration::from_secs(10));
+        let oom_kills = EntryFilter::new().message_contains("Out of memory");
+        let user_debug = EntryFilter::new().facility(LogFacility::User.as_u8());
+        let filter = EntryFilter::any_of(vec![usb_warnings, oom_kills, user_debug]).build();
+
+        let matched: Vec<&str> = fixtures
+            .iter()
+            .filter(|entry| filter(entry))
+            .map(|entry| entry.message.as_str())
+            .collect();
+        assert_eq!(
+            matched,
+            vec![
+                "usb 1-1: USB disconnect",
+                "Out of memory: Killed process 42",
+                "debugging USB",
+            ]
+        );
+
+        // Conditions added to any_of must hold too
+        let kern_only = EntryFilter::any_of(vec![
+            EntryFilter::new().message_contains("USB"),
+            EntryFilter::new().message_contains("memory"),
+        ])
+        .facility(LogFacility::Kern.as_u8())
+        .build();
+        assert_eq!(fixtures.iter().filter(|entry| kern_only(entry)).count(), 4);
+
+        assert!(fixtures.iter().all(EntryFilter::new().build()));
+        assert!(!fixtures.iter().any(EntryFilter::any_of(vec![]).build()));
+
+        let filtered: Vec<Result<Entry, RMesgError>> =
+            EntriesIterator::from_dyn(Box::new(fixtures.into_iter().map(Ok)))
+                .filter_entries(EntryFilter::new().max_level(LogLevel::Error as u8).build())
+                .collect();
+        assert_eq!(filtered.len(), 3);
+    }
+}
@@ -0,0 +1,72 @@
+use crate::entry::Entry;
+
+use std::collections::BTreeMap;
+
+/// Maps each entry's sequence number to its position in `entries`, for random access by
+/// sequence number. Entries without a sequence number (such as continuation lines, or all
+/// entries from klogctl) are left out.
+pub fn build_sequence_index(entries: &[Entry]) -> BTreeMap<u64, usize> {
+    entries
+        .iter()
+        .enumerate()
+        .filter_map(|(position, entry)| {
+            entry
+                .sequence_num
+                .map(|sequence_num| (sequence_num, position))
+        })
+        .collect()
+}
+
+/// Looks up the entry with sequence number `seq`, using an index from `build_sequence_index`.
+pub fn find_by_sequence<'a>(
+    entries: &'a [Entry],
+    index: &BTreeMap<u64, usize>,
+    seq: u64,
+) -> Option<&'a Entry> {
+    index.get(&seq).and_then(|position| entries.get(*position))
+}
+
+/// All entries from the one with sequence number `seq` onwards (or from the next one after it,
+/// when there's no such entry), using an index from `build_sequence_index`.
+/// Empty when no entry is that recent.
+pub fn entries_since_sequence<'a>(
+    entries: &'a [Entry],
+    index: &BTreeMap<u64, usize>,
+    seq: u64,
+) -> &'a [Entry] {
+    match index.range(seq..).next() {
+        Some((_, position)) => entries.get(*position..).unwrap_or(&[]),
+        None => &[],
+    }
+}
+
+/**********************************************************************************/
+// Tests! Tests! Tests!
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_sequence_index() {
+        let entries = vec![
+            Entry::builder().sequence_num(10).message("ten").build(),
+            Entry::builder().message(" LINE2=continuation").build(),
+            Entry::builder().sequence_num(11).message("eleven").build(),
+            Entry::builder()
+                .sequence_num(13)
+                .message("thirteen")
+                .build(),
+        ];
+        let index = build_sequence_index(&entries);
+        assert_eq!(index.len(), 3);
+
+        assert_eq!(find_by_sequence(&entries, &index, 11), Some(&entries[2]));
+        assert_eq!(find_by_sequence(&entries, &index, 12), None);
+
+        assert_eq!(entries_since_sequence(&entries, &index, 10), &entries[..]);
+        assert_eq!(entries_since_sequence(&entries, &index, 11), &entries[2..]);
+        assert_eq!(entries_since_sequence(&entries, &index, 12), &entries[3..]);
+        assert!(entries_since_sequence(&entries, &index, 14).is_empty());
+    }
+}
@@ -1,5 +1,5 @@
 use crate::common;
-use crate::entry::{Entry, EntryParsingError};
+use crate::entry::{classify_message, Entry, EntryParsingError};
 /// This crate provides a klogctl interface from Rust.
 /// klogctl is a Linux syscall that allows reading the Linux Kernel Log buffer.
 /// https://elinux.org/Debugging_by_printing
@@ -10,12 +10,20 @@ use crate::entry::{Entry, EntryParsingError};
 /// This allows Rust programs to consume dmesg-like output programmatically.
 ///
 use crate::error::RMesgError;
+use crate::Backend;
 
 use errno::errno;
+#[cfg(rmesg_regex_parser)]
 use lazy_static::lazy_static;
+use num::FromPrimitive;
+use num_derive::FromPrimitive;
+#[cfg(rmesg_regex_parser)]
 use regex::Regex;
+use std::collections::HashMap;
 use std::convert::TryFrom;
 use std::fs;
+use std::io::Write;
+use std::path::Path;
 use std::time::{Duration, SystemTime};
 use strum_macros::Display;
 
@@ -42,7 +50,7 @@ unsafe fn klogctl(
 
 // SYSLOG constants
 // https://linux.die.net/man/3/klogctl
-#[derive(Debug, Display, Clone)]
+#[derive(Debug, Display, Clone, FromPrimitive)]
 pub enum KLogType {
     SyslogActionClose,
     SyslogActionOpen,
@@ -62,9 +70,17 @@ pub type SignedInt = libc::c_int;
 /// The path under /proc where the parameter to set (or unset) logging a timestamp resides
 pub const SYS_MODULE_PRINTK_PARAMETERS_TIME: &str = "/sys/module/printk/parameters/time";
 
+/// The path under /proc holding the console, default message, minimum console and default
+/// console log levels, in that order
+pub const PROC_SYS_KERNEL_PRINTK: &str = "/proc/sys/kernel/printk";
+
 /// suggest polling every ten seconds
+#[cfg(not(rmesg_embedded))]
 pub const SUGGESTED_POLL_INTERVAL: std::time::Duration = Duration::from_secs(10);
+#[cfg(rmesg_embedded)]
+pub const SUGGESTED_POLL_INTERVAL: std::time::Duration = Duration::from_secs(5);
 
+#[cfg(rmesg_regex_parser)]
 lazy_static! {
     static ref RE_ENTRY_WITH_TIMESTAMP: Regex = Regex::new(
         r"(?x)^
@@ -92,6 +108,11 @@ lazy_static! {
 /// lines to not be ignored. In order to aid this, two functions are provided in this crate to
 /// check `kernel_log_timestamps_enabled` and to set or unset `kernel_log_timestamps_enable`.
 ///
+/// The klogctl interface does not provide sequence numbers, so as in `KLogEntriesNoTimestamp`,
+/// each entry's `sequence_num` is set to its position (starting at zero) among the entries
+/// returned. Unless the buffer is cleared or wraps around while it's being read, that's the
+/// entry's line position within the buffer.
+///
 /// The UX is left to the consumer.
 ///
 pub struct KLogEntries {
@@ -101,7 +122,14 @@ pub struct KLogEntries {
     poll_interval: Duration,
     sleep_interval: Duration, // Just slightly longer than poll interval so the check passes
     last_poll: SystemTime,
-
+    // Entries parsed from a buffer passed to `from_str_buffer`, in which case klogctl is never called
+    from_buffer: Option<std::vec::IntoIter<Result<Entry, EntryParsingError>>>,
+    // Sequence number of the last entry returned that had one, for last_sequence_num()
+    last_seq: Option<u64>,
+    // Position of the next entry returned, used as its sequence number
+    next_position: u64,
+    // Entries positioned before this are skipped, see set_start_sequence_num()
+    start_seq: u64,
 }
 
 impl KLogEntries {
@@ -142,9 +170,62 @@ impl KLogEntries {
             last_poll,
             clear,
             last_timestamp: None,
+            from_buffer: None,
+            last_seq: None,
+            next_position: 0,
+            start_seq: 0,
         })
     }
 
+    /// FOR TESTING ONLY: Create a KLogEntries over the klog-formatted lines in `buffer`
+    /// (as `klog_raw` would return them) instead of the kernel log buffer.
+    ///
+    /// The iterator yields each line parsed, then returns `None` once they're all returned.
+    /// It never calls klogctl, so code consuming a KLogEntries can be tested without root
+    /// privileges or a live kernel. `poll_interval` is kept, but never used.
+    pub fn from_str_buffer(buffer: String, poll_interval: Duration) -> KLogEntries {
+        let entries: Vec<Result<Entry, EntryParsingError>> = buffer
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(entry_from_line)
+            .collect();
+
+        KLogEntries {
+            entries: Vec::new(),
+            poll_interval,
+            sleep_interval: poll_interval.saturating_add(Duration::from_millis(200)),
+            last_poll: SystemTime::now(),
+            clear: false,
+            last_timestamp: None,
+            from_buffer: Some(entries.into_iter()),
+            last_seq: None,
+            next_position: 0,
+            start_seq: 0,
+        }
+    }
+
+    /// The sequence number (that is, the position) of the last entry `next()` returned, or
+    /// `None` if it hasn't returned any.
+    pub fn last_sequence_num(&self) -> Option<u64> {
+        self.last_seq
+    }
+
+    /// Skips the entries numbered before `seq`, such as the ones a previous run already
+    /// processed, up to `last_sequence_num() + 1`. Entries are numbered by position, so this
+    /// starts at the wrong entry if the buffer was cleared or wrapped around since.
+    pub fn set_start_sequence_num(&mut self, seq: u64) {
+        self.start_seq = seq;
+    }
+
+    /// Changes the poll interval set by `with_options`, starting with the next call to `next()`.
+    ///
+    /// This lets a consumer poll less often while the log is quiet, and more often while
+    /// it's busy.
+    pub fn set_poll_interval(&mut self, interval: Duration) {
+        self.poll_interval = interval;
+        self.sleep_interval = interval.saturating_add(Duration::from_millis(200));
+    }
+
     /// This method conducts the actual polling of the log buffer.
     ///
     /// It tracks the timestamp of the last line buffered, and only adds lines
@@ -200,6 +281,39 @@ impl Iterator for KLogEntries {
     /// NOT a thread-safe method either. It is suggested this method be always
     /// blocked on to ensure no messages are missed.
     fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let mut next = self.next_entry();
+            if let Some(Ok(entry)) = &mut next {
+                let position = self.next_position;
+                self.next_position += 1;
+                if position < self.start_seq {
+                    continue;
+                }
+
+                entry.sequence_num = Some(position);
+                self.last_seq = entry.sequence_num;
+            }
+
+            return next;
+        }
+    }
+
+    /// At least the entries already buffered. There's no upper bound, since the
+    /// iterator keeps polling for new entries forever (unless created by `from_str_buffer`).
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match &self.from_buffer {
+            Some(from_buffer) => from_buffer.size_hint(),
+            None => (self.entries.len(), None),
+        }
+    }
+}
+
+impl KLogEntries {
+    fn next_entry(&mut self) -> Option<Result<Entry, RMesgError>> {
+        if let Some(from_buffer) = &mut self.from_buffer {
+            return from_buffer.next().map(|result| Ok(result?));
+        }
+
         while self.entries.is_empty() {
             let elapsed = match self.last_poll.elapsed() {
                 Ok(duration) => duration,
@@ -223,6 +337,55 @@ impl Iterator for KLogEntries {
     }
 }
 
+/// Reads the kernel log buffer once and iterates over the lines that were in it,
+/// without requiring kernel log timestamps to be enabled.
+///
+/// Unlike `KLogEntries`, this iterator does not follow/tail the buffer. Since it never
+/// needs to tell new lines apart from ones it has already seen, it has no use for timestamps
+/// and works whether or not `klog_timestamps_enabled()` is true.
+///
+/// The klogctl interface does not provide sequence numbers, so each entry's `sequence_num`
+/// is set to its line position (starting at zero) within the buffer as read. Timestamps are
+/// kept when present, and are `None` otherwise.
+///
+pub struct KLogEntriesNoTimestamp {
+    entries: std::vec::IntoIter<Entry>,
+}
+
+impl KLogEntriesNoTimestamp {
+    /// Create a new KLogEntriesNoTimestamp, reading the buffer immediately.
+    /// `clear: bool` specifies Whether or not to clear the buffer after it is read.
+    pub fn with_options(clear: bool) -> Result<KLogEntriesNoTimestamp, RMesgError> {
+        let entries: Vec<Entry> = klog(clear)?
+            .into_iter()
+            .enumerate()
+            .map(|(position, mut entry)| {
+                entry.sequence_num = Some(position as u64);
+                entry
+            })
+            .collect();
+
+        Ok(KLogEntriesNoTimestamp {
+            entries: entries.into_iter(),
+        })
+    }
+}
+
+/// Trait to iterate over lines of the kernel log buffer.
+impl Iterator for KLogEntriesNoTimestamp {
+    type Item = Result<Entry, RMesgError>;
+
+    /// Returns the next line read from the buffer, or `None` once all of them are returned.
+    fn next(&mut self) -> Option<Self::Item> {
+        self.entries.next().map(Ok)
+    }
+
+    /// Exact, since all entries are read up front.
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.entries.size_hint()
+    }
+}
+
 /// This is the key safe function that makes the klogctl syslog call with parameters.
 /// While the internally used function supports all klogctl parameters, this function
 /// only provides one bool parameter which indicates whether the buffer is to be cleared
@@ -232,6 +395,18 @@ impl Iterator for KLogEntries {
 /// whether or not "async" feature is enabled
 ///
 pub fn klog_raw(clear: bool) -> Result<String, RMesgError> {
+    let mut buffer: Vec<u8> = Vec::new();
+    klog_raw_to_writer(clear, &mut buffer)?;
+
+    Ok(String::from_utf8(buffer)?)
+}
+
+/// Same as `klog_raw`, but writes the buffer to `writer` instead of returning it, and
+/// returns the number of bytes written.
+///
+/// klogctl still reads the buffer into a single allocation, but it isn't copied again
+/// (into a String) when all the caller does is write it to a file or socket.
+pub fn klog_raw_to_writer<W: Write>(clear: bool, writer: &mut W) -> Result<usize, RMesgError> {
     let mut dummy_buffer: Vec<u8> = vec![0; 0];
     let kernel_buffer_size =
         safely_wrapped_klogctl(KLogType::SyslogActionSizeBuffer, &mut dummy_buffer)?;
@@ -244,12 +419,9 @@ pub fn klog_raw(clear: bool) -> Result<String, RMesgError> {
     let mut real_buffer: Vec<u8> = vec![0; kernel_buffer_size];
     let bytes_read = safely_wrapped_klogctl(klogtype, &mut real_buffer)?;
 
-    //adjust buffer capacity to what was read
-    real_buffer.resize(bytes_read, 0);
-    let utf8_str = String::from_utf8(real_buffer)?;
+    writer.write_all(&real_buffer[..bytes_read])?;
 
-    // if incremental,
-    Ok(utf8_str)
+    Ok(bytes_read)
 }
 
 /// This is the key safe function that makes the klogctl syslog call with parameters.
@@ -262,26 +434,207 @@ pub fn klog_raw(clear: bool) -> Result<String, RMesgError> {
 ///
 pub fn klog(clear: bool) -> Result<Vec<Entry>, RMesgError> {
     let all_lines = klog_raw(clear)?;
-    Ok(entries_from_lines(&all_lines)?)
+    let mut entries = entries_from_lines(&all_lines)?;
+    for entry in entries.iter_mut() {
+        entry.source_backend = Some(Backend::KLogCtl);
+    }
+
+    Ok(entries)
+}
+
+/// Reads the whole kernel log buffer and clears it, in a single klogctl call, so no entry
+/// logged in between is lost. Same as `klog(true)`.
+///
+/// This is not idempotent: calling it again returns only the entries logged since the
+/// previous call. Other readers of the buffer miss whatever it cleared.
+pub fn klog_read_clear() -> Result<Vec<Entry>, RMesgError> {
+    klog(true)
+}
+
+/// Clears the kernel log buffer without reading it.
+///
+/// This is destructive: every entry in the buffer is discarded, for every reader.
+///
+/// Returns `RMesgError::KLogError` with the errno klogctl set on failure.
+pub fn klog_clear() -> Result<(), RMesgError> {
+    // Safe because SyslogActionClear takes no buffer
+    unsafe {
+        klogctl_errno(
+            KLogType::SyslogActionClear as libc::c_int,
+            std::ptr::null_mut(),
+            0,
+        )?
+    };
+
+    Ok(())
 }
 
 /// This function checks whether or not timestamps are enabled in the Linux Kernel log entries.
 pub fn klog_timestamps_enabled() -> Result<bool, RMesgError> {
-    Ok(fs::read_to_string(SYS_MODULE_PRINTK_PARAMETERS_TIME)?
-        .trim()
-        .to_uppercase()
-        == "Y")
+    klog_timestamps_enabled_at(None)
+}
+
+/// Same as `klog_timestamps_enabled`, reading `file_override` (when `Some`) rather than
+/// `SYS_MODULE_PRINTK_PARAMETERS_TIME`.
+pub fn klog_timestamps_enabled_at(file_override: Option<&Path>) -> Result<bool, RMesgError> {
+    let path = file_override.unwrap_or_else(|| Path::new(SYS_MODULE_PRINTK_PARAMETERS_TIME));
+
+    Ok(fs::read_to_string(path)?.trim().to_uppercase() == "Y")
 }
 
 /// This function can enable or disable whether or not timestamps are enabled in the Linux Kernel log entries.
+/// Returns `RMesgError::OperationNotPermitted` without the privileges to do so.
 pub fn klog_timestamps_enable(desired: bool) -> Result<(), RMesgError> {
-    Ok(fs::write(
-        SYS_MODULE_PRINTK_PARAMETERS_TIME,
+    klog_timestamps_enable_at(desired, None)
+}
+
+/// Same as `klog_timestamps_enable`, writing `file_override` (when `Some`) rather than
+/// `SYS_MODULE_PRINTK_PARAMETERS_TIME`.
+pub fn klog_timestamps_enable_at(
+    desired: bool,
+    file_override: Option<&Path>,
+) -> Result<(), RMesgError> {
+    let path = file_override.unwrap_or_else(|| Path::new(SYS_MODULE_PRINTK_PARAMETERS_TIME));
+
+    let result = fs::write(
+        path,
         match desired {
             true => "Y\n",
             false => "N\n",
         },
-    )?)
+    );
+
+    match result {
+        Ok(()) => Ok(()),
+        Err(e) if e.raw_os_error() == Some(libc::EPERM) => Err(RMesgError::OperationNotPermitted(
+            format!("Write to File {}", path.display()),
+        )),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// The size, in bytes, of the kernel log buffer.
+///
+/// Returns `RMesgError::KLogError` with the errno klogctl set on failure.
+pub fn klog_buffer_size() -> Result<usize, RMesgError> {
+    klog_size(KLogType::SyslogActionSizeBuffer)
+}
+
+/// The number of bytes in the kernel log buffer not yet read through `SyslogActionRead`.
+///
+/// Once this approaches `klog_buffer_size()`, the oldest unread messages are about to be
+/// overwritten.
+///
+/// Returns `RMesgError::KLogError` with the errno klogctl set on failure.
+pub fn klog_unread_size() -> Result<usize, RMesgError> {
+    klog_size(KLogType::SyslogActionSizeUnread)
+}
+
+/// Resumes printing kernel messages to the console, after `klog_console_off`.
+///
+/// Returns `RMesgError::KLogError` with the errno klogctl set on failure.
+pub fn klog_console_on() -> Result<(), RMesgError> {
+    // Safe because SyslogActionConsoleOn takes no buffer
+    unsafe {
+        klogctl_errno(
+            KLogType::SyslogActionConsoleOn as libc::c_int,
+            std::ptr::null_mut(),
+            0,
+        )?
+    };
+
+    Ok(())
+}
+
+/// Stops printing kernel messages to the console (they are still logged to the buffer),
+/// until `klog_console_on` is called.
+///
+/// Returns `RMesgError::KLogError` with the errno klogctl set on failure.
+pub fn klog_console_off() -> Result<(), RMesgError> {
+    // Safe because SyslogActionConsoleOff takes no buffer
+    unsafe {
+        klogctl_errno(
+            KLogType::SyslogActionConsoleOff as libc::c_int,
+            std::ptr::null_mut(),
+            0,
+        )?
+    };
+
+    Ok(())
+}
+
+/// Turns the console back on (see `klog_console_on`) when dropped.
+///
+/// Errors turning the console on are ignored, since there's no way to return them from `drop`.
+pub struct KLogConsoleGuard {
+    _private: (),
+}
+
+impl Drop for KLogConsoleGuard {
+    fn drop(&mut self) {
+        let _ = klog_console_on();
+    }
+}
+
+/// Calls `f`, then turns the console back on, even if `f` panics.
+///
+/// This is meant for code that calls `klog_console_off` to keep kernel messages off the
+/// console for a while (for instance, during an automated test).
+pub fn klog_console_on_after<F: FnOnce() -> T, T>(f: F) -> T {
+    let _guard = KLogConsoleGuard { _private: () };
+    f()
+}
+
+/// Sets the console log level: only messages more severe than `level` (that is, with a
+/// numerically lower level) are printed to the console. The kernel accepts 1 through 8.
+///
+/// Returns `RMesgError::KLogError` with the errno klogctl set on failure (`EINVAL` for a
+/// level out of range, `EPERM` without CAP_SYSLOG or CAP_SYS_ADMIN).
+pub fn klog_console_level_set(level: u8) -> Result<(), RMesgError> {
+    // Safe because SyslogActionConsoleLevel takes no buffer: the level is passed in `len`
+    unsafe {
+        klogctl_errno(
+            KLogType::SyslogActionConsoleLevel as libc::c_int,
+            std::ptr::null_mut(),
+            libc::c_int::from(level),
+        )?
+    };
+
+    Ok(())
+}
+
+/// Gets the console log level, as set by `klog_console_level_set`.
+///
+/// klogctl has no action to read it back, so this is the first field of `PROC_SYS_KERNEL_PRINTK`.
+pub fn klog_console_level_get() -> Result<u8, RMesgError> {
+    klog_console_level_get_at(None)
+}
+
+/// Same as `klog_console_level_get`, reading `file_override` (when `Some`) rather than
+/// `PROC_SYS_KERNEL_PRINTK`.
+pub fn klog_console_level_get_at(file_override: Option<&Path>) -> Result<u8, RMesgError> {
+    let path = file_override.unwrap_or_else(|| Path::new(PROC_SYS_KERNEL_PRINTK));
+
+    let contents = fs::read_to_string(path)?;
+    let console_level = match contents.split_whitespace().next() {
+        Some(console_level) => console_level,
+        None => {
+            return Err(RMesgError::InternalError(format!(
+                "File {} is empty; expected the console log level",
+                path.display()
+            )))
+        }
+    };
+
+    match console_level.parse::<u8>() {
+        Ok(level) => Ok(level),
+        Err(e) => Err(RMesgError::IntegerOutOfBound(format!(
+            "Unable to parse console log level {} from {}: {}",
+            console_level,
+            path.display(),
+            e
+        ))),
+    }
 }
 
 // Message spec: https://github.com/torvalds/linux/blob/master/Documentation/ABI/testing/dev-kmsg
@@ -290,13 +643,21 @@ pub fn klog_timestamps_enable(desired: bool) -> Result<(), RMesgError> {
 // OR
 // <5>[   233434.343533] a.out[4054]: segfault at 7ffd5503d358 ip 00007ffd5503d358 sp 00007ffd5503d258 error 15
 pub fn entries_from_lines(all_lines: &str) -> Result<Vec<Entry>, EntryParsingError> {
-    let entry_results: Result<Vec<Entry>, EntryParsingError> =
-        all_lines.lines().map(entry_from_line).collect();
+    let entry_results: Result<Vec<Entry>, EntryParsingError> = all_lines
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(entry_from_line)
+        .collect();
 
     entry_results
 }
 
+#[cfg(rmesg_regex_parser)]
 pub fn entry_from_line(line: &str) -> Result<Entry, EntryParsingError> {
+    if line.is_empty() {
+        return Err(EntryParsingError::EmptyLine);
+    }
+
     if let Some(klogparts) = RE_ENTRY_WITH_TIMESTAMP.captures(line) {
         let (facility, level) = match klogparts.name("faclevstr") {
             Some(faclevstr) => common::parse_favlecstr(faclevstr.as_str(), line)?,
@@ -308,28 +669,169 @@ pub fn entry_from_line(line: &str) -> Result<Entry, EntryParsingError> {
             None => None,
         };
 
-        let message = klogparts["message"].to_owned();
+        let message = &klogparts["message"];
+
+        Ok(Entry {
+            facility,
+            level,
+            sequence_num: None,
+            timestamp_from_system_start,
+            message: message.to_owned(),
+            category: classify_message(message),
+            raw_flags: None,
+            dict: HashMap::new(),
+            source_backend: None,
+        })
+    } else {
+        // No priority, such as the first line read after the buffer wrapped around, which
+        // may have lost its start: it's all message
+        Ok(Entry {
+            facility: None,
+            level: None,
+            sequence_num: None,
+            timestamp_from_system_start: None,
+            message: line.to_owned(),
+            category: classify_message(line),
+            raw_flags: None,
+            dict: HashMap::new(),
+            source_backend: None,
+        })
+    }
+}
+
+#[cfg(not(rmesg_regex_parser))]
+pub fn entry_from_line(line: &str) -> Result<Entry, EntryParsingError> {
+    if line.is_empty() {
+        return Err(EntryParsingError::EmptyLine);
+    }
+
+    if let Some((faclevstr, timestampstr, message)) = split_klog_line(line) {
+        let (facility, level) = common::parse_favlecstr(faclevstr, line)?;
+
+        let timestamp_from_system_start = match timestampstr {
+            Some(timestampstr) => common::parse_timestamp_secs(timestampstr, line)?,
+            None => None,
+        };
 
         Ok(Entry {
             facility,
             level,
             sequence_num: None,
             timestamp_from_system_start,
-            message,
+            message: message.to_owned(),
+            category: classify_message(message),
+            raw_flags: None,
+            dict: HashMap::new(),
+            source_backend: None,
         })
     } else {
+        // No priority, such as the first line read after the buffer wrapped around, which
+        // may have lost its start: it's all message
         Ok(Entry {
             facility: None,
             level: None,
             sequence_num: None,
             timestamp_from_system_start: None,
             message: line.to_owned(),
+            category: classify_message(line),
+            raw_flags: None,
+            dict: HashMap::new(),
+            source_backend: None,
         })
     }
 }
 
+/// Hand-written equivalent of `RE_ENTRY_WITH_TIMESTAMP`, splitting a line into its
+/// priority, timestamp (when there is one) and message.
+#[cfg(not(rmesg_regex_parser))]
+fn split_klog_line(line: &str) -> Option<(&str, Option<&str>, &str)> {
+    let rest = line.trim_start_matches(common::is_posix_space);
+    let (faclevstr, rest) = rest.strip_prefix('<')?.split_once('>')?;
+    if !common::is_digits(faclevstr) {
+        return None;
+    }
+
+    let rest = rest.trim_start_matches(common::is_posix_space);
+    if let Some(bracketed) = rest.strip_prefix('[') {
+        let bracketed = bracketed.trim_start_matches(common::is_posix_space);
+        if let Some((timestampstr, message)) = bracketed.split_once(']') {
+            let is_timestamp = match timestampstr.split_once('.') {
+                Some((secs, fraction)) => common::is_digits(secs) && common::is_digits(fraction),
+                None => false,
+            };
+            if is_timestamp {
+                return Some((faclevstr, Some(timestampstr), message));
+            }
+        }
+    }
+
+    Some((faclevstr, None, rest))
+}
+
 // ************************** Private
 
+/// The one place klogctl is called. A failure is returned as `RMesgError::KLogError`,
+/// carrying the errno klogctl set.
+///
+/// Unsafe for the same reason as `klogctl_action`.
+unsafe fn klogctl_errno(
+    action: libc::c_int,
+    buf: *mut libc::c_char,
+    len: libc::c_int,
+) -> Result<libc::c_int, RMesgError> {
+    let response = klogctl(action, buf, len);
+
+    if response < 0 {
+        return Err(RMesgError::KLogError(errno().0));
+    }
+
+    Ok(response)
+}
+
+/// Makes one of the klogctl calls that takes no buffer and returns a size.
+fn klog_size(klogtype: KLogType) -> Result<usize, RMesgError> {
+    // Safe because size actions take no buffer
+    let response = unsafe { klogctl_errno(klogtype as libc::c_int, std::ptr::null_mut(), 0)? };
+
+    match usize::try_from(response) {
+        Ok(size) => Ok(size),
+        Err(e) => Err(RMesgError::IntegerOutOfBound(format!(
+            "Error converting size from klogctl from <c_int>::({}) into <usize>: {:?}",
+            response, e
+        ))),
+    }
+}
+
+/// Calls klogctl, mapping failures (through errno) into RMesgError.
+///
+/// Unsafe because klogctl writes up to `len` bytes to `buf`: the caller has to make sure
+/// `buf` is valid for that many bytes (or null, for actions that take no buffer).
+pub(crate) unsafe fn klogctl_action(
+    action: libc::c_int,
+    buf: *mut libc::c_char,
+    len: libc::c_int,
+) -> Result<libc::c_int, RMesgError> {
+    match klogctl_errno(action, buf, len) {
+        Err(RMesgError::KLogError(err)) => {
+            let action_name = match KLogType::from_i32(action) {
+                Some(klogtype) => klogtype.to_string(),
+                None => format!("klogctl action {}", action),
+            };
+
+            if err == libc::EPERM {
+                Err(RMesgError::OperationNotPermitted(action_name))
+            } else {
+                Err(RMesgError::InternalError(format!(
+                    "Request ({}) to klogctl failed. errno={}",
+                    action_name,
+                    errno::Errno(err)
+                )))
+            }
+        }
+        result => result,
+    }
+}
+
 /// Safely wraps the klogctl for Rusty types
 /// All higher-level functions are built over this function at the base.
 /// It prevents unsafe code from proliferating beyond this wrapper.
@@ -354,20 +856,8 @@ pub fn safely_wrapped_klogctl(klogtype: KLogType, buf_u8: &mut [u8]) -> Result<u
         }
     };
 
-    let response_cint: libc::c_int = unsafe { klogctl(klt, buf_cchar, buflen) };
-
-    if response_cint < 0 {
-        let err = errno();
-
-        if err.0 == libc::EPERM {
-            return Err(RMesgError::OperationNotPermitted(format!("{}", klogtype)));
-        } else {
-            return Err(RMesgError::InternalError(format!(
-                "Request ({}) to klogctl failed. errno={}",
-                klogtype, err
-            )));
-        }
-    }
+    // Safe because the pointer and length both come from the same live slice
+    let response_cint = unsafe { klogctl_action(klt, buf_cchar, buflen)? };
 
     let response = match usize::try_from(response_cint) {
         Ok(i) => i,
@@ -388,6 +878,43 @@ pub fn safely_wrapped_klogctl(klogtype: KLogType, buf_u8: &mut [u8]) -> Result<u
 #[cfg(all(test, target_os = "linux"))]
 mod test {
     use super::*;
+    use crate::common::TempFile;
+    use crate::entry::LogLevel;
+
+    #[test]
+    fn test_klog_timestamps_enable_at() {
+        let path = TempFile::with_contents("printk-time", "N\n");
+
+        assert!(!klog_timestamps_enabled_at(Some(&path)).unwrap());
+        klog_timestamps_enable_at(true, Some(&path)).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "Y\n");
+        assert!(klog_timestamps_enabled_at(Some(&path)).unwrap());
+        klog_timestamps_enable_at(false, Some(&path)).unwrap();
+        assert!(!klog_timestamps_enabled_at(Some(&path)).unwrap());
+
+        fs::remove_file(&path).unwrap();
+        assert!(klog_timestamps_enabled_at(Some(&path)).is_err());
+    }
+
+    #[test]
+    fn test_klog_console_level() {
+        let path = TempFile::with_contents("printk", "4\t4\t1\t7\n");
+        assert_eq!(klog_console_level_get_at(Some(&path)).unwrap(), 4);
+        fs::write(&path, "").unwrap();
+        assert!(klog_console_level_get_at(Some(&path)).is_err());
+
+        // Out of range, or not permitted when unprivileged: either way it's klogctl's errno
+        assert!(matches!(
+            klog_console_level_set(0),
+            Err(RMesgError::KLogError(_))
+        ));
+
+        // Setting the level it's already at round-trips, when privileged to set it
+        let level = klog_console_level_get().unwrap();
+        if klog_console_level_set(level).is_ok() {
+            assert_eq!(klog_console_level_get().unwrap(), level);
+        }
+    }
 
     #[test]
     fn get_kernel_buffer_size() {
@@ -400,30 +927,139 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_klog_buffer_size() {
+        let buffer_size = klog_buffer_size().unwrap();
+        assert!(buffer_size > 0, "Buffer size should be greater than zero.");
+
+        match klog_unread_size() {
+            Ok(unread) => assert!(unread <= buffer_size),
+            Err(RMesgError::KLogError(err)) => assert_eq!(err, libc::EPERM),
+            Err(e) => panic!("Expected a KLogError, got: {:?}", e),
+        }
+    }
+
+    #[test]
+    fn test_klogctl_action_error() {
+        let result = unsafe { klogctl_action(999, std::ptr::null_mut(), 0) };
+        match result {
+            Err(RMesgError::InternalError(s)) => assert!(s.contains("klogctl action 999"), "{}", s),
+            other => panic!("Expected an InternalError, got: {:?}", other),
+        }
+    }
+
     #[test]
     fn test_klog() {
         let entries = klog(false);
         assert!(entries.is_ok(), "Response from klog not Ok");
         assert!(!entries.unwrap().is_empty(), "Should have non-empty logs");
     }
-    
+
+    #[test]
+    fn test_klog_raw_to_writer() {
+        let mut buffer: Vec<u8> = Vec::new();
+        let written = klog_raw_to_writer(false, &mut buffer).unwrap();
+        assert_eq!(written, buffer.len());
+        assert!(written > 0, "Should have non-empty logs");
+    }
+
+    #[test]
+    fn test_klog_no_timestamp() {
+        let iterator = KLogEntriesNoTimestamp::with_options(false);
+        assert!(iterator.is_ok(), "Response from klog not Ok");
+
+        for (position, entry) in iterator.unwrap().enumerate() {
+            assert!(entry.is_ok());
+            assert_eq!(entry.unwrap().sequence_num, Some(position as u64));
+        }
+    }
+
+    #[test]
+    fn test_size_hint() {
+        let mut iterator = KLogEntriesNoTimestamp::with_options(false).unwrap();
+        let (remaining, upper) = iterator.size_hint();
+        assert_eq!(Some(remaining), upper);
+
+        if iterator.next().is_some() {
+            assert_eq!(iterator.size_hint(), (remaining - 1, Some(remaining - 1)));
+        }
+        assert_eq!(iterator.count(), remaining.saturating_sub(1));
+
+        let iterator = KLogEntries::with_options(false, SUGGESTED_POLL_INTERVAL).unwrap();
+        assert_eq!(iterator.size_hint(), (0, None));
+    }
+
+    #[test]
+    fn test_from_str_buffer() {
+        let buffer = "<6>[    1.000000] first\n<4>[    2.000000] second\n<99999999999>third\n";
+        let mut iterator = KLogEntries::from_str_buffer(buffer.to_owned(), SUGGESTED_POLL_INTERVAL);
+        assert_eq!(iterator.size_hint(), (3, Some(3)));
+
+        let first = iterator.next().unwrap().unwrap();
+        assert_eq!(first.level, Some(LogLevel::Info));
+        assert_eq!(
+            first.timestamp_from_system_start,
+            Some(Duration::from_secs(1))
+        );
+        assert_eq!(first.message, " first");
+        assert_eq!(first.sequence_num, Some(0));
+        assert_eq!(iterator.last_sequence_num(), Some(0));
+
+        let second = iterator.next().unwrap().unwrap();
+        assert_eq!(second.message, " second");
+        assert_eq!(second.sequence_num, Some(1));
+        assert_eq!(iterator.last_sequence_num(), Some(1));
+        assert!(matches!(
+            iterator.next(),
+            Some(Err(RMesgError::EntryParsingError { .. }))
+        ));
+        assert!(iterator.next().is_none());
+    }
+
+    #[test]
+    fn test_set_start_sequence_num() {
+        let buffer = "<6>[    1.000000] first\n<6>[    2.000000] second\n<6>[    3.000000] third\n";
+        let mut iterator = KLogEntries::from_str_buffer(buffer.to_owned(), SUGGESTED_POLL_INTERVAL);
+        iterator.set_start_sequence_num(1);
+        assert_eq!(iterator.last_sequence_num(), None);
+
+        let second = iterator.next().unwrap().unwrap();
+        assert_eq!(second.message, " second");
+        assert_eq!(second.sequence_num, Some(1));
+        assert_eq!(iterator.last_sequence_num(), Some(1));
+        assert_eq!(iterator.next().unwrap().unwrap().sequence_num, Some(2));
+        assert!(iterator.next().is_none());
+    }
+
+    #[test]
+    fn test_set_poll_interval() {
+        let mut iterator = KLogEntries::with_options(false, SUGGESTED_POLL_INTERVAL).unwrap();
+
+        iterator.set_poll_interval(Duration::from_millis(100));
+        assert_eq!(iterator.poll_interval, Duration::from_millis(100));
+        assert_eq!(iterator.sleep_interval, Duration::from_millis(300));
+
+        iterator.set_poll_interval(Duration::MAX);
+        assert_eq!(iterator.sleep_interval, Duration::MAX);
+    }
+
     #[test]
     fn test_parse_serialize() {
         let line1 = "<6>a.out[4054]: segfault at 7ffd5503d358 ip 00007ffd5503d358 sp 00007ffd5503d258 error 15";
         let entries1 = entries_from_lines(line1).unwrap();
-        let e1r = entries1.get(0).unwrap();
+        let e1r = entries1.first().unwrap();
         let line1again = e1r.to_klog_str().unwrap();
         assert_eq!(line1, line1again);
 
         let line2 = "<7>[   233434.343533] a.out[4054]: segfault at 7ffd5503d358 ip 00007ffd5503d358 sp 00007ffd5503d258 error 15";
         let entries2 = entries_from_lines(line2).unwrap();
-        let e2r = entries2.get(0).unwrap();
+        let e2r = entries2.first().unwrap();
         let line2again = e2r.to_klog_str().unwrap();
         assert_eq!(line2, line2again);
 
         let line3 = "233434.343533] a.out[4054]: segfault at 7ffd5503d358 ip 00007ffd5503d358 sp 00007ffd5503d258 error 15";
         let entries3 = entries_from_lines(line3).unwrap();
-        let e3r = entries3.get(0).unwrap();
+        let e3r = entries3.first().unwrap();
         let line3again = e3r.to_klog_str().unwrap();
         assert_eq!(line3, line3again);
     }
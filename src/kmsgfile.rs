@@ -1,5 +1,5 @@
 use crate::common;
-use crate::entry::{Entry, EntryParsingError};
+use crate::entry::{classify_message, Entry, EntryCategory, EntryParsingError, EntryRef, LogLevel};
 /// This crate provides a /dev/kmsg file interface from Rust. Reading from this
 /// virtual device is the more modern and simpler way to read the kernel
 /// log buffer than making syscalls directly.
@@ -7,17 +7,36 @@ use crate::entry::{Entry, EntryParsingError};
 /// This allows Rust programs to consume dmesg-like output programmatically.
 ///
 use crate::error::RMesgError;
+use crate::Backend;
 
+/// Byte-level /dev/kmsg header parser, the implementation behind the no-regex feature
+pub mod parser;
+
+#[cfg(rmesg_regex_parser)]
 use lazy_static::lazy_static;
 use nonblock::NonBlockingReader;
+#[cfg(rmesg_regex_parser)]
 use regex::Regex;
+use std::collections::HashMap;
 use std::fs as stdfs;
 
 use std::io as stdio;
-use std::io::BufRead;
+use std::io::{BufRead, Read, Write};
 use std::iter::Iterator;
+use std::os::unix::fs::OpenOptionsExt;
+use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, OwnedFd, RawFd};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
 
 const DEV_KMSG_PATH: &str = "/dev/kmsg";
+
+// Each read from /dev/kmsg returns one record, and fails if the record doesn't fit the buffer.
+// The kernel formats records of up to about 8 KiB (dict lines and escaping included), so
+// embedded defaults don't make this any smaller.
+const READ_BUFFER_CAPACITY: usize = 8 * 1024;
+#[cfg(rmesg_regex_parser)]
 lazy_static! {
     static ref RE_ENTRY_WITH_TIMESTAMP: Regex = Regex::new(
         r"(?x)^
@@ -25,8 +44,8 @@ lazy_static! {
             # Sequence is a 64-bit integer: https://www.kernel.org/doc/Documentation/ABI/testing/dev-kmsg
             [[:space:]]*(?P<sequencenum>[[:digit:]]*)[[:space:]]*,
             [[:space:]]*(?P<timestampstr>[[:digit:]]*)[[:space:]]*,
-            # Ignore everything until the semi-colon and then the semicolon
-            [[^;]]*;
+            # Flags are everything until the semi-colon, then the semicolon
+            (?P<flags>[^;]*);
             (?P<message>.*)
             $"
     )
@@ -43,36 +62,567 @@ lazy_static! {
 ///
 pub struct KMsgEntriesIter {
     raw: bool,
-    lines_iter: stdio::Lines<stdio::BufReader<stdfs::File>>,
+    options: KMsgOptions,
+    reader: stdio::BufReader<stdfs::File>,
+    // Reading a regular file never blocks, unlike reading /dev/kmsg
+    regular_file: bool,
+    // Set by with_seek: lines are skipped until an entry numbered at least this
+    skip_before: Option<u64>,
+    // Where the file was opened from, for restart(). None when given a file descriptor.
+    path: Option<PathBuf>,
+    // Sequence number of the last entry returned that had one, for last_sequence_num()
+    last_seq: Option<u64>,
+}
+
+/// Further options for how KMsgEntriesIter opens the file and processes entries.
+#[derive(Debug, Clone)]
+pub struct KMsgOptions {
+    /// Clean up each parsed entry's message with `Entry::normalize`
+    pub normalize_messages: bool,
+
+    /// Give up with `RMesgError::OpenTimeout` if opening the file takes longer than this.
+    /// The open happens on a separate thread, which is left behind (to finish or stay blocked)
+    /// when it times out.
+    pub open_timeout: Option<Duration>,
+
+    /// Open the file with O_CLOEXEC, so child processes don't inherit it. On by default.
+    pub open_cloexec: bool,
+
+    /// Append continuation lines (those starting with a space, such as ` SUBSYSTEM=usb`)
+    /// to the message of the parsed entry before them, separated by newlines, rather than
+    /// returning them as entries of their own.
+    pub merge_continuations: bool,
+
+    /// Give up waiting for the next entry after this long, returning `RMesgError::ReadTimeout`
+    /// in its place (the iterator can still be read from afterwards). Reading a regular file
+    /// never waits, so this makes no difference to a `file_override` that's a regular file.
+    pub read_timeout: Option<Duration>,
+
+    /// Size of the read buffer, in bytes: 8 KiB by default.
+    /// A larger buffer takes fewer read() calls on a regular file, but each read from
+    /// /dev/kmsg returns a single record regardless, so there it only costs memory.
+    /// Reading /dev/kmsg fails with EINVAL if a record doesn't fit the buffer.
+    pub read_buffer_capacity: usize,
+}
+
+impl Default for KMsgOptions {
+    fn default() -> Self {
+        KMsgOptions {
+            normalize_messages: false,
+            open_timeout: None,
+            open_cloexec: true,
+            merge_continuations: false,
+            read_timeout: None,
+            read_buffer_capacity: READ_BUFFER_CAPACITY,
+        }
+    }
 }
 
 impl KMsgEntriesIter {
     /// Create a new KMsgEntries with two specific options
     /// `file_override`: When `Some`, overrides the path from where to read the kernel logs
     /// `raw: bool` When set, does not parse the message and instead sets the entire log entry in the "message" field
+    #[deprecated(
+        note = "Use `with_path`, which takes an `Option<&Path>`: `with_path(file_override.as_deref().map(Path::new), raw)`"
+    )]
     pub fn with_options(file_override: Option<String>, raw: bool) -> Result<Self, RMesgError> {
-        let path = file_override.as_deref().unwrap_or(DEV_KMSG_PATH);
+        Self::with_path(file_override.as_deref().map(Path::new), raw)
+    }
 
-        let file = match stdfs::File::open(path) {
-            Ok(fc) => fc,
-            Err(e) => {
-                if e.raw_os_error() == Some(libc::EPERM) {
-                    return Err(RMesgError::OperationNotPermitted(format!(
-                        "Open File {}",
-                        path
-                    )));
-                } else {
-                    return Err(RMesgError::DevKMsgFileOpenError(format!(
-                        "Unable to open file {}: {}",
-                        path, e
-                    )));
+    /// Create a new KMsgEntries with two specific options
+    /// `file_override`: When `Some`, overrides the path from where to read the kernel logs
+    /// `raw: bool` When set, does not parse the message and instead sets the entire log entry in the "message" field
+    pub fn with_path(file_override: Option<&Path>, raw: bool) -> Result<Self, RMesgError> {
+        Self::with_kmsg_options(file_override, raw, KMsgOptions::default())
+    }
+
+    /// Same as `with_path`, with further `options` applied to the entries read
+    pub fn with_kmsg_options(
+        file_override: Option<&Path>,
+        raw: bool,
+        options: KMsgOptions,
+    ) -> Result<Self, RMesgError> {
+        let path = file_override.unwrap_or_else(|| Path::new(DEV_KMSG_PATH));
+
+        let file = match options.open_timeout {
+            Some(timeout) => open_file_with_timeout(path, options.open_cloexec, timeout)?,
+            None => open_file(path, options.open_cloexec)?,
+        };
+
+        let mut iter = Self::from_file(file, raw, options);
+        iter.path = Some(path.to_path_buf());
+        Ok(iter)
+    }
+
+    /// Same as `with_path`, but waits for entries with epoll(7) (see `EpollKMsgEntriesIter`).
+    ///
+    /// Returns `RMesgError::IOError` for a `file_override` epoll can't wait on, such as a regular file.
+    pub fn with_epoll(
+        file_override: Option<&Path>,
+        raw: bool,
+    ) -> Result<EpollKMsgEntriesIter, RMesgError> {
+        let inner = Self::with_path(file_override, raw)?;
+
+        let epoll_fd = unsafe { libc::epoll_create1(libc::EPOLL_CLOEXEC) };
+        if epoll_fd < 0 {
+            return Err(RMesgError::io_error(
+                "Unable to create epoll instance",
+                stdio::Error::last_os_error(),
+            ));
+        }
+        let epoll = unsafe { OwnedFd::from_raw_fd(epoll_fd) };
+
+        let kmsg_fd = inner.as_raw_fd();
+        let mut event = libc::epoll_event {
+            events: (libc::EPOLLIN | libc::EPOLLPRI) as u32,
+            u64: kmsg_fd as u64,
+        };
+        if unsafe { libc::epoll_ctl(epoll_fd, libc::EPOLL_CTL_ADD, kmsg_fd, &mut event) } < 0 {
+            return Err(RMesgError::io_error(
+                "Unable to add kernel log device file to epoll instance",
+                stdio::Error::last_os_error(),
+            ));
+        }
+
+        Ok(EpollKMsgEntriesIter { inner, epoll })
+    }
+
+    /// Same as `with_path`, reading from an already open file descriptor, such as one
+    /// inherited from a container runtime or supervisor, rather than opening the file.
+    ///
+    /// SAFETY NOTE: The iterator takes ownership of `fd`, and closes it when dropped. The caller
+    /// must not use or close it afterwards (unless it's given back with `into_raw_fd`).
+    /// Returns `RMesgError::IOError` if `fd` isn't an open file descriptor (EBADF),
+    /// in which case ownership isn't taken.
+    pub fn from_raw_fd(fd: RawFd, raw: bool) -> Result<Self, RMesgError> {
+        if unsafe { libc::fcntl(fd, libc::F_GETFD) } < 0 {
+            return Err(RMesgError::io_error(
+                &format!("Unable to use file descriptor {} for the kernel log", fd),
+                stdio::Error::last_os_error(),
+            ));
+        }
+
+        let file = unsafe { stdfs::File::from_raw_fd(fd) };
+        Ok(Self::from_file(file, raw, KMsgOptions::default()))
+    }
+
+    fn from_file(file: stdfs::File, raw: bool, options: KMsgOptions) -> Self {
+        let regular_file = file.metadata().map(|m| m.is_file()).unwrap_or(false);
+        let reader = stdio::BufReader::with_capacity(options.read_buffer_capacity, file);
+
+        Self {
+            raw,
+            options,
+            reader,
+            regular_file,
+            skip_before: None,
+            path: None,
+            last_seq: None,
+        }
+    }
+
+    /// The sequence number of the last entry `next()` returned, skipping entries without one
+    /// (such as raw entries). A consumer can save it, to resume after it with `with_seek`.
+    pub fn last_sequence_num(&self) -> Option<u64> {
+        self.last_seq
+    }
+
+    /// Goes back to the oldest entry in the kernel log buffer (or the start of a `file_override`),
+    /// for instance to parse the same fixture file again. Anything buffered but not yet returned
+    /// is dropped, as is the starting point of `with_seek` and `last_sequence_num()`.
+    ///
+    /// The file is rewound where it can be, keeping its file descriptor. Otherwise (for a pipe,
+    /// say) it's opened again, with the same options, which fails for iterators made with
+    /// `from_raw_fd`.
+    pub fn restart(&mut self) -> Result<(), RMesgError> {
+        self.skip_before = None;
+        self.last_seq = None;
+
+        if lseek(self.reader.get_ref(), libc::SEEK_SET).is_ok() {
+            let buffered = self.reader.buffer().len();
+            self.reader.consume(buffered);
+            return Ok(());
+        }
+
+        match self.path.clone() {
+            Some(path) => {
+                *self = Self::with_kmsg_options(Some(&path), self.raw, self.options.clone())?;
+                Ok(())
+            }
+            None => Err(RMesgError::IOError {
+                message:
+                    "Unable to restart: the file can't be rewound, and was never opened by path"
+                        .to_owned(),
+                source: None,
+            }),
+        }
+    }
+
+    /// Same as `with_path`, but `next()` gives up waiting for an entry after `timeout`,
+    /// returning `RMesgError::ReadTimeout` (see `KMsgOptions::read_timeout`).
+    pub fn with_timeout(
+        file_override: Option<&Path>,
+        raw: bool,
+        timeout: Duration,
+    ) -> Result<Self, RMesgError> {
+        let options = KMsgOptions {
+            read_timeout: Some(timeout),
+            ..Default::default()
+        };
+        Self::with_kmsg_options(file_override, raw, options)
+    }
+
+    /// Same as `with_path`, with a read buffer of `capacity` bytes
+    /// (see `KMsgOptions::read_buffer_capacity` for the trade-offs).
+    pub fn with_capacity(
+        file_override: Option<&Path>,
+        raw: bool,
+        capacity: usize,
+    ) -> Result<Self, RMesgError> {
+        let options = KMsgOptions {
+            read_buffer_capacity: capacity,
+            ..Default::default()
+        };
+        Self::with_kmsg_options(file_override, raw, options)
+    }
+
+    /// Same as `with_path`, but skips the entries already in the buffer, only returning those
+    /// written afterwards (as `dmesg --follow-new` does). `with_options` starts from the oldest
+    /// entry in the buffer instead.
+    pub fn with_tail(file_override: Option<&Path>, raw: bool) -> Result<Self, RMesgError> {
+        let iter = Self::with_path(file_override, raw)?;
+        lseek(iter.reader.get_ref(), libc::SEEK_END)?;
+        Ok(iter)
+    }
+
+    /// Same as `with_path`, but starts at the entry with sequence number `start_seq`,
+    /// such as the one after the last entry a previous run processed.
+    ///
+    /// /dev/kmsg can only be rewound to the oldest entry in the buffer, not to a given sequence
+    /// number, so this seeks there and skips the entries before `start_seq` as they're read.
+    /// Returns `RMesgError::SequenceNotFound` if the oldest entry is already past `start_seq`,
+    /// i.e. if the entries from `start_seq` on have been overwritten, rather than silently
+    /// starting from the oldest entry. When `start_seq` hasn't been written yet, the iterator
+    /// waits for it.
+    pub fn with_seek(
+        file_override: Option<&Path>,
+        raw: bool,
+        start_seq: u64,
+    ) -> Result<Self, RMesgError> {
+        let mut iter = Self::with_path(file_override, raw)?;
+        lseek(iter.reader.get_ref(), libc::SEEK_SET)?;
+
+        if let Some(oldest) = iter.buffered_sequence_num()? {
+            if oldest > start_seq {
+                return Err(RMesgError::SequenceNotFound(start_seq));
+            }
+        }
+
+        iter.skip_before = Some(start_seq);
+        Ok(iter)
+    }
+
+    /// Same as `with_path`, merging continuation lines into the entry before them
+    /// when `merge` is set (see `KMsgOptions::merge_continuations`).
+    pub fn with_merge_continuations(
+        file_override: Option<&Path>,
+        raw: bool,
+        merge: bool,
+    ) -> Result<Self, RMesgError> {
+        let options = KMsgOptions {
+            merge_continuations: merge,
+            ..Default::default()
+        };
+        Self::with_kmsg_options(file_override, raw, options)
+    }
+
+    /// Same as `next()`, but returns `None` rather than waiting when there's no entry to read yet.
+    /// The iterator can still be read from (and waited on) afterwards.
+    ///
+    /// The file is made non-blocking (with O_NONBLOCK) for the duration of the call only.
+    pub fn try_next(&mut self) -> Option<Result<Entry, RMesgError>> {
+        let fd = self.reader.get_ref().as_raw_fd();
+        let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+        if flags < 0 || unsafe { libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) } < 0 {
+            return Some(Err(RMesgError::io_error(
+                "Unable to make kernel log device file non-blocking",
+                stdio::Error::last_os_error(),
+            )));
+        }
+
+        // Nothing to wait for when reads don't block
+        let read_timeout = self.options.read_timeout.take();
+        let next = self.next();
+        self.options.read_timeout = read_timeout;
+
+        if unsafe { libc::fcntl(fd, libc::F_SETFL, flags) } < 0 {
+            return Some(Err(RMesgError::io_error(
+                "Unable to make kernel log device file blocking again",
+                stdio::Error::last_os_error(),
+            )));
+        }
+
+        next
+    }
+
+    /// Reads every entry there is to read without waiting, using `try_next()`.
+    pub fn drain_available(&mut self) -> Vec<Result<Entry, RMesgError>> {
+        std::iter::from_fn(|| self.try_next()).collect()
+    }
+
+    /// Reads up to `max` entries: waits for the first one as `next()` does, then takes
+    /// whatever else there is to read without waiting (see `try_next()`).
+    ///
+    /// Returns an empty Vec only when `max` is 0, or when there are no entries left at all.
+    pub fn next_batch(&mut self, max: usize) -> Vec<Result<Entry, RMesgError>> {
+        let mut batch = Vec::new();
+        if max == 0 {
+            return batch;
+        }
+
+        if let Some(first) = self.next() {
+            batch.push(first);
+            while batch.len() < max {
+                match self.try_next() {
+                    Some(entry) => batch.push(entry),
+                    None => break,
                 }
             }
+        }
+
+        batch
+    }
+
+    /// Reads every remaining entry without waiting for new ones (see `drain_available()`),
+    /// continuing past errors, and returns the entries and the errors separately.
+    pub fn drain_all(mut self) -> (Vec<Entry>, Vec<RMesgError>) {
+        let mut entries = Vec::new();
+        let mut errors = Vec::new();
+
+        for entry in self.drain_available() {
+            match entry {
+                Ok(entry) => entries.push(entry),
+                Err(e) => errors.push(e),
+            }
+        }
+
+        (entries, errors)
+    }
+
+    /// Like `drain_all()`, but silently discards errors.
+    pub fn drain_all_ok(mut self) -> Vec<Entry> {
+        self.drain_available()
+            .into_iter()
+            .filter_map(Result::ok)
+            .collect()
+    }
+
+    /// Reads the next line, without the line ending, the same way `BufRead::lines()` would.
+    /// The reader is kept (rather than its `Lines`) so the file underneath stays reachable.
+    ///
+    /// When there's a `read_timeout`, and nothing is buffered, waits with poll(2) for something to
+    /// read first, returning an error of kind `TimedOut` if nothing arrives in time.
+    fn next_line(&mut self) -> Option<stdio::Result<String>> {
+        if let Some(timeout) = self.options.read_timeout {
+            if self.reader.buffer().is_empty() {
+                if let Err(e) = self.poll_readable(timeout) {
+                    return Some(Err(e));
+                }
+            }
+        }
+
+        let mut line = String::new();
+        match self.reader.read_line(&mut line) {
+            Ok(0) => None,
+            Ok(_) => {
+                if line.ends_with('\n') {
+                    line.pop();
+                    if line.ends_with('\r') {
+                        line.pop();
+                    }
+                }
+                Some(Ok(line))
+            }
+            Err(e) => Some(Err(e)),
+        }
+    }
+
+    fn poll_readable(&self, timeout: Duration) -> stdio::Result<()> {
+        let mut pollfd = libc::pollfd {
+            fd: self.reader.get_ref().as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
         };
+        let timeout_ms = timeout.as_millis().min(libc::c_int::MAX as u128) as libc::c_int;
+
+        match unsafe { libc::poll(&mut pollfd, 1, timeout_ms) } {
+            0 => Err(stdio::Error::new(
+                stdio::ErrorKind::TimedOut,
+                "No kernel log entry arrived in time",
+            )),
+            n if n < 0 => Err(stdio::Error::last_os_error()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Reads the next line, skipping those before the `with_seek` starting point
+    /// (including the continuation lines of skipped entries).
+    fn next_unskipped_line(&mut self) -> Option<stdio::Result<String>> {
+        loop {
+            let line = self.next_line();
+            // Blank lines aren't entries
+            if let Some(Ok(l)) = &line {
+                if l.is_empty() {
+                    continue;
+                }
+            }
+            if let (Some(start_seq), Some(Ok(l))) = (self.skip_before, &line) {
+                match parse_kmsg_header(l) {
+                    Some(header) if header.sequence_num >= start_seq => self.skip_before = None,
+                    _ => continue,
+                }
+            }
+
+            return line;
+        }
+    }
+
+    /// The sequence number of the next entry, read without consuming it.
+    /// Reads a record into the buffer if it's empty, so this blocks if there is none.
+    fn buffered_sequence_num(&mut self) -> Result<Option<u64>, RMesgError> {
+        let buffer = self.reader.fill_buf()?;
+        let first_line = buffer.split(|b| *b == b'\n').next().unwrap_or_default();
 
-        let lines_iter = stdio::BufReader::new(file).lines();
+        Ok(std::str::from_utf8(first_line)
+            .ok()
+            .and_then(parse_kmsg_header)
+            .map(|header| header.sequence_num))
+    }
+
+    /// Whether the next line is a continuation line. /dev/kmsg returns a record (including
+    /// its continuation lines) per read, so only what's already buffered is looked at, since
+    /// reading more would block until the next record.
+    fn continuation_follows(&mut self) -> bool {
+        if self.regular_file && self.reader.buffer().is_empty() {
+            // An error here comes up again when reading the next line
+            let _ = self.reader.fill_buf();
+        }
+
+        self.reader.buffer().first() == Some(&b' ')
+    }
+
+    fn merge_continuations(&mut self, entry: &mut Entry) -> Result<(), RMesgError> {
+        while self.continuation_follows() {
+            match self.next_line() {
+                Some(Ok(continuation)) => {
+                    entry.message.push('\n');
+                    entry.message.push_str(&continuation);
+                }
+                Some(Err(e)) => {
+                    return Err(RMesgError::io_error(
+                        "Error reading continuation line from kernel log device file",
+                        e,
+                    ))
+                }
+                None => break,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Exposes the file descriptor of the open /dev/kmsg file, e.g. to register it with
+/// poll/select/epoll loops. The iterator still owns the file descriptor: callers must not
+/// close it, or change its flags or offset, while the iterator is in use.
+impl AsRawFd for KMsgEntriesIter {
+    fn as_raw_fd(&self) -> RawFd {
+        self.reader.get_ref().as_raw_fd()
+    }
+}
+
+/// Gives up the file descriptor of the open /dev/kmsg file, e.g. to pass it on to a child process.
+///
+/// SAFETY NOTE: After this, the caller owns the file descriptor, and is responsible for closing it.
+/// Anything already buffered by the iterator, but not yet returned, is lost.
+impl IntoRawFd for KMsgEntriesIter {
+    fn into_raw_fd(self) -> RawFd {
+        self.reader.into_inner().into_raw_fd()
+    }
+}
+
+/// Same as KMsgEntriesIter, but waits for entries with epoll(7) rather than a blocking read,
+/// only reading once the file is ready. See `KMsgEntriesIter::with_epoll`.
+///
+/// Other file descriptors can be added to the same epoll instance (see `epoll_fd()`), to wait
+/// on all of them at once with epoll_wait(2). `next()` itself keeps waiting until there's
+/// an entry to read, whichever of the other file descriptors are ready in the meantime.
+pub struct EpollKMsgEntriesIter {
+    inner: KMsgEntriesIter,
+    epoll: OwnedFd,
+}
+
+impl EpollKMsgEntriesIter {
+    /// The epoll instance's file descriptor. The iterator still owns it: callers may add to
+    /// (and wait on) it, but must not close it, or remove the kernel log file from it.
+    pub fn epoll_fd(&self) -> RawFd {
+        self.epoll.as_raw_fd()
+    }
+
+    fn wait_readable(&self) -> Result<(), RMesgError> {
+        let kmsg_fd = self.inner.as_raw_fd() as u64;
+        let mut events = [libc::epoll_event { events: 0, u64: 0 }; 8];
+
+        loop {
+            let ready = unsafe {
+                libc::epoll_wait(
+                    self.epoll.as_raw_fd(),
+                    events.as_mut_ptr(),
+                    events.len() as libc::c_int,
+                    -1,
+                )
+            };
+
+            if ready < 0 {
+                let e = stdio::Error::last_os_error();
+                if e.kind() == stdio::ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(RMesgError::io_error(
+                    "Error waiting on kernel log device file",
+                    e,
+                ));
+            }
+
+            // Copied out, since epoll_event is packed on x86_64 (fields of which can't be borrowed)
+            if events[..ready as usize].iter().any(|event| {
+                let fd = event.u64;
+                fd == kmsg_fd
+            }) {
+                return Ok(());
+            }
+        }
+    }
+}
+
+impl AsRawFd for EpollKMsgEntriesIter {
+    fn as_raw_fd(&self) -> RawFd {
+        self.inner.as_raw_fd()
+    }
+}
+
+impl Iterator for EpollKMsgEntriesIter {
+    type Item = Result<Entry, RMesgError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // Whatever's already buffered can be read without waiting
+        if self.inner.reader.buffer().is_empty() {
+            if let Err(e) = self.wait_readable() {
+                return Some(Err(e));
+            }
+        }
 
-        Ok(Self { raw, lines_iter })
+        self.inner.next()
     }
 }
 
@@ -84,12 +634,31 @@ impl Iterator for KMsgEntriesIter {
     /// NOT a thread-safe method either. It is suggested this method be always
     /// blocked on to ensure no messages are missed.
     fn next(&mut self) -> Option<Self::Item> {
-        match self.lines_iter.next() {
+        let mut next = self.next_entry();
+        if let Some(Ok(entry)) = &mut next {
+            entry.source_backend = Some(Backend::DevKMsg);
+            if entry.sequence_num.is_some() {
+                self.last_seq = entry.sequence_num;
+            }
+        }
+
+        next
+    }
+}
+
+impl KMsgEntriesIter {
+    fn next_entry(&mut self) -> Option<Result<Entry, RMesgError>> {
+        match self.next_unskipped_line() {
             None => None,
-            Some(Err(e)) => Some(Err(RMesgError::IOError(format!(
-                "Error reading next line from kernel log device file: {}",
-                e
-            )))),
+            Some(Err(e)) if e.kind() == stdio::ErrorKind::TimedOut => {
+                Some(Err(RMesgError::ReadTimeout))
+            }
+            // Only when the file is non-blocking (as during try_next), and there's nothing to read yet
+            Some(Err(e)) if e.kind() == stdio::ErrorKind::WouldBlock => None,
+            Some(Err(e)) => Some(Err(RMesgError::io_error(
+                "Error reading next line from kernel log device file",
+                e,
+            ))),
             Some(Ok(line)) => {
                 if self.raw {
                     Some(Ok(Entry {
@@ -98,50 +667,153 @@ impl Iterator for KMsgEntriesIter {
                         timestamp_from_system_start: None,
                         sequence_num: None,
                         message: line,
+                        category: EntryCategory::Normal,
+                        raw_flags: None,
+                        dict: HashMap::new(),
+                        source_backend: None,
                     }))
                 } else {
-                    Some(entry_from_line(&line).map_err(|e| e.into()))
+                    let mut entry = match entry_from_line(&line) {
+                        Ok(entry) => entry,
+                        Err(e) => return Some(Err(e.into())),
+                    };
+
+                    if self.options.merge_continuations {
+                        if let Err(e) = self.merge_continuations(&mut entry) {
+                            return Some(Err(e));
+                        }
+                    }
+
+                    if self.options.normalize_messages {
+                        entry.normalize();
+                    }
+
+                    Some(Ok(entry))
                 }
             }
         }
     }
 }
 
-pub fn kmsg_raw(file_override: Option<String>) -> Result<String, RMesgError> {
-    let path = file_override.as_deref().unwrap_or(DEV_KMSG_PATH);
+/// Repositions the open /dev/kmsg file: `libc::SEEK_SET` rewinds to the oldest entry in the buffer,
+/// and `libc::SEEK_END` skips to just after the newest.
+fn lseek(file: &stdfs::File, whence: libc::c_int) -> Result<(), RMesgError> {
+    if unsafe { libc::lseek(file.as_raw_fd(), 0, whence) } < 0 {
+        return Err(RMesgError::io_error(
+            "Unable to seek in kernel log device file",
+            stdio::Error::last_os_error(),
+        ));
+    }
 
-    let file = match stdfs::File::open(path) {
-        Ok(fc) => fc,
+    Ok(())
+}
+
+fn open_file(path: &Path, cloexec: bool) -> Result<stdfs::File, RMesgError> {
+    let custom_flags = match cloexec {
+        true => libc::O_CLOEXEC,
+        false => 0,
+    };
+
+    match stdfs::OpenOptions::new()
+        .read(true)
+        .custom_flags(custom_flags)
+        .open(path)
+    {
+        Ok(fc) => {
+            // std opens files with O_CLOEXEC regardless, so it has to be cleared explicitly
+            if !cloexec {
+                let fd = fc.as_raw_fd();
+                let flags = unsafe { libc::fcntl(fd, libc::F_GETFD) };
+                if flags < 0
+                    || unsafe { libc::fcntl(fd, libc::F_SETFD, flags & !libc::FD_CLOEXEC) } < 0
+                {
+                    return Err(RMesgError::DevKMsgFileOpenError(format!(
+                        "Unable to clear the close-on-exec flag of file {}: {}",
+                        path.display(),
+                        stdio::Error::last_os_error()
+                    )));
+                }
+            }
+            Ok(fc)
+        }
         Err(e) => {
             if e.raw_os_error() == Some(libc::EPERM) {
-                return Err(RMesgError::OperationNotPermitted(format!(
+                Err(RMesgError::OperationNotPermitted(format!(
                     "Open File {}",
-                    path
-                )));
+                    path.display()
+                )))
             } else {
-                return Err(RMesgError::DevKMsgFileOpenError(format!(
+                Err(RMesgError::DevKMsgFileOpenError(format!(
                     "Unable to open file {}: {}",
-                    path, e
-                )));
+                    path.display(),
+                    e
+                )))
             }
         }
-    };
+    }
+}
+
+fn open_file_with_timeout(
+    path: &Path,
+    cloexec: bool,
+    timeout: Duration,
+) -> Result<stdfs::File, RMesgError> {
+    let (sender, receiver) = mpsc::channel();
+    let thread_path = path.to_path_buf();
+
+    // If we stop waiting, the send fails and the file (if it ever opens) is closed right away
+    thread::spawn(move || {
+        let _ = sender.send(open_file(&thread_path, cloexec));
+    });
+
+    match receiver.recv_timeout(timeout) {
+        Ok(result) => result,
+        Err(_) => Err(RMesgError::OpenTimeout(format!(
+            "Opening file {} took longer than {:?}",
+            path.display(),
+            timeout
+        ))),
+    }
+}
+
+/// Same as `kmsg_raw_from_path`.
+#[deprecated(
+    note = "Use `kmsg_raw_from_path`, which takes an `Option<&Path>`: `kmsg_raw_from_path(file_override.as_deref().map(Path::new))`"
+)]
+pub fn kmsg_raw(file_override: Option<String>) -> Result<String, RMesgError> {
+    kmsg_raw_from_path(file_override.as_deref().map(Path::new))
+}
+
+/// Same as `kmsg_raw_bytes`, with any invalid UTF-8 replaced (see `String::from_utf8_lossy`).
+pub fn kmsg_raw_from_path(file_override: Option<&Path>) -> Result<String, RMesgError> {
+    let file_contents = kmsg_raw_bytes(file_override)?;
+
+    Ok(String::from_utf8_lossy(&file_contents).into_owned())
+}
+
+/// Reads everything in the kernel log buffer, as is. The bytes may not be valid UTF-8,
+/// since some drivers log binary data.
+pub fn kmsg_raw_bytes(file_override: Option<&Path>) -> Result<Vec<u8>, RMesgError> {
+    let path = file_override.unwrap_or_else(|| Path::new(DEV_KMSG_PATH));
+
+    let file = open_file(path, true)?;
 
     let mut noblock_file = NonBlockingReader::from_fd(file)?;
 
-    let mut file_contents = String::new();
-    match noblock_file.read_available_to_string(&mut file_contents) {
+    let mut file_contents = Vec::new();
+    match noblock_file.read_available(&mut file_contents) {
         Ok(_) => {}
         Err(e) => {
             if e.raw_os_error() == Some(libc::EPERM) {
                 return Err(RMesgError::OperationNotPermitted(format!(
                     "Read from File {}",
-                    path
+                    path.display()
                 )));
             } else {
                 return Err(RMesgError::DevKMsgFileOpenError(format!(
                     "Unable to read from file {}: {}",
-                    path, e
+                    path.display(),
+                    e
                 )));
             }
         }
@@ -150,6 +822,66 @@ pub fn kmsg_raw(file_override: Option<String>) -> Result<String, RMesgError> {
     Ok(file_contents)
 }
 
+/// Same as `kmsg_raw_bytes`, but writes the buffer to `writer`, a chunk at a time (one entry
+/// at a time, from /dev/kmsg), rather than collecting it. Returns the number of bytes written.
+pub fn kmsg_raw_to_writer<W: Write>(
+    file_override: Option<&Path>,
+    writer: &mut W,
+) -> Result<u64, RMesgError> {
+    let path = file_override.unwrap_or_else(|| Path::new(DEV_KMSG_PATH));
+
+    let mut file = open_file(path, true)?;
+
+    let fd = file.as_raw_fd();
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+    if flags < 0 || unsafe { libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) } < 0 {
+        return Err(RMesgError::io_error(
+            &format!("Unable to make file {} non-blocking", path.display()),
+            stdio::Error::last_os_error(),
+        ));
+    }
+
+    let mut chunk = vec![0; READ_BUFFER_CAPACITY];
+    let mut written: u64 = 0;
+    loop {
+        match file.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(n) => {
+                writer.write_all(&chunk[..n])?;
+                written += n as u64;
+            }
+            // Everything available has been read
+            Err(e) if e.kind() == stdio::ErrorKind::WouldBlock => break,
+            Err(e) if e.kind() == stdio::ErrorKind::Interrupted => continue,
+            // The entry about to be read was overwritten; the next read returns the oldest one left
+            Err(e) if e.raw_os_error() == Some(libc::EPIPE) => continue,
+            Err(e) if e.raw_os_error() == Some(libc::EPERM) => {
+                return Err(RMesgError::OperationNotPermitted(format!(
+                    "Read from File {}",
+                    path.display()
+                )))
+            }
+            Err(e) => {
+                return Err(RMesgError::DevKMsgFileOpenError(format!(
+                    "Unable to read from file {}: {}",
+                    path.display(),
+                    e
+                )))
+            }
+        }
+    }
+
+    Ok(written)
+}
+
+/// Same as `kmsg_from_path`.
+#[deprecated(
+    note = "Use `kmsg_from_path`, which takes an `Option<&Path>`: `kmsg_from_path(file_override.as_deref().map(Path::new))`"
+)]
+pub fn kmsg(file_override: Option<String>) -> Result<Vec<Entry>, RMesgError> {
+    kmsg_from_path(file_override.as_deref().map(Path::new))
+}
+
 /// This is the key safe function that makes the klogctl syslog call with parameters.
 /// While the internally used function supports all klogctl parameters, this function
 /// only provides one bool parameter which indicates whether the buffer is to be cleared
@@ -158,12 +890,123 @@ pub fn kmsg_raw(file_override: Option<String>) -> Result<String, RMesgError> {
 /// Note that this is a by-definition synchronous function. So it is available
 /// whether or not "async" feature is enabled
 ///
-pub fn kmsg(file_override: Option<String>) -> Result<Vec<Entry>, RMesgError> {
-    let file_contents = kmsg_raw(file_override)?;
-    let entry_results: Result<Vec<Entry>, EntryParsingError> =
-        file_contents.lines().map(entry_from_line).collect();
+pub fn kmsg_from_path(file_override: Option<&Path>) -> Result<Vec<Entry>, RMesgError> {
+    let file_contents = kmsg_raw_from_path(file_override)?;
+    let entry_results: Result<Vec<Entry>, EntryParsingError> = file_contents
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(entry_from_line)
+        .collect();
+
+    let mut entries = entry_results?;
+    for entry in entries.iter_mut() {
+        entry.source_backend = Some(Backend::DevKMsg);
+    }
+
+    Ok(entries)
+}
+
+/// Writes `message` into the kernel log buffer, at log level `level` (0 to 7, as in `LogLevel`),
+/// such as a marker for where a test starts. The kernel logs it with the user facility.
+/// It reads back as an entry of its own, through `kmsg_from_path()` or `KMsgEntriesIter`.
+pub fn kmsg_write(
+    message: &str,
+    level: u8,
+    file_override: Option<&Path>,
+) -> Result<(), RMesgError> {
+    if level > LogLevel::Debug as u8 {
+        return Err(RMesgError::IntegerOutOfBound(format!(
+            "Log level {} is not between 0 and 7",
+            level
+        )));
+    }
+
+    write_record(level.into(), message, file_override)
+}
 
-    Ok(entry_results?)
+/// Writes the message of `entry` into the kernel log buffer, with its facility and level
+/// (level info when it has none). The kernel replaces the kern facility with user.
+pub fn kmsg_write_entry(entry: &Entry, file_override: Option<&Path>) -> Result<(), RMesgError> {
+    let priority = match (entry.to_faclev(), entry.level) {
+        (Some(faclev), _) => faclev,
+        (None, Some(level)) => level as u32,
+        (None, None) => LogLevel::Info as u32,
+    };
+
+    write_record(priority, &entry.message, file_override)
+}
+
+// Each write to /dev/kmsg is a record of its own: <priority>message
+fn write_record(
+    priority: u32,
+    message: &str,
+    file_override: Option<&Path>,
+) -> Result<(), RMesgError> {
+    let path = file_override.unwrap_or_else(|| Path::new(DEV_KMSG_PATH));
+    let record = format!("<{}>{}\n", priority, message);
+
+    let result = stdfs::OpenOptions::new()
+        .append(true)
+        .open(path)
+        .and_then(|mut file| file.write_all(record.as_bytes()));
+
+    match result {
+        Ok(()) => Ok(()),
+        Err(e) if e.raw_os_error() == Some(libc::EPERM) => Err(RMesgError::OperationNotPermitted(
+            format!("Write to File {}", path.display()),
+        )),
+        Err(e) => Err(RMesgError::DevKMsgFileOpenError(format!(
+            "Unable to write to file {}: {}",
+            path.display(),
+            e
+        ))),
+    }
+}
+
+/// Same as `kmsg_from_path`, but only the entries from sequence number `start_seq` on
+/// (see `KMsgEntriesIter::with_seek`), along with their continuation lines.
+///
+/// Returns `RMesgError::SequenceNotFound` if the oldest entry in the buffer is already past
+/// `start_seq`, i.e. if the entries from `start_seq` on have been overwritten.
+pub fn kmsg_from_seq(
+    start_seq: u64,
+    file_override: Option<&Path>,
+) -> Result<Vec<Entry>, RMesgError> {
+    let entries = kmsg_from_path(file_override)?;
+
+    if let Some(oldest) = entries.iter().find_map(|entry| entry.sequence_num) {
+        if oldest > start_seq {
+            return Err(RMesgError::SequenceNotFound(start_seq));
+        }
+    }
+
+    Ok(entries
+        .into_iter()
+        .skip_while(|entry| !entry.sequence_num.is_some_and(|seq| seq >= start_seq))
+        .collect())
+}
+
+/// Same as `kmsg_from_path`, with continuation lines appended to the message of the entry before
+/// them, separated by newlines (see `KMsgOptions::merge_continuations`).
+pub fn kmsg_merged(file_override: Option<&Path>) -> Result<Vec<Entry>, RMesgError> {
+    let file_contents = kmsg_raw_from_path(file_override)?;
+    let mut entries: Vec<Entry> = Vec::new();
+
+    for line in file_contents.lines().filter(|line| !line.is_empty()) {
+        match entries.last_mut() {
+            Some(entry) if line.starts_with(' ') => {
+                entry.message.push('\n');
+                entry.message.push_str(line);
+            }
+            _ => {
+                let mut entry = entry_from_line(line)?;
+                entry.source_backend = Some(Backend::DevKMsg);
+                entries.push(entry);
+            }
+        }
+    }
+
+    Ok(entries)
 }
 
 // Message spec: https://github.com/torvalds/linux/blob/master/Documentation/ABI/testing/dev-kmsg
@@ -174,7 +1017,12 @@ pub fn kmsg(file_override: Option<String>) -> Result<Vec<Entry>, RMesgError> {
 //  LINE 3 = foobar ; with semicolon
 // 6,2,0,-;x86/fpu: Supporting XSAVE feature 0x001: 'x87 floating point registers'
 // 6,3,0,-,more,deets;x86/fpu: Supporting XSAVE; feature 0x002: 'SSE registers'
+#[cfg(rmesg_regex_parser)]
 pub fn entry_from_line(line: &str) -> Result<Entry, EntryParsingError> {
+    if line.is_empty() {
+        return Err(EntryParsingError::EmptyLine);
+    }
+
     if let Some(kmsgparts) = RE_ENTRY_WITH_TIMESTAMP.captures(line) {
         let (facility, level) = match kmsgparts.name("faclevstr") {
             Some(faclevstr) => common::parse_favlecstr(faclevstr.as_str(), line)?,
@@ -182,7 +1030,11 @@ pub fn entry_from_line(line: &str) -> Result<Entry, EntryParsingError> {
         };
 
         let sequence_num = match kmsgparts.name("sequencenum") {
-            Some(sequencestr) => Some(common::parse_fragment::<usize>(sequencestr.as_str(), line)?),
+            Some(sequencestr) => Some(common::parse_fragment::<u64>(
+                sequencestr.as_str(),
+                common::Field::SequenceNum,
+                line,
+            )?),
             None => None,
         };
 
@@ -191,34 +1043,212 @@ pub fn entry_from_line(line: &str) -> Result<Entry, EntryParsingError> {
             None => None,
         };
 
-        let message = kmsgparts["message"].to_owned();
+        let flags = &kmsgparts["flags"];
+        let message = &kmsgparts["message"];
 
         Ok(Entry {
             facility,
             level,
             sequence_num,
             timestamp_from_system_start,
-            message,
+            message: message.to_owned(),
+            category: classify_message(message),
+            raw_flags: Some(flags.to_owned()),
+            dict: common::parse_flags_dict(flags),
+            source_backend: None,
+        })
+    } else {
+        check_continuation_line(line)?;
+
+        Ok(Entry {
+            facility: None,
+            level: None,
+            sequence_num: None,
+            timestamp_from_system_start: None,
+            message: line.to_owned(),
+            category: classify_message(line),
+            raw_flags: None,
+            dict: HashMap::new(),
+            source_backend: None,
+        })
+    }
+}
+
+/// Same as the regex-based parser, but built over `parse_kmsg_header`.
+#[cfg(not(rmesg_regex_parser))]
+pub fn entry_from_line(line: &str) -> Result<Entry, EntryParsingError> {
+    entry_from_line_by_hand(line)
+}
+
+// Also built for tests with the regex-based parser, to check the two agree
+#[cfg(any(not(rmesg_regex_parser), test))]
+fn entry_from_line_by_hand(line: &str) -> Result<Entry, EntryParsingError> {
+    if line.is_empty() {
+        return Err(EntryParsingError::EmptyLine);
+    }
+
+    if let Some(header) = parse_kmsg_header(line) {
+        let (facility, level) = common::parse_faclev(header.faclev, line)?;
+
+        Ok(Entry {
+            facility,
+            level,
+            sequence_num: Some(header.sequence_num),
+            timestamp_from_system_start: common::timestamp_from_microsecs(header.timestamp_micros),
+            message: header.message.to_owned(),
+            category: classify_message(header.message),
+            raw_flags: Some(header.flags.to_owned()),
+            dict: common::parse_flags_dict(header.flags),
+            source_backend: None,
         })
     } else {
+        check_kmsg_header_fields(line)?;
+        check_continuation_line(line)?;
+
         Ok(Entry {
             facility: None,
             level: None,
             sequence_num: None,
             timestamp_from_system_start: None,
             message: line.to_owned(),
+            category: classify_message(line),
+            raw_flags: None,
+            dict: HashMap::new(),
+            source_backend: None,
         })
     }
 }
 
+/// For a line `parse_kmsg_header` rejected, returns the error the regex-based parser gives
+/// for it, if any: lines shaped like a header whose fields are empty or don't fit their
+/// integer types are errors, not continuation lines.
+fn check_kmsg_header_fields(line: &str) -> Result<(), EntryParsingError> {
+    if let Some([faclevstr, sequencestr, timestampstr]) = kmsg_header_fields(line) {
+        common::parse_favlecstr(faclevstr, line)?;
+        common::parse_fragment::<u64>(sequencestr, common::Field::SequenceNum, line)?;
+        common::parse_fragment::<u64>(timestampstr, common::Field::Timestamp, line)?;
+    }
+
+    Ok(())
+}
+
+/// For a line without a header, returns `EntryParsingError::UnrecognizedFormat` unless it's a
+/// continuation line, which starts with a space.
+fn check_continuation_line(line: &str) -> Result<(), EntryParsingError> {
+    if line.starts_with(' ') {
+        Ok(())
+    } else {
+        Err(EntryParsingError::UnrecognizedFormat(format!(
+            "Line is neither a header nor a continuation line. Line: {}",
+            line
+        )))
+    }
+}
+
+/// The unparsed facility and level, sequence number and timestamp of a line, if it has the
+/// shape `RE_ENTRY_WITH_TIMESTAMP` matches: three comma-separated runs of digits (which may be
+/// empty, and padded with whitespace), then the flags up to the first semicolon.
+fn kmsg_header_fields(line: &str) -> Option<[&str; 3]> {
+    let (header, _) = line.split_once(';')?;
+    let mut fields = header.splitn(4, ',');
+
+    let mut numbers = [""; 3];
+    for number in numbers.iter_mut() {
+        *number = fields.next()?.trim_matches(common::is_posix_space);
+        if !common::is_digits(number) {
+            return None;
+        }
+    }
+
+    // The flags, which must follow a comma
+    fields.next().map(|_| numbers)
+}
+
+/// Parses a /dev/kmsg line without copying the message out of it.
+///
+/// Parses the same lines as `entry_from_line`, whichever parser that uses, and fails on the
+/// same lines: continuation lines, which have no header, are all message.
+pub fn entry_from_line_ref(line: &str) -> Result<EntryRef<'_>, EntryParsingError> {
+    if line.is_empty() {
+        return Err(EntryParsingError::EmptyLine);
+    }
+
+    match parse_kmsg_header(line) {
+        Some(header) => {
+            let (facility, level) = common::parse_faclev(header.faclev, line)?;
+
+            Ok(EntryRef {
+                facility,
+                level,
+                sequence_num: Some(header.sequence_num),
+                timestamp_from_system_start: common::timestamp_from_microsecs(
+                    header.timestamp_micros,
+                ),
+                raw_flags: Some(header.flags),
+                message: header.message,
+            })
+        }
+        None => {
+            check_kmsg_header_fields(line)?;
+            check_continuation_line(line)?;
+
+            Ok(EntryRef {
+                facility: None,
+                level: None,
+                sequence_num: None,
+                timestamp_from_system_start: None,
+                raw_flags: None,
+                message: line,
+            })
+        }
+    }
+}
+
+/// The fields at the start of a /dev/kmsg line, before the message.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct KmsgHeader<'a> {
+    /// Syslog priority: the facility in the top 28 bits, the level in the bottom 3 bits
+    pub faclev: u32,
+    pub sequence_num: u64,
+    /// Microseconds since the system started
+    pub timestamp_micros: u64,
+    /// Everything between the timestamp and the semicolon, such as `-` or `-,KEY=VALUE`
+    pub flags: &'a str,
+    pub message: &'a str,
+}
+
+/// Splits a /dev/kmsg line into its header and message by hand, without a regular expression.
+///
+/// Accepts the lines `RE_ENTRY_WITH_TIMESTAMP` does: three comma-separated integers
+/// (optionally padded with whitespace), then anything up to the first semicolon (the flags),
+/// then the message. Returns `None` for anything else, such as continuation lines.
+/// See `parser::parse_kmsg_header`, which this wraps.
+pub fn parse_kmsg_header(line: &str) -> Option<KmsgHeader<'_>> {
+    let (faclev, sequence_num, timestamp_micros, message_start) =
+        parser::parse_kmsg_header(line.as_bytes())?;
+
+    Some(KmsgHeader {
+        faclev,
+        sequence_num,
+        timestamp_micros,
+        flags: line[..message_start - 1]
+            .splitn(4, ',')
+            .nth(3)
+            .unwrap_or(""),
+        message: &line[message_start..],
+    })
+}
+
 /**********************************************************************************/
 // Tests! Tests! Tests!
 #[cfg(all(test, target_os = "linux"))]
 mod test {
     use super::*;
+    use crate::common::TempFile;
+    use crate::entry::LogFacility;
     #[test]
     fn test_kmsg() {
-        let entries = kmsg(None);
+        let entries = kmsg_from_path(None);
         assert!(entries.is_ok(), "Response from kmsg not Ok");
         assert!(!entries.unwrap().is_empty(), "Should have non-empty logs");
     }
@@ -230,7 +1260,7 @@ mod test {
         //assert!(enable_timestamp_result.is_ok());
 
         // Don't clear the buffer. Poll every second.
-        let iterator_result = KMsgEntriesIter::with_options(None, false);
+        let iterator_result = KMsgEntriesIter::with_path(None, false);
         assert!(iterator_result.is_ok());
 
         let iterator = iterator_result.unwrap();
@@ -244,8 +1274,538 @@ mod test {
         }
     }
 
+    /// Resident set size of this process in kilobytes, if the platform reports it
+    fn resident_set_size_kb() -> Option<u64> {
+        let status = stdfs::read_to_string("/proc/self/status").ok()?;
+        let line = status.lines().find(|l| l.starts_with("VmRSS:"))?;
+        line.split_whitespace().nth(1)?.parse().ok()
+    }
+
+    #[test]
+    fn test_iterator_does_not_buffer_entries() {
+        // The iterator is expected to stream entries off the file, so reading a bunch of them
+        // shouldn't grow memory by more than this.
+        const MAX_GROWTH_KB: u64 = 16 * 1024;
+
+        // Reading beyond what's in the buffer would block
+        let available = kmsg_from_path(None).unwrap().len().min(100);
+
+        // Sampled after the eager read above, so only the iterator's own growth is measured
+        let rss_before = match resident_set_size_kb() {
+            Some(rss) => rss,
+            None => return,
+        };
+
+        let iterator = KMsgEntriesIter::with_path(None, false).unwrap();
+        for entry in iterator.take(available) {
+            assert!(entry.is_ok());
+        }
+
+        let rss_after = resident_set_size_kb().unwrap();
+        assert!(
+            rss_after.saturating_sub(rss_before) <= MAX_GROWTH_KB,
+            "Reading {} entries grew resident memory from {}kB to {}kB",
+            available,
+            rss_before,
+            rss_after
+        );
+    }
+
+    #[test]
+    fn test_iterator_normalize_messages() {
+        let path = TempFile::with_contents(
+            "normalize",
+            "6,1,100,-;  padded\0message  with  double\0 spaces  \n6,2,200,-;a\x07b\n",
+        );
+
+        let options = KMsgOptions {
+            normalize_messages: true,
+            ..Default::default()
+        };
+        let iterator =
+            KMsgEntriesIter::with_kmsg_options(Some(path.as_path()), false, options).unwrap();
+        let messages: Vec<String> = iterator.map(|e| e.unwrap().message).collect();
+
+        assert_eq!(messages, vec!["padded message with double spaces", "ab"]);
+    }
+
+    #[test]
+    fn test_drain_all() {
+        let path = TempFile::with_contents(
+            "drain-all",
+            b"6,1,100,-;first\n6,2,200,-;\xff\xfe not utf-8\n6,3,300,-;third\n",
+        );
+        let file_override = Some(path.as_path());
+
+        let (entries, errors) = KMsgEntriesIter::with_path(file_override, false)
+            .unwrap()
+            .drain_all();
+        let messages: Vec<String> = entries.into_iter().map(|e| e.message).collect();
+        assert_eq!(messages, vec!["first", "third"]);
+        assert_eq!(errors.len(), 1);
+
+        let entries = KMsgEntriesIter::with_path(file_override, false)
+            .unwrap()
+            .drain_all_ok();
+        assert_eq!(entries.len(), 2);
+
+        // Returns once the kernel log buffer is read, rather than waiting for new entries
+        let (entries, _) = KMsgEntriesIter::with_path(None, false).unwrap().drain_all();
+        assert!(!entries.is_empty());
+    }
+
+    #[test]
+    fn test_merge_continuations() {
+        let path = TempFile::with_contents(
+            "merge",
+            " LINE0=orphan\n6,1,100,-;usb 1-1: new device\n SUBSYSTEM=usb\n DEVICE=+usb:1-1\n6,2,200,-;next\n",
+        );
+        let file_override = Some(path.as_path());
+
+        let merged: Vec<Entry> =
+            KMsgEntriesIter::with_merge_continuations(file_override, false, true)
+                .unwrap()
+                .map(Result::unwrap)
+                .collect();
+        let unmerged = KMsgEntriesIter::with_merge_continuations(file_override, false, false)
+            .unwrap()
+            .count();
+        let eager = kmsg_merged(file_override).unwrap();
+
+        assert_eq!(unmerged, 5);
+        assert_eq!(merged.len(), 3);
+        assert_eq!(merged[0].message, " LINE0=orphan");
+        assert_eq!(merged[1].sequence_num, Some(1));
+        assert_eq!(
+            merged[1].timestamp_from_system_start,
+            Some(Duration::from_micros(100))
+        );
+        assert_eq!(
+            merged[1].message,
+            "usb 1-1: new device\n SUBSYSTEM=usb\n DEVICE=+usb:1-1"
+        );
+        assert_eq!(merged[2].message, "next");
+        assert_eq!(eager, merged);
+    }
+
+    #[test]
+    fn test_with_seek() {
+        let path = TempFile::with_contents(
+            "seek",
+            "6,5,100,-;five\n SUBSYSTEM=usb\n6,6,200,-;six\n SUBSYSTEM=pci\n6,7,300,-;seven\n",
+        );
+        let file_override = Some(path.as_path());
+
+        let from_six: Vec<String> = KMsgEntriesIter::with_seek(file_override, false, 6)
+            .unwrap()
+            .map(|e| e.unwrap().message)
+            .collect();
+        let from_oldest = KMsgEntriesIter::with_seek(file_override, true, 5)
+            .unwrap()
+            .count();
+        let past_newest = KMsgEntriesIter::with_seek(file_override, false, 8)
+            .unwrap()
+            .count();
+        let overwritten = KMsgEntriesIter::with_seek(file_override, false, 4);
+
+        assert_eq!(from_six, vec!["six", " SUBSYSTEM=pci", "seven"]);
+        assert_eq!(from_oldest, 5);
+        assert_eq!(past_newest, 0);
+        assert!(matches!(overwritten, Err(RMesgError::SequenceNotFound(4))));
+    }
+
+    #[test]
+    fn test_with_tail() {
+        use std::io::Write;
+
+        let path = TempFile::with_contents("tail", "6,5,100,-;old\n6,6,200,-;older\n");
+        let file_override = Some(path.as_path());
+
+        let mut iter = KMsgEntriesIter::with_tail(file_override, false).unwrap();
+        assert!(iter.next().is_none());
+
+        let mut file = stdfs::OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"6,7,300,-;new\n").unwrap();
+        let messages: Vec<String> = iter.map(|e| e.unwrap().message).collect();
+
+        assert_eq!(messages, vec!["new"]);
+    }
+
+    #[test]
+    fn test_from_raw_fd() {
+        let path = TempFile::with_contents("from-fd", "6,1,100,-;first\n6,2,200,-;second\n");
+        let fd = stdfs::File::open(&path).unwrap().into_raw_fd();
+
+        let messages: Vec<String> = KMsgEntriesIter::from_raw_fd(fd, false)
+            .unwrap()
+            .map(|e| e.unwrap().message)
+            .collect();
+        assert_eq!(messages, vec!["first", "second"]);
+
+        assert!(matches!(
+            KMsgEntriesIter::from_raw_fd(-1, false),
+            Err(RMesgError::IOError { .. })
+        ));
+    }
+
+    #[test]
+    fn test_kmsg_raw_bytes() {
+        let path = TempFile::new("raw-bytes");
+        let contents = b"6,1,100,-;first\n6,2,200,-;\xff\xfe not utf-8\n";
+        stdfs::write(&path, contents).unwrap();
+        let file_override = Some(path.as_path());
+
+        let bytes = kmsg_raw_bytes(file_override).unwrap();
+        let lossy = kmsg_raw_from_path(file_override).unwrap();
+
+        assert_eq!(bytes, contents);
+        assert_eq!(
+            lossy,
+            "6,1,100,-;first\n6,2,200,-;\u{FFFD}\u{FFFD} not utf-8\n"
+        );
+    }
+
+    #[test]
+    fn test_last_sequence_num() {
+        let path = TempFile::with_contents(
+            "last-seq",
+            "6,7,100,-;first\n LINE=continuation\n6,8,200,-;second\n",
+        );
+
+        let mut iter = KMsgEntriesIter::with_path(Some(path.as_path()), false).unwrap();
+        assert_eq!(iter.last_sequence_num(), None);
+        iter.next().unwrap().unwrap();
+        assert_eq!(iter.last_sequence_num(), Some(7));
+        iter.next().unwrap().unwrap();
+        assert_eq!(iter.last_sequence_num(), Some(7));
+        iter.next().unwrap().unwrap();
+        assert_eq!(iter.last_sequence_num(), Some(8));
+
+        iter.restart().unwrap();
+        assert_eq!(iter.last_sequence_num(), None);
+    }
+
+    #[test]
+    fn test_source_backend() {
+        let path = TempFile::with_contents("source", "6,1,100,-;first\n LINE=continuation\n");
+
+        let mut iter = KMsgEntriesIter::with_path(Some(path.as_path()), false).unwrap();
+        assert_eq!(
+            iter.next().unwrap().unwrap().source_backend,
+            Some(Backend::DevKMsg)
+        );
+
+        let entries = kmsg_from_path(Some(path.as_path())).unwrap();
+        assert!(entries
+            .iter()
+            .all(|entry| entry.source_backend == Some(Backend::DevKMsg)));
+
+        // Parsing a line on its own doesn't read from any backend
+        assert_eq!(
+            entry_from_line("6,1,100,-;first").unwrap().source_backend,
+            None
+        );
+    }
+
+    #[test]
+    fn test_kmsg_raw_to_writer() {
+        let path = TempFile::new("raw-writer");
+        let mut contents = b"6,1,100,-;\xff not utf-8\n".to_vec();
+        for seq in 2..1000 {
+            contents.extend_from_slice(format!("6,{},100,-;entry\n", seq).as_bytes());
+        }
+        assert!(contents.len() > READ_BUFFER_CAPACITY);
+        stdfs::write(&path, &contents).unwrap();
+
+        let mut written = Vec::new();
+        let count = kmsg_raw_to_writer(Some(path.as_path()), &mut written).unwrap();
+
+        assert_eq!(written, contents);
+        assert_eq!(count, contents.len() as u64);
+    }
+
+    #[test]
+    fn test_with_capacity() {
+        let path = TempFile::with_contents("capacity", "6,1,100,-;first\n6,2,200,-;second\n");
+        let file_override = Some(path.as_path());
+
+        // Smaller than a line, which a regular file copes with
+        let mut iter = KMsgEntriesIter::with_capacity(file_override, false, 4).unwrap();
+        assert_eq!(iter.reader.capacity(), 4);
+        let messages: Vec<String> = iter.by_ref().map(|e| e.unwrap().message).collect();
+
+        assert_eq!(messages, vec!["first", "second"]);
+    }
+
+    #[test]
+    fn test_kmsg_write() {
+        let path = TempFile::with_contents("write", "");
+        let file_override = Some(path.as_path());
+
+        kmsg_write("---- test_foo start ----", 5, file_override).unwrap();
+        let mut entry = entry_from_line("30,7,100,-;daemon says hi").unwrap();
+        kmsg_write_entry(&entry, file_override).unwrap();
+        entry.level = None;
+        kmsg_write_entry(&entry, file_override).unwrap();
+        let out_of_bound = kmsg_write("too low", 8, file_override);
+
+        let contents = stdfs::read_to_string(&path).unwrap();
+
+        assert_eq!(
+            contents,
+            "<5>---- test_foo start ----\n<30>daemon says hi\n<6>daemon says hi\n"
+        );
+        assert!(matches!(
+            out_of_bound,
+            Err(RMesgError::IntegerOutOfBound(_))
+        ));
+    }
+
+    #[test]
+    fn test_kmsg_from_seq() {
+        let path = TempFile::with_contents(
+            "from-seq",
+            "6,5,100,-;five\n SUBSYSTEM=usb\n6,6,200,-;six\n SUBSYSTEM=pci\n6,7,300,-;seven\n",
+        );
+        let file_override = Some(path.as_path());
+
+        let from_six: Vec<String> = kmsg_from_seq(6, file_override)
+            .unwrap()
+            .into_iter()
+            .map(|e| e.message)
+            .collect();
+        let past_newest = kmsg_from_seq(8, file_override).unwrap();
+        let overwritten = kmsg_from_seq(4, file_override);
+
+        assert_eq!(from_six, vec!["six", " SUBSYSTEM=pci", "seven"]);
+        assert!(past_newest.is_empty());
+        assert!(matches!(overwritten, Err(RMesgError::SequenceNotFound(4))));
+    }
+
+    #[test]
+    fn test_restart() {
+        let path = TempFile::with_contents("restart", "6,1,100,-;first\n6,2,200,-;second\n");
+
+        let mut iter = KMsgEntriesIter::with_seek(Some(path.as_path()), false, 2).unwrap();
+        let first_pass: Vec<Entry> = iter.by_ref().map(Result::unwrap).collect();
+        iter.restart().unwrap();
+        let second_pass: Vec<Entry> = iter.by_ref().map(Result::unwrap).collect();
+
+        // Restarting part way through
+        iter.restart().unwrap();
+        iter.next();
+        iter.restart().unwrap();
+        let third_pass: Vec<Entry> = iter.map(Result::unwrap).collect();
+
+        assert_eq!(first_pass.len(), 1);
+        assert_eq!(second_pass.len(), 2);
+        assert_eq!(third_pass, second_pass);
+    }
+
+    #[test]
+    fn test_as_raw_fd() {
+        let iterator = KMsgEntriesIter::with_path(None, false).unwrap();
+        let fd = iterator.as_raw_fd();
+        assert!(
+            unsafe { libc::fcntl(fd, libc::F_GETFD) } != -1,
+            "fd should be open"
+        );
+    }
+
+    #[test]
+    fn test_into_raw_fd() {
+        let iterator = KMsgEntriesIter::with_path(None, false).unwrap();
+        let fd = iterator.into_raw_fd();
+
+        // Still open after the iterator is gone, and ours to close
+        assert!(
+            unsafe { libc::fcntl(fd, libc::F_GETFD) } != -1,
+            "fd should be open"
+        );
+        assert_eq!(unsafe { libc::close(fd) }, 0);
+    }
+
+    #[test]
+    fn test_read_timeout() {
+        use std::io::Write;
+
+        let path = TempFile::new("read-timeout");
+        let cpath = std::ffi::CString::new(path.to_string_lossy().into_owned()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(cpath.as_ptr(), 0o600) }, 0);
+
+        // Opening a FIFO for reading and writing doesn't block, and keeps it from ending
+        let mut writer = stdfs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&path)
+            .unwrap();
+        let mut iter =
+            KMsgEntriesIter::with_timeout(Some(path.as_path()), false, Duration::from_millis(50))
+                .unwrap();
+
+        assert!(matches!(iter.next(), Some(Err(RMesgError::ReadTimeout))));
+
+        writer.write_all(b"6,1,100,-;arrived\n").unwrap();
+        assert_eq!(iter.next().unwrap().unwrap().message, "arrived");
+        assert!(matches!(iter.next(), Some(Err(RMesgError::ReadTimeout))));
+    }
+
+    #[test]
+    fn test_drain_available() {
+        use std::io::Write;
+
+        let path = TempFile::new("drain-avail");
+        let cpath = std::ffi::CString::new(path.to_string_lossy().into_owned()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(cpath.as_ptr(), 0o600) }, 0);
+
+        let mut writer = stdfs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&path)
+            .unwrap();
+        let mut iter = KMsgEntriesIter::with_path(Some(path.as_path()), false).unwrap();
+
+        assert!(iter.try_next().is_none());
+
+        writer
+            .write_all(b"6,1,100,-;first\n6,2,200,-;second\n")
+            .unwrap();
+        let messages: Vec<String> = iter
+            .drain_available()
+            .into_iter()
+            .map(|e| e.unwrap().message)
+            .collect();
+        assert_eq!(messages, vec!["first", "second"]);
+        assert!(iter.try_next().is_none());
+
+        // Blocking again afterwards
+        let fd = iter.as_raw_fd();
+        assert_eq!(
+            unsafe { libc::fcntl(fd, libc::F_GETFL) } & libc::O_NONBLOCK,
+            0
+        );
+    }
+
+    #[test]
+    fn test_with_epoll() {
+        use std::io::Write;
+
+        let path = TempFile::new("epoll");
+        let cpath = std::ffi::CString::new(path.to_string_lossy().into_owned()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(cpath.as_ptr(), 0o600) }, 0);
+
+        let mut writer = stdfs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&path)
+            .unwrap();
+        let mut iter = KMsgEntriesIter::with_epoll(Some(path.as_path()), false).unwrap();
+        assert!(iter.epoll_fd() >= 0);
+        assert_ne!(iter.epoll_fd(), iter.as_raw_fd());
+
+        writer
+            .write_all(b"6,1,100,-;first\n6,2,200,-;second\n")
+            .unwrap();
+        assert_eq!(iter.next().unwrap().unwrap().message, "first");
+        assert_eq!(iter.next().unwrap().unwrap().message, "second");
+
+        // epoll can't wait on regular files
+        let path = TempFile::with_contents("epoll-file", "6,1,100,-;first\n");
+        let result = KMsgEntriesIter::with_epoll(Some(path.as_path()), false);
+        assert!(matches!(result, Err(RMesgError::IOError { .. })));
+    }
+
+    #[test]
+    fn test_next_batch() {
+        use std::io::Write;
+
+        let path = TempFile::new("next-batch");
+        let cpath = std::ffi::CString::new(path.to_string_lossy().into_owned()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(cpath.as_ptr(), 0o600) }, 0);
+
+        let mut writer = stdfs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&path)
+            .unwrap();
+        let mut iter = KMsgEntriesIter::with_path(Some(path.as_path()), false).unwrap();
+
+        writer
+            .write_all(b"6,1,100,-;first\n6,2,200,-;second\n6,3,300,-;third\n")
+            .unwrap();
+        let messages = |batch: Vec<Result<Entry, RMesgError>>| -> Vec<String> {
+            batch.into_iter().map(|e| e.unwrap().message).collect()
+        };
+
+        assert!(iter.next_batch(0).is_empty());
+        assert_eq!(messages(iter.next_batch(2)), vec!["first", "second"]);
+        // The rest of what's available, without waiting for more
+        assert_eq!(messages(iter.next_batch(10)), vec!["third"]);
+    }
+
+    #[test]
+    fn test_open_timeout() {
+        let options = KMsgOptions {
+            open_timeout: Some(Duration::from_secs(5)),
+            ..Default::default()
+        };
+        assert!(KMsgEntriesIter::with_kmsg_options(None, false, options).is_ok());
+
+        // Opening a FIFO for reading blocks until there's a writer
+        let path = TempFile::new("open-timeout");
+        let cpath = std::ffi::CString::new(path.to_string_lossy().into_owned()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(cpath.as_ptr(), 0o600) }, 0);
+
+        let options = KMsgOptions {
+            open_timeout: Some(Duration::from_millis(100)),
+            ..Default::default()
+        };
+        let result = KMsgEntriesIter::with_kmsg_options(Some(path.as_path()), false, options);
+
+        // Unblock the opening thread before cleaning up
+        let writer = unsafe { libc::open(cpath.as_ptr(), libc::O_WRONLY | libc::O_NONBLOCK) };
+        if writer >= 0 {
+            unsafe { libc::close(writer) };
+        }
+
+        assert!(matches!(result, Err(RMesgError::OpenTimeout(_))));
+    }
+
+    #[test]
+    fn test_open_cloexec() {
+        let fd_flags = |options: KMsgOptions| {
+            let iterator = KMsgEntriesIter::with_kmsg_options(None, false, options).unwrap();
+            unsafe { libc::fcntl(iterator.as_raw_fd(), libc::F_GETFD) }
+        };
+
+        let flags = fd_flags(KMsgOptions::default());
+        assert_ne!(flags & libc::FD_CLOEXEC, 0, "Should be close-on-exec");
+
+        let flags = fd_flags(KMsgOptions {
+            open_cloexec: false,
+            ..Default::default()
+        });
+        assert!(flags >= 0);
+        assert_eq!(flags & libc::FD_CLOEXEC, 0, "Should not be close-on-exec");
+    }
+
     #[test]
     fn test_parse_serialize() {
+        // Every field of the header is captured
+        let line = "14,779,91650777797,-,SUBSYSTEM=usb;docker0: port 2 entered disabled state";
+        let entry: Entry = line.parse().unwrap();
+        assert_eq!(entry.facility, Some(LogFacility::User));
+        assert_eq!(entry.level, Some(LogLevel::Info));
+        assert_eq!(entry.sequence_num, Some(779));
+        assert_eq!(
+            entry.timestamp_from_system_start,
+            Some(Duration::from_micros(91650777797))
+        );
+        assert_eq!(entry.raw_flags.as_deref(), Some("-,SUBSYSTEM=usb"));
+        assert_eq!(entry.message, "docker0: port 2 entered disabled state");
+        assert_eq!(Entry::from_kmsg_str(line).unwrap(), entry);
+        assert_eq!(entry_from_line(line).unwrap(), entry);
+
         let line1 = " LINE2=foobar";
         let e1r = entry_from_line(line1);
         assert!(e1r.is_ok());
@@ -258,4 +1818,51 @@ mod test {
         let line2again = e2r.unwrap().to_kmsg_str().unwrap();
         assert_eq!(line2, line2again);
     }
+
+    #[test]
+    fn test_entry_from_line_ref() {
+        for line in [
+            "6,779,91650777797,-;docker0: port 2(veth98d5024) entered disabled state",
+            "6,3,0,-,more,deets;x86/fpu: Supporting XSAVE; feature 0x002",
+            " LINE2=foobar",
+        ] {
+            let entry_ref = entry_from_line_ref(line).unwrap();
+            assert_eq!(entry_ref.to_owned(), entry_from_line(line).unwrap());
+
+            // The message is a slice of the line, not a copy
+            let line_range = line.as_bytes().as_ptr_range();
+            assert!(
+                line_range.contains(&entry_ref.message.as_ptr()) || entry_ref.message.is_empty()
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_kmsg_header() {
+        assert_eq!(
+            parse_kmsg_header("6,3,0,-,more,deets;x86/fpu: Supporting XSAVE; feature 0x002"),
+            Some(KmsgHeader {
+                faclev: 6,
+                sequence_num: 3,
+                timestamp_micros: 0,
+                flags: "-,more,deets",
+                message: "x86/fpu: Supporting XSAVE; feature 0x002",
+            })
+        );
+        assert_eq!(
+            parse_kmsg_header(" 91 , 13 , 1234568 , - ;padded"),
+            Some(KmsgHeader {
+                faclev: 91,
+                sequence_num: 13,
+                timestamp_micros: 1234568,
+                flags: " - ",
+                message: "padded",
+            })
+        );
+
+        assert_eq!(parse_kmsg_header(" LINE 3 = foobar ; with semicolon"), None);
+        assert_eq!(parse_kmsg_header("6,45,5000002,-"), None);
+        assert_eq!(parse_kmsg_header("6,45,5000002;no flags"), None);
+        assert_eq!(parse_kmsg_header("+6,45,5000002,-;signed"), None);
+    }
 }
@@ -0,0 +1,179 @@
+/// Byte-level parser for the header of a /dev/kmsg line, written as a state machine over
+/// the bytes so it needs neither `regex` nor anything beyond `core`.
+///
+/// Message spec: https://github.com/torvalds/linux/blob/master/Documentation/ABI/testing/dev-kmsg
+///
+use core::convert::TryFrom;
+
+#[derive(Clone, Copy)]
+enum State {
+    /// Skipping whitespace before a number, no digits seen yet
+    BeforeDigits,
+    /// Within a number
+    Digits,
+    /// Skipping whitespace after a number, waiting for the comma
+    AfterDigits,
+    /// Skipping the flags (and any other fields) up to the semicolon
+    Flags,
+}
+
+/// Parses the header of a /dev/kmsg line:
+/// `<facility+level>,<sequence>,<timestamp>,<flags...>;<message>`
+///
+/// Returns the combined facility and level (syslog priority), the sequence number,
+/// the timestamp in microseconds since the system started, and the offset at which the message starts.
+///
+/// The three numbers may be padded with whitespace. Returns `None` when the line is not in this
+/// format (such as continuation lines), or when a number doesn't fit its type.
+pub fn parse_kmsg_header(line: &[u8]) -> Option<(u32, u64, u64, usize)> {
+    let mut fields = [0u64; 3];
+    let mut field = 0;
+    let mut state = State::BeforeDigits;
+
+    for (offset, &byte) in line.iter().enumerate() {
+        state = match (state, byte) {
+            (State::Flags, b';') => {
+                let faclev = u32::try_from(fields[0]).ok()?;
+                return Some((faclev, fields[1], fields[2], offset + 1));
+            }
+            (State::Flags, _) => State::Flags,
+            (State::BeforeDigits, b) | (State::AfterDigits, b) if is_space(b) => state,
+            (State::Digits, b) if is_space(b) => State::AfterDigits,
+            (State::BeforeDigits, b'0'..=b'9') | (State::Digits, b'0'..=b'9') => {
+                let digit = u64::from(byte - b'0');
+                fields[field] = fields[field].checked_mul(10)?.checked_add(digit)?;
+                State::Digits
+            }
+            (State::Digits, b',') | (State::AfterDigits, b',') => {
+                field += 1;
+                match field < fields.len() {
+                    true => State::BeforeDigits,
+                    false => State::Flags,
+                }
+            }
+            _ => return None,
+        };
+    }
+
+    None
+}
+
+/// The POSIX `[[:space:]]` class
+fn is_space(byte: u8) -> bool {
+    matches!(byte, b' ' | b'\t' | b'\n' | 0x0B | 0x0C | b'\r')
+}
+
+/**********************************************************************************/
+// Tests! Tests! Tests!
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// The parsed header, with the message in place of its offset
+    type Expected = Option<(u32, u64, u64, &'static str)>;
+
+    /// Lines, and what they're expected to parse into
+    const CASES: &[(&str, Expected)] = &[
+        (
+            "5,0,0,-;Linux version 4.14.131-linuxkit (root@6d384074ad24) (gcc version 8.3.0 (Alpine 8.3.0)) #1 SMP Fri Jul 19 12:31:17 UTC 2019",
+            Some((5, 0, 0, "Linux version 4.14.131-linuxkit (root@6d384074ad24) (gcc version 8.3.0 (Alpine 8.3.0)) #1 SMP Fri Jul 19 12:31:17 UTC 2019")),
+        ),
+        (
+            "6,1,0,-;Command, line: BOOT_IMAGE=/boot/kernel console=ttyS0",
+            Some((6, 1, 0, "Command, line: BOOT_IMAGE=/boot/kernel console=ttyS0")),
+        ),
+        (
+            "6,779,91650777797,-;docker0: port 2(veth98d5024) entered disabled state",
+            Some((6, 779, 91650777797, "docker0: port 2(veth98d5024) entered disabled state")),
+        ),
+        // Semicolons in the message
+        (
+            "6,3,0,-,more,deets;x86/fpu: Supporting XSAVE; feature 0x002: 'SSE registers'",
+            Some((6, 3, 0, "x86/fpu: Supporting XSAVE; feature 0x002: 'SSE registers'")),
+        ),
+        ("4,4,1500,-;a;b;c;", Some((4, 4, 1500, "a;b;c;"))),
+        // Metadata fields and flags
+        (
+            "6,780,91650777800,-,caller=T123;usb 1-1: new device",
+            Some((6, 780, 91650777800, "usb 1-1: new device")),
+        ),
+        ("4,44,5000001,c;continued line", Some((4, 44, 5000001, "continued line"))),
+        ("4,45,5000002,;empty flags", Some((4, 45, 5000002, "empty flags"))),
+        // Whitespace
+        (" 6 , 13 , 1234568 , - ;padded fields", Some((6, 13, 1234568, "padded fields"))),
+        ("\t6\t,13,1234568,-;tabs", Some((6, 13, 1234568, "tabs"))),
+        ("6,14,1234569,-; leading space", Some((6, 14, 1234569, " leading space"))),
+        ("4,12,1234567,-;trailing spaces   ", Some((4, 12, 1234567, "trailing spaces   "))),
+        ("6,43,5000000,-;", Some((6, 43, 5000000, ""))),
+        // Facilities and levels
+        ("0,1000,100000000,-;panic", Some((0, 1000, 100000000, "panic"))),
+        ("91,2004,50000003,-;ftp", Some((91, 2004, 50000003, "ftp"))),
+        // Largest values
+        (
+            "4294967295,18446744073709551615,18446744073709551615,-;max",
+            Some((u32::MAX, u64::MAX, u64::MAX, "max")),
+        ),
+        // Continuation lines
+        (" LINE2=foobar", None),
+        (" LINE 3 = foobar ; with semicolon", None),
+        (" SUBSYSTEM=usb", None),
+        // Not in the format
+        ("", None),
+        ("6,45,5000002,-", None),
+        ("6,45,5000002;missing flags", None),
+        ("6,45;too few fields", None),
+        (",45,5000002,-;empty field", None),
+        ("6,,5000002,-;empty field", None),
+        ("+6,45,5000002,-;signed", None),
+        ("-6,45,5000002,-;signed", None),
+        ("6,4 5,5000002,-;space within a number", None),
+        ("0x6,45,5000002,-;hex", None),
+        // Overflow
+        ("4294967296,1,1,-;faclev too large", None),
+        ("6,18446744073709551616,1,-;sequence too large", None),
+        ("6,1,18446744073709551616,-;timestamp too large", None),
+    ];
+
+    #[test]
+    fn test_parse_kmsg_header() {
+        for (line, expected) in CASES {
+            let parsed = parse_kmsg_header(line.as_bytes())
+                .map(|(faclev, seq, ts, start)| (faclev, seq, ts, &line[start..]));
+            assert_eq!(parsed, *expected, "Line: {:?}", line);
+        }
+    }
+
+    #[test]
+    fn test_non_utf8_message() {
+        let line = b"6,1,2,-;\xff\xfe";
+        assert_eq!(parse_kmsg_header(line), Some((6, 1, 2, 8)));
+    }
+
+    /// The hand-written parser must parse every line into what the regex-based one does,
+    /// and fail on the same lines with the same error.
+    #[cfg(rmesg_regex_parser)]
+    #[test]
+    fn test_agrees_with_regex() {
+        for (line, _) in CASES {
+            match (
+                super::super::entry_from_line_by_hand(line),
+                super::super::entry_from_line(line),
+            ) {
+                (Ok(by_hand), Ok(by_regex)) => assert_eq!(by_hand, by_regex, "Line: {:?}", line),
+                (Err(by_hand), Err(by_regex)) => {
+                    assert_eq!(
+                        by_hand.to_string(),
+                        by_regex.to_string(),
+                        "Line: {:?}",
+                        line
+                    )
+                }
+                (by_hand, by_regex) => panic!(
+                    "Line: {:?}, by hand: {:?}, by regex: {:?}",
+                    line, by_hand, by_regex
+                ),
+            }
+        }
+    }
+}
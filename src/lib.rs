@@ -1,15 +1,61 @@
 mod common;
 
+/// Union of the klogctl and /dev/kmsg backends (or of any iterators over entries), deduplicated
+pub mod aggregate;
+/// Analysis of entries as they are read, such as rate anomaly detection
+pub mod analysis;
+/// Kernel audit subsystem records
+pub mod audit;
+/// Compact, relative encoding of time-contiguous runs of entries
+pub mod batch;
+/// Per-thread cache of the system boot time
+pub mod cache;
+/// Bridges between entries and channels, tokio's or the standard library's
+pub mod channel;
+/// Collections of entries organized for analysis
+pub mod collection;
 pub mod entry;
 pub mod error;
+/// Composable filters over iterators of entries, by level, facility, message, sequence number or time
+pub mod filter;
+/// Indexes over entries for random access
+pub mod index;
 /// KLog Implementation (makes klogctl aka syslog system call through libc)
 pub mod klogctl;
 /// KMsg Implementation (reads from the /dev/kmsg file)
 pub mod kmsgfile;
+/// Metrics about the entries read, recorded through the `metrics` crate
+#[cfg(feature = "metrics")]
+pub mod metrics_integration;
+/// Writing entries out in formats other tools read
+pub mod output;
+/// Bounded queue of entries, most severe first
+pub mod queue;
+/// Masking of sensitive information, such as addresses and serial numbers, in entries
+#[cfg(feature = "regex")]
+pub mod redact;
+/// Replay entries through a handler to reconstruct state
+pub mod replay;
+/// Statistics over collections of entries
+pub mod stats;
+/// Asynchronous Stream of entries, read on tokio's blocking thread pool
+#[cfg(feature = "tokio")]
+pub mod stream;
+/// systemd readiness notification
+#[cfg(feature = "systemd")]
+pub mod systemd;
+/// Length-prefixed binary encoding of entries for inter-process transport
+pub mod wire;
 
+pub use aggregate::{merge_entries, MergedEntriesIterator};
+pub use kmsgfile::entry_from_line;
+
+use std::fs::{File, OpenOptions};
+use std::io::{LineWriter, Write};
 use std::iter::Iterator;
+use std::path::Path;
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Backend {
     Default,
     KLogCtl,
@@ -19,6 +65,8 @@ pub enum Backend {
 pub enum EntriesIterator {
     KLogCtl(klogctl::KLogEntries),
     DevKMsg(kmsgfile::KMsgEntriesIter),
+    /// Entries from any other source, such as a test fixture. See `EntriesIterator::from_dyn`.
+    Custom(Box<dyn Iterator<Item = Result<entry::Entry, error::RMesgError>> + Send>),
 }
 impl Iterator for EntriesIterator {
     type Item = Result<entry::Entry, error::RMesgError>;
@@ -26,13 +74,176 @@ impl Iterator for EntriesIterator {
         match self {
             Self::KLogCtl(k) => k.next(),
             Self::DevKMsg(d) => d.next(),
+            Self::Custom(c) => c.next(),
+        }
+    }
+}
+
+impl EntriesIterator {
+    /// The sequence number of the last entry returned that had one, to checkpoint where a
+    /// consumer got to (see `logs_iter_from_seq`). Always `None` for `Custom` iterators,
+    /// which don't keep track of it.
+    pub fn last_sequence_num(&self) -> Option<u64> {
+        match self {
+            Self::KLogCtl(k) => k.last_sequence_num(),
+            Self::DevKMsg(d) => d.last_sequence_num(),
+            Self::Custom(_) => None,
+        }
+    }
+
+    /// Wraps any iterator over entries, so that code written against EntriesIterator
+    /// can consume it (for instance, entries read from a fixture in a test).
+    pub fn from_dyn(
+        iter: Box<dyn Iterator<Item = Result<entry::Entry, error::RMesgError>> + Send>,
+    ) -> EntriesIterator {
+        EntriesIterator::Custom(iter)
+    }
+
+    /// Keeps the entries `f` returns true for. Errors are passed through, without calling `f`.
+    pub fn filter_entries<F: Fn(&entry::Entry) -> bool + 'static>(
+        self,
+        f: F,
+    ) -> FilteredEntriesIterator {
+        FilteredEntriesIterator {
+            inner: self,
+            predicate: Box::new(f),
+        }
+    }
+
+    /// Yields entries alone, passing errors to `on_error` instead, and carrying on past them.
+    /// This keeps a long-running tail going when an entry fails to parse.
+    pub fn skip_errors<F: Fn(error::RMesgError)>(self, on_error: F) -> SkippingEntriesIterator<F> {
+        SkippingEntriesIterator {
+            inner: self,
+            on_error,
+        }
+    }
+
+    /// Same as `skip_errors`, dropping errors without looking at them.
+    pub fn skip_errors_silent(self) -> impl Iterator<Item = entry::Entry> {
+        self.filter_map(Result::ok)
+    }
+}
+
+/// See `EntriesIterator::filter_entries`.
+pub struct FilteredEntriesIterator {
+    inner: EntriesIterator,
+    predicate: Box<dyn Fn(&entry::Entry) -> bool>,
+}
+
+impl Iterator for FilteredEntriesIterator {
+    type Item = Result<entry::Entry, error::RMesgError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let predicate = &self.predicate;
+        self.inner.find(|entry| match entry {
+            Ok(entry) => predicate(entry),
+            Err(_) => true,
+        })
+    }
+}
+
+/// See `EntriesIterator::skip_errors`.
+pub struct SkippingEntriesIterator<F: Fn(error::RMesgError)> {
+    inner: EntriesIterator,
+    on_error: F,
+}
+
+impl<F: Fn(error::RMesgError)> Iterator for SkippingEntriesIterator<F> {
+    type Item = entry::Entry;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for result in &mut self.inner {
+            match result {
+                Ok(entry) => return Some(entry),
+                Err(e) => (self.on_error)(e),
+            }
+        }
+
+        None
+    }
+}
+
+/// Convenience methods for any iterator over entries, whichever backend (or adapter) it is.
+pub trait RMesgIterator: Iterator<Item = Result<entry::Entry, error::RMesgError>> + Sized {
+    /// Keeps entries at least as severe as `min`, dropping those without a level.
+    /// Errors are passed through.
+    fn filter_level(self, min: entry::LogLevel) -> LevelFilteredIter<Self> {
+        LevelFilteredIter { inner: self, min }
+    }
+
+    /// Passes entries through unchanged, appending each to the file at `path` (created if needed)
+    /// in the same format as their `Display` output, one per line.
+    ///
+    /// When writing an entry fails, the write error is returned in place of the entry.
+    fn tee_to_file(self, path: &Path) -> Result<TeeToFileIter<Self>, error::RMesgError> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(TeeToFileIter {
+            inner: self,
+            writer: LineWriter::new(file),
+        })
+    }
+
+    /// Reads the remaining entries, and returns how many of them were errors.
+    ///
+    /// IMPORTANT NOTE: Both backends wait for new entries once they've read the buffer,
+    /// so this only returns for iterators that end.
+    fn count_errors(&mut self) -> u64 {
+        self.filter(Result::is_err).count() as u64
+    }
+}
+
+impl<I> RMesgIterator for I where I: Iterator<Item = Result<entry::Entry, error::RMesgError>> {}
+
+/// See `RMesgIterator::filter_level`.
+pub struct LevelFilteredIter<I> {
+    inner: I,
+    min: entry::LogLevel,
+}
+
+impl<I> Iterator for LevelFilteredIter<I>
+where
+    I: Iterator<Item = Result<entry::Entry, error::RMesgError>>,
+{
+    type Item = Result<entry::Entry, error::RMesgError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let min = self.min as u8;
+        self.inner.find(|entry| match entry {
+            Ok(entry) => entry.level.is_some_and(|level| level as u8 <= min),
+            Err(_) => true,
+        })
+    }
+}
+
+/// See `RMesgIterator::tee_to_file`.
+pub struct TeeToFileIter<I> {
+    inner: I,
+    writer: LineWriter<File>,
+}
+
+impl<I> Iterator for TeeToFileIter<I>
+where
+    I: Iterator<Item = Result<entry::Entry, error::RMesgError>>,
+{
+    type Item = Result<entry::Entry, error::RMesgError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entry = match self.inner.next()? {
+            Ok(entry) => entry,
+            Err(e) => return Some(Err(e)),
+        };
+
+        match writeln!(self.writer, "{}", entry) {
+            Ok(()) => Some(Ok(entry)),
+            Err(e) => Some(Err(e.into())),
         }
     }
 }
 
 pub fn log_entries(b: Backend, clear: bool) -> Result<Vec<entry::Entry>, error::RMesgError> {
     match b {
-        Backend::Default => match kmsgfile::kmsg(None) {
+        Backend::Default => match kmsgfile::kmsg_from_path(None) {
             Ok(e) => Ok(e),
             Err(error::RMesgError::DevKMsgFileOpenError(s)) => {
                 eprintln!(
@@ -43,14 +254,69 @@ pub fn log_entries(b: Backend, clear: bool) -> Result<Vec<entry::Entry>, error::
             }
             Err(e) => Err(e),
         },
-        Backend::KLogCtl => klogctl::klog(clear),
-        Backend::DevKMsg => kmsgfile::kmsg(None),
+        Backend::KLogCtl => match clear {
+            true => klogctl::klog_read_clear(),
+            false => klogctl::klog(false),
+        },
+        Backend::DevKMsg => kmsgfile::kmsg_from_path(None),
+    }
+}
+
+/// Same as `log_entries`, but returns at most the `n` most recent entries. Only those are
+/// parsed, rather than every entry in the buffer, so a line that fails to parse is an error
+/// only if it's among the last `n`.
+pub fn log_entries_n(
+    b: Backend,
+    clear: bool,
+    n: usize,
+) -> Result<Vec<entry::Entry>, error::RMesgError> {
+    match b {
+        Backend::Default => match kmsgfile::kmsg_raw_from_path(None) {
+            Ok(raw) => last_entries(&raw, n, Backend::DevKMsg),
+            Err(error::RMesgError::DevKMsgFileOpenError(s)) => {
+                eprintln!(
+                    "Falling back from device file to klogctl syscall due to error: {}",
+                    s
+                );
+                last_entries(&klogctl::klog_raw(clear)?, n, Backend::KLogCtl)
+            }
+            Err(e) => Err(e),
+        },
+        Backend::KLogCtl => last_entries(&klogctl::klog_raw(clear)?, n, Backend::KLogCtl),
+        Backend::DevKMsg => last_entries(&kmsgfile::kmsg_raw_from_path(None)?, n, Backend::DevKMsg),
+    }
+}
+
+// `backend` is the one `raw` was read from, which decides how lines are parsed
+fn last_entries(
+    raw: &str,
+    n: usize,
+    backend: Backend,
+) -> Result<Vec<entry::Entry>, error::RMesgError> {
+    let parse = match backend {
+        Backend::KLogCtl => klogctl::entry_from_line,
+        _ => kmsgfile::entry_from_line,
+    };
+
+    if n == 0 {
+        return Ok(vec![]);
     }
+
+    // parse from the end, so lines before the last n are never looked at
+    let mut last: Vec<entry::Entry> = Vec::new();
+    for line in raw.lines().rev().filter(|line| !line.is_empty()).take(n) {
+        let mut entry = parse(line)?;
+        entry.source_backend = Some(backend);
+        last.push(entry);
+    }
+    last.reverse();
+
+    Ok(last)
 }
 
 pub fn logs_raw(b: Backend, clear: bool) -> Result<String, error::RMesgError> {
     match b {
-        Backend::Default => match kmsgfile::kmsg_raw(None) {
+        Backend::Default => match kmsgfile::kmsg_raw_from_path(None) {
             Ok(e) => Ok(e),
             Err(error::RMesgError::DevKMsgFileOpenError(s)) => {
                 eprintln!(
@@ -62,13 +328,92 @@ pub fn logs_raw(b: Backend, clear: bool) -> Result<String, error::RMesgError> {
             Err(e) => Err(e),
         },
         Backend::KLogCtl => klogctl::klog_raw(clear),
-        Backend::DevKMsg => kmsgfile::kmsg_raw(None),
+        Backend::DevKMsg => kmsgfile::kmsg_raw_from_path(None),
+    }
+}
+
+/// Same as `logs_raw`, but writes the raw log to `writer` rather than returning it, and returns
+/// the number of bytes written.
+///
+/// /dev/kmsg is written out an entry at a time (see `kmsgfile::kmsg_raw_to_writer`). klogctl
+/// still has to read the whole buffer at once, but it isn't copied into a String (see
+/// `klogctl::klog_raw_to_writer`).
+pub fn logs_raw_to_writer<W: Write>(
+    b: Backend,
+    clear: bool,
+    writer: &mut W,
+) -> Result<u64, error::RMesgError> {
+    match b {
+        Backend::Default => match kmsgfile::kmsg_raw_to_writer(None, writer) {
+            Ok(written) => Ok(written),
+            Err(error::RMesgError::DevKMsgFileOpenError(s)) => {
+                eprintln!(
+                    "Falling back from device file to klogctl syscall due to error: {}",
+                    s
+                );
+                Ok(klogctl::klog_raw_to_writer(clear, writer)? as u64)
+            }
+            Err(e) => Err(e),
+        },
+        Backend::KLogCtl => Ok(klogctl::klog_raw_to_writer(clear, writer)? as u64),
+        Backend::DevKMsg => kmsgfile::kmsg_raw_to_writer(None, writer),
+    }
+}
+
+/// Same as `log_entries`, run on tokio's blocking thread pool (see `tokio::task::spawn_blocking`).
+///
+/// This is a convenience wrapper rather than asynchronous I/O: the read still blocks, just not
+/// on the executor. Returns `RMesgError::TaskPanicked` if the read panics.
+#[cfg(feature = "tokio")]
+pub async fn log_entries_async(
+    b: Backend,
+    clear: bool,
+) -> Result<Vec<entry::Entry>, error::RMesgError> {
+    run_blocking(move || log_entries(b, clear)).await
+}
+
+/// Same as `logs_raw`, run on tokio's blocking thread pool. See `log_entries_async`.
+#[cfg(feature = "tokio")]
+pub async fn logs_raw_async(b: Backend, clear: bool) -> Result<String, error::RMesgError> {
+    run_blocking(move || logs_raw(b, clear)).await
+}
+
+#[cfg(feature = "tokio")]
+async fn run_blocking<T, F>(f: F) -> Result<T, error::RMesgError>
+where
+    F: FnOnce() -> Result<T, error::RMesgError> + Send + 'static,
+    T: Send + 'static,
+{
+    match tokio::task::spawn_blocking(f).await {
+        Ok(result) => result,
+        Err(e) => Err(error::RMesgError::TaskPanicked(e.to_string())),
     }
 }
 
 pub fn logs_iter(b: Backend, clear: bool, raw: bool) -> Result<EntriesIterator, error::RMesgError> {
+    logs_iter_with(b, clear, raw, false)
+}
+
+/// Same as `logs_iter`, but when reading through klogctl (including `Backend::Default` falling
+/// back to it) with timestamps disabled, enables them (see `klogctl::klog_timestamps_enable`)
+/// rather than returning `RMesgError::KLogTimestampsDisabled`. That takes privileges: without
+/// them, this returns `RMesgError::OperationNotPermitted`.
+pub fn logs_iter_auto_enable_timestamps(
+    b: Backend,
+    clear: bool,
+    raw: bool,
+) -> Result<EntriesIterator, error::RMesgError> {
+    logs_iter_with(b, clear, raw, true)
+}
+
+fn logs_iter_with(
+    b: Backend,
+    clear: bool,
+    raw: bool,
+    auto_enable: bool,
+) -> Result<EntriesIterator, error::RMesgError> {
     match b {
-        Backend::Default => match kmsgfile::KMsgEntriesIter::with_options(None, raw) {
+        Backend::Default => match kmsgfile::KMsgEntriesIter::with_path(None, raw) {
             Ok(e) => Ok(EntriesIterator::DevKMsg(e)),
             Err(error::RMesgError::DevKMsgFileOpenError(s)) => {
                 eprintln!(
@@ -76,32 +421,100 @@ pub fn logs_iter(b: Backend, clear: bool, raw: bool) -> Result<EntriesIterator,
                     s
                 );
                 Ok(EntriesIterator::KLogCtl(
-                    klog_entries_only_if_timestamp_enabled(clear)?,
+                    klog_entries_only_if_timestamp_enabled(clear, auto_enable, None)?,
                 ))
             }
             Err(e) => Err(e),
         },
         Backend::KLogCtl => Ok(EntriesIterator::KLogCtl(
-            klog_entries_only_if_timestamp_enabled(clear)?,
+            klog_entries_only_if_timestamp_enabled(clear, auto_enable, None)?,
         )),
         Backend::DevKMsg => Ok(EntriesIterator::DevKMsg(
-            kmsgfile::KMsgEntriesIter::with_options(None, raw)?,
+            kmsgfile::KMsgEntriesIter::with_path(None, raw)?,
+        )),
+    }
+}
+
+/// Same as `logs_iter` (without clearing the buffer), but starts at the entry with sequence
+/// number `seq`, such as the one after the last entry a previous run processed.
+///
+/// The backends differ in how they find it:
+/// - `Backend::DevKMsg` reads kernel sequence numbers. See `KMsgEntriesIter::with_seek`,
+///   including the `RMesgError::SequenceNotFound` it returns once `seq` has been overwritten.
+/// - `Backend::KLogCtl` has no way to seek, so it reads the whole buffer and skips the entries
+///   numbered before `seq`. klogctl doesn't report sequence numbers, so entries are numbered by
+///   their position in the buffer (see `KLogEntries`): if the buffer was cleared or wrapped around
+///   since `seq` was recorded, this starts at the wrong entry.
+/// - `Backend::Default` uses DevKMsg, falling back to KLogCtl if /dev/kmsg can't be opened.
+pub fn logs_iter_from_seq(
+    b: Backend,
+    seq: u64,
+    raw: bool,
+) -> Result<EntriesIterator, error::RMesgError> {
+    match b {
+        Backend::Default => match kmsgfile::KMsgEntriesIter::with_seek(None, raw, seq) {
+            Ok(e) => Ok(EntriesIterator::DevKMsg(e)),
+            Err(error::RMesgError::DevKMsgFileOpenError(s)) => {
+                eprintln!(
+                    "Falling back from device file to klogctl syscall due to error: {}",
+                    s
+                );
+                klog_entries_from_seq(seq)
+            }
+            Err(e) => Err(e),
+        },
+        Backend::KLogCtl => klog_entries_from_seq(seq),
+        Backend::DevKMsg => Ok(EntriesIterator::DevKMsg(
+            kmsgfile::KMsgEntriesIter::with_seek(None, raw, seq)?,
         )),
     }
 }
 
+fn klog_entries_from_seq(seq: u64) -> Result<EntriesIterator, error::RMesgError> {
+    let mut entries = klog_entries_only_if_timestamp_enabled(false, false, None)?;
+    entries.set_start_sequence_num(seq);
+    Ok(EntriesIterator::KLogCtl(entries))
+}
+
+/// Same as `logs_iter`, but discards entries less severe than `max_level` (that is, with a
+/// numerically greater level: 0 is the most severe). Errors, and entries without a level,
+/// are passed through.
+pub fn logs_iter_filtered(
+    b: Backend,
+    clear: bool,
+    raw: bool,
+    max_level: u8,
+) -> Result<FilteredEntriesIterator, error::RMesgError> {
+    Ok(logs_iter(b, clear, raw)?.filter_entries(at_most_level(max_level)))
+}
+
+fn at_most_level(max_level: u8) -> impl Fn(&entry::Entry) -> bool {
+    move |entry| match entry.level {
+        Some(level) => level as u8 <= max_level,
+        None => true,
+    }
+}
+
+/// When `auto_enable` is set, enables timestamps if they're disabled, rather than giving up.
+/// `time_override` stands in for the printk time parameter, as in `klog_timestamps_enabled_at`.
 fn klog_entries_only_if_timestamp_enabled(
     clear: bool,
+    auto_enable: bool,
+    time_override: Option<&Path>,
 ) -> Result<klogctl::KLogEntries, error::RMesgError> {
-    let log_timestamps_enabled = klogctl::klog_timestamps_enabled()?;
+    let log_timestamps_enabled = klogctl::klog_timestamps_enabled_at(time_override)?;
 
     // ensure timestamps in logs
     if !log_timestamps_enabled {
-        eprintln!("WARNING: Timestamps are disabled but tailing/following logs (as you've requested) requires them.");
-        eprintln!("Aboring program.");
-        eprintln!("You can enable timestamps by running the following: ");
-        eprintln!("  echo Y > /sys/module/printk/parameters/time");
-        return Err(error::RMesgError::KLogTimestampsDisabled);
+        if auto_enable {
+            klogctl::klog_timestamps_enable_at(true, time_override)?;
+        } else {
+            eprintln!("WARNING: Timestamps are disabled but tailing/following logs (as you've requested) requires them.");
+            eprintln!("Aboring program.");
+            eprintln!("You can enable timestamps by running the following: ");
+            eprintln!("  echo Y > /sys/module/printk/parameters/time");
+            return Err(error::RMesgError::KLogTimestampsDisabled);
+        }
     }
 
     klogctl::KLogEntries::with_options(clear, klogctl::SUGGESTED_POLL_INTERVAL)
@@ -113,6 +526,248 @@ fn klog_entries_only_if_timestamp_enabled(
 #[cfg(all(test, target_os = "linux"))]
 mod test {
     use super::*;
+    use crate::common::TempFile;
+    use crate::entry::{Entry, LogLevel};
+    use crate::error::RMesgError;
+
+    fn entries() -> Vec<Result<Entry, RMesgError>> {
+        [
+            Some(LogLevel::Info),
+            Some(LogLevel::Error),
+            None,
+            Some(LogLevel::Emergency),
+        ]
+        .iter()
+        .map(|level| {
+            Ok(Entry::builder()
+                .maybe_level(*level)
+                .message(&format!("{:?}", level))
+                .build())
+        })
+        .chain(std::iter::once(Err(RMesgError::InternalError(
+            "broken".to_owned(),
+        ))))
+        .collect()
+    }
+
+    #[test]
+    fn test_filter_level() {
+        let filtered: Vec<Result<Entry, RMesgError>> = entries()
+            .into_iter()
+            .filter_level(LogLevel::Error)
+            .collect();
+        assert_eq!(filtered.len(), 3);
+        assert_eq!(filtered[0].as_ref().unwrap().level, Some(LogLevel::Error));
+        assert_eq!(
+            filtered[1].as_ref().unwrap().level,
+            Some(LogLevel::Emergency)
+        );
+        assert!(filtered[2].is_err());
+    }
+
+    #[test]
+    fn test_tee_to_file() {
+        let path = TempFile::new("tee");
+
+        let passed_through = entries().into_iter().tee_to_file(&path).unwrap().count();
+        let contents = std::fs::read_to_string(&path).unwrap();
+
+        assert_eq!(passed_through, 5);
+        assert_eq!(contents, "Some(Info)\nSome(Error)\nNone\nSome(Emergency)\n");
+    }
+
+    #[test]
+    fn test_klog_entries_auto_enable() {
+        let time = TempFile::with_contents("auto-enable-time", "N\n");
+
+        assert!(matches!(
+            klog_entries_only_if_timestamp_enabled(false, false, Some(&time)),
+            Err(RMesgError::KLogTimestampsDisabled)
+        ));
+        assert_eq!(std::fs::read_to_string(&time).unwrap(), "N\n");
+
+        assert!(klog_entries_only_if_timestamp_enabled(false, true, Some(&time)).is_ok());
+        assert_eq!(std::fs::read_to_string(&time).unwrap(), "Y\n");
+    }
+
+    #[test]
+    fn test_logs_iter_from_seq_checkpoint() {
+        let mut first_run = logs_iter_from_seq(Backend::KLogCtl, 0, false).unwrap();
+        let read: Vec<Entry> = first_run.by_ref().take(3).map(Result::unwrap).collect();
+        assert_eq!(first_run.last_sequence_num(), Some(2));
+
+        // resume as if the first run had stopped after the second entry (numbered 1)
+        let mut second_run = logs_iter_from_seq(Backend::KLogCtl, 2, false).unwrap();
+        let resumed = second_run.next().unwrap().unwrap();
+        assert_eq!(resumed.sequence_num, Some(2));
+        assert_eq!(resumed.message, read[2].message);
+        assert_eq!(second_run.last_sequence_num(), Some(2));
+    }
+
+    #[test]
+    fn test_count_errors() {
+        let mut iterator = entries().into_iter();
+        assert_eq!(iterator.count_errors(), 1);
+        assert_eq!(iterator.count_errors(), 0);
+    }
+
+    #[test]
+    fn test_filter_entries() {
+        let path = TempFile::with_contents("filter", b"6,1,100,-;usb 1-1: new device\n3,2,200,-;nvme timeout\n6,3,300,-;\xff not utf-8\n6,4,400,-;usb 1-2: new device\n");
+
+        let iterator = EntriesIterator::DevKMsg(
+            kmsgfile::KMsgEntriesIter::with_path(Some(path.as_path()), false).unwrap(),
+        );
+        let filtered: Vec<Result<Entry, RMesgError>> = iterator
+            .filter_entries(|entry| entry.message.starts_with("usb"))
+            .collect();
+
+        assert_eq!(filtered.len(), 3);
+        assert_eq!(filtered[0].as_ref().unwrap().message, "usb 1-1: new device");
+        assert!(filtered[1].is_err());
+        assert_eq!(filtered[2].as_ref().unwrap().message, "usb 1-2: new device");
+    }
+
+    #[test]
+    fn test_from_dyn() {
+        let entries = vec![
+            kmsgfile::entry_from_line("6,1,100,-;first").unwrap(),
+            kmsgfile::entry_from_line("6,2,200,-;second").unwrap(),
+        ];
+
+        let messages: Vec<String> =
+            EntriesIterator::from_dyn(Box::new(entries.into_iter().map(Ok)))
+                .filter_entries(|entry| entry.message != "first")
+                .map(|entry| entry.unwrap().message)
+                .collect();
+        assert_eq!(messages, vec!["second"]);
+    }
+
+    #[test]
+    fn test_at_most_level() {
+        let entries = vec![
+            kmsgfile::entry_from_line("6,1,100,-;info").unwrap(),
+            kmsgfile::entry_from_line("3,2,200,-;error").unwrap(),
+            kmsgfile::entry_from_line(" continuation").unwrap(),
+            kmsgfile::entry_from_line("4,3,300,-;warning").unwrap(),
+        ];
+
+        let messages: Vec<String> =
+            EntriesIterator::from_dyn(Box::new(entries.into_iter().map(Ok)))
+                .filter_entries(at_most_level(4))
+                .map(|entry| entry.unwrap().message)
+                .collect();
+        assert_eq!(messages, vec!["error", " continuation", "warning"]);
+    }
+
+    #[test]
+    fn test_logs_iter_from_seq() {
+        let first = logs_iter(Backend::DevKMsg, false, false)
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+        let seq = first.sequence_num.unwrap() + 1;
+
+        let next = logs_iter_from_seq(Backend::DevKMsg, seq, false)
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(next.sequence_num, Some(seq));
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn test_log_entries_async() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+
+        let entries = runtime.block_on(log_entries_async(Backend::Default, false));
+        assert!(!entries.unwrap().is_empty(), "Should have non-empty logs");
+
+        let raw = runtime.block_on(logs_raw_async(Backend::Default, false));
+        assert!(!raw.unwrap().is_empty(), "Should have non-empty logs");
+
+        let panicked = runtime.block_on(run_blocking::<(), _>(|| panic!("read failed")));
+        assert!(matches!(panicked, Err(RMesgError::TaskPanicked(_))));
+    }
+
+    #[test]
+    fn test_logs_raw_to_writer() {
+        let mut raw = Vec::new();
+        let written = logs_raw_to_writer(Backend::Default, false, &mut raw).unwrap();
+        assert_eq!(written, raw.len() as u64);
+        assert!(written > 0, "Should have non-empty logs");
+    }
+
+    #[test]
+    fn test_skip_errors() {
+        let results = || {
+            vec![
+                kmsgfile::entry_from_line("6,1,100,-;first").map_err(RMesgError::from),
+                Err(RMesgError::EntryParsingError {
+                    message: "bad line".to_owned(),
+                    source: None,
+                }),
+                kmsgfile::entry_from_line("6,2,200,-;second").map_err(RMesgError::from),
+                Err(RMesgError::ReadTimeout),
+                kmsgfile::entry_from_line("6,3,300,-;third").map_err(RMesgError::from),
+            ]
+        };
+
+        let errors = std::cell::RefCell::new(vec![]);
+        let messages: Vec<String> = EntriesIterator::from_dyn(Box::new(results().into_iter()))
+            .skip_errors(|e| errors.borrow_mut().push(e.to_string()))
+            .map(|entry| entry.message)
+            .collect();
+        assert_eq!(messages, vec!["first", "second", "third"]);
+        assert_eq!(errors.borrow().len(), 2);
+        assert!(errors.borrow()[0].contains("bad line"));
+
+        let messages: Vec<String> = EntriesIterator::from_dyn(Box::new(results().into_iter()))
+            .skip_errors_silent()
+            .map(|entry| entry.message)
+            .collect();
+        assert_eq!(messages, vec!["first", "second", "third"]);
+    }
+
+    #[test]
+    fn test_log_entries_n() {
+        let entries = log_entries_n(Backend::Default, false, 5).unwrap();
+        assert!(entries.len() <= 5);
+        assert!(!entries.is_empty(), "Should have non-empty logs");
+
+        let raw = "6,1,100,-;first\n6,2,200,-;second\n6,3,300,-;third\n";
+        let last = last_entries(raw, 2, Backend::DevKMsg).unwrap();
+        let messages: Vec<&str> = last.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(messages, vec!["second", "third"]);
+        assert!(last_entries(raw, 0, Backend::DevKMsg).unwrap().is_empty());
+        assert!(
+            last_entries("6,18446744073709551616,100,-;bad\n", 0, Backend::DevKMsg)
+                .unwrap()
+                .is_empty()
+        );
+
+        // only the lines kept are parsed
+        let raw_with_error =
+            "6,18446744073709551616,100,-;bad\n6,2,200,-;second\n6,3,300,-;third\n";
+        assert_eq!(
+            last_entries(raw_with_error, 2, Backend::DevKMsg)
+                .unwrap()
+                .len(),
+            2
+        );
+        assert!(last_entries(raw_with_error, 3, Backend::DevKMsg).is_err());
+        assert_eq!(
+            last_entries(raw, usize::MAX, Backend::DevKMsg)
+                .unwrap()
+                .len(),
+            3
+        );
+    }
 
     #[test]
     fn test_log_entries() {
@@ -0,0 +1,116 @@
+use crate::cache;
+use crate::entry::Entry;
+use crate::error::RMesgError;
+use crate::EntriesIterator;
+
+use metrics::{counter, describe_counter, describe_histogram, histogram, Unit};
+use std::iter::Iterator;
+use std::time::{Duration, SystemTime};
+
+/// Entries read, labelled with `level` and `facility`
+pub const ENTRIES_TOTAL: &str = "rmesg.entries.total";
+/// Entries that failed to parse (or otherwise failed to be read)
+pub const PARSE_ERRORS_TOTAL: &str = "rmesg.parse_errors.total";
+/// Microseconds between an entry being logged by the kernel, and it being read
+pub const TIMESTAMP_LAG_US: &str = "rmesg.entry.timestamp_lag_us";
+
+/// Describes the metrics recorded by `MetricsEntriesIter` to the installed `metrics` recorder.
+/// Call this once, after installing the recorder.
+pub fn register_metrics() {
+    describe_counter!(ENTRIES_TOTAL, Unit::Count, "Kernel log entries read");
+    describe_counter!(
+        PARSE_ERRORS_TOTAL,
+        Unit::Count,
+        "Kernel log entries that could not be read or parsed"
+    );
+    describe_histogram!(
+        TIMESTAMP_LAG_US,
+        Unit::Microseconds,
+        "Time between the kernel logging an entry and it being read"
+    );
+}
+
+/// Passes entries through unchanged, recording metrics about them as they go by.
+pub struct MetricsEntriesIter {
+    inner: EntriesIterator,
+}
+
+impl EntriesIterator {
+    /// Records metrics about every entry read through this iterator. See `register_metrics`.
+    pub fn with_metrics(self) -> MetricsEntriesIter {
+        MetricsEntriesIter { inner: self }
+    }
+}
+
+impl Iterator for MetricsEntriesIter {
+    type Item = Result<Entry, RMesgError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let next = self.inner.next();
+
+        match &next {
+            Some(Ok(entry)) => record_entry(entry),
+            Some(Err(_)) => counter!(PARSE_ERRORS_TOTAL).increment(1),
+            None => {}
+        }
+
+        next
+    }
+}
+
+fn record_entry(entry: &Entry) {
+    let level = match entry.level {
+        Some(level) => level.to_string(),
+        None => "unknown".to_owned(),
+    };
+    let facility = match entry.facility {
+        Some(facility) => facility.to_string(),
+        None => "unknown".to_owned(),
+    };
+    counter!(ENTRIES_TOTAL, "level" => level, "facility" => facility).increment(1);
+
+    if let Some(lag) = timestamp_lag(entry) {
+        histogram!(TIMESTAMP_LAG_US).record(lag.as_micros() as f64);
+    }
+}
+
+/// How long ago the entry was logged, if it has a timestamp and the boot time is known
+fn timestamp_lag(entry: &Entry) -> Option<Duration> {
+    let logged_at = cache::boot_time_cached()
+        .ok()?
+        .checked_add(entry.timestamp_from_system_start?)?;
+
+    SystemTime::now().duration_since(logged_at).ok()
+}
+
+/**********************************************************************************/
+// Tests! Tests! Tests!
+
+#[cfg(all(test, target_os = "linux"))]
+mod test {
+    use super::*;
+    use crate::common::TempFile;
+    use crate::kmsgfile::KMsgEntriesIter;
+
+    #[test]
+    fn test_with_metrics_passes_entries_through() {
+        let path = TempFile::with_contents("metrics", "6,1,100,-;first\n6,2,200,-;second\n");
+
+        let iterator = EntriesIterator::DevKMsg(
+            KMsgEntriesIter::with_path(Some(path.as_path()), false).unwrap(),
+        );
+        let messages: Vec<String> = iterator
+            .with_metrics()
+            .map(|e| e.unwrap().message)
+            .collect();
+
+        assert_eq!(messages, vec!["first", "second"]);
+    }
+
+    #[test]
+    fn test_timestamp_lag() {
+        let entry = crate::kmsgfile::entry_from_line("6,1,100,-;first").unwrap();
+        let lag = timestamp_lag(&entry).unwrap();
+        assert!(lag > Duration::from_micros(0));
+    }
+}
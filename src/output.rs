@@ -0,0 +1,3 @@
+/// Newline-delimited JSON, one entry per line (`serde` feature)
+#[cfg(feature = "serde")]
+pub mod ndjson;
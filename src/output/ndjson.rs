@@ -0,0 +1,103 @@
+use crate::entry::Entry;
+use crate::error::RMesgError;
+
+use std::io::Write;
+
+/// Writes each entry as a JSON object on a line of its own (newline-delimited JSON, as log
+/// shippers such as Fluent Bit, Logstash and Vector read), and returns how many were written.
+///
+/// Stops at, and returns, the first error the iterator produces (or the first write error).
+pub fn write_entries<W: Write>(
+    entries: impl Iterator<Item = Result<Entry, RMesgError>>,
+    writer: &mut W,
+) -> Result<usize, RMesgError> {
+    let mut written = 0;
+    for entry in entries {
+        write_entry(&entry?, writer)?;
+        written += 1;
+    }
+
+    Ok(written)
+}
+
+/// Same as `write_entries`, for entries already read.
+pub fn write_entries_all<W: Write>(entries: &[Entry], writer: &mut W) -> Result<(), RMesgError> {
+    for entry in entries {
+        write_entry(entry, writer)?;
+    }
+
+    Ok(())
+}
+
+fn write_entry<W: Write>(entry: &Entry, writer: &mut W) -> Result<(), RMesgError> {
+    // Newlines in the message are escaped, so each entry stays on one line
+    serde_json::to_writer(&mut *writer, entry)
+        .map_err(|e| RMesgError::io_error("Unable to write entry as JSON", e))?;
+    writer.write_all(b"\n")?;
+
+    Ok(())
+}
+
+/**********************************************************************************/
+// Tests! Tests! Tests!
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::kmsgfile::entry_from_line;
+
+    fn entries() -> Vec<Entry> {
+        [
+            "6,1,100,-;first",
+            "3,2,200,-,SUBSYSTEM=usb;second, with \"quotes\"",
+            " LINE2=foobar",
+        ]
+        .iter()
+        .map(|line| entry_from_line(line).unwrap())
+        .collect()
+    }
+
+    fn parse_back(output: &[u8]) -> Vec<Entry> {
+        std::str::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_write_entries() {
+        let mut output = Vec::new();
+        let written = write_entries(entries().into_iter().map(Ok), &mut output).unwrap();
+
+        assert_eq!(written, 3);
+        assert_eq!(parse_back(&output), entries());
+    }
+
+    #[test]
+    fn test_write_entries_all() {
+        let mut entry = entry_from_line("6,3,300,-;multi").unwrap();
+        entry.message.push_str("\n SUBSYSTEM=usb");
+        let mut entries = entries();
+        entries.push(entry);
+
+        let mut output = Vec::new();
+        write_entries_all(&entries, &mut output).unwrap();
+
+        assert_eq!(output.iter().filter(|b| **b == b'\n').count(), 4);
+        assert_eq!(parse_back(&output), entries);
+    }
+
+    #[test]
+    fn test_write_entries_stops_at_error() {
+        let results = vec![
+            Ok(entries().remove(0)),
+            Err(RMesgError::InternalError("broken".to_owned())),
+            Ok(entries().remove(1)),
+        ];
+
+        let mut output = Vec::new();
+        assert!(write_entries(results.into_iter(), &mut output).is_err());
+        assert_eq!(parse_back(&output), vec![entries().remove(0)]);
+    }
+}
@@ -0,0 +1,216 @@
+use crate::entry::Entry;
+use crate::error::RMesgError;
+use crate::EntriesIterator;
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+/// Orders entries by severity alone: the lower the level's numeric value, the higher
+/// the priority. Entries without a level come last.
+///
+/// This is kept private to the queue, since ordering entries by severity is not a
+/// natural ordering of entries in general.
+#[derive(Debug, Clone)]
+struct BySeverity(Entry);
+
+impl BySeverity {
+    fn priority(&self) -> u8 {
+        match self.0.level {
+            Some(level) => u8::MAX - level as u8,
+            None => 0,
+        }
+    }
+}
+
+impl PartialEq for BySeverity {
+    fn eq(&self, other: &Self) -> bool {
+        self.priority() == other.priority()
+    }
+}
+
+impl Eq for BySeverity {}
+
+impl PartialOrd for BySeverity {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for BySeverity {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.priority().cmp(&other.priority())
+    }
+}
+
+/// A bounded queue that hands out the most severe entry first.
+///
+/// When the queue is full, pushing an entry evicts the least severe entry held
+/// (or drops the pushed entry, if nothing held is less severe). Entries of the same
+/// severity come out in no particular order.
+#[derive(Debug, Clone)]
+pub struct PriorityQueue {
+    heap: BinaryHeap<BySeverity>,
+    pub max_size: usize,
+}
+
+impl PriorityQueue {
+    pub fn new(max_size: usize) -> PriorityQueue {
+        PriorityQueue {
+            heap: BinaryHeap::with_capacity(max_size),
+            max_size,
+        }
+    }
+
+    /// Adds an entry, evicting the least severe entry if the queue is full.
+    ///
+    /// Eviction scans the whole queue, so a push into a full queue is O(max_size).
+    pub fn push(&mut self, entry: Entry) {
+        let entry = BySeverity(entry);
+
+        if self.heap.len() < self.max_size {
+            self.heap.push(entry);
+            return;
+        }
+
+        let mut entries = std::mem::take(&mut self.heap).into_vec();
+        if let Some((lowest, _)) = entries.iter().enumerate().min_by_key(|(_, e)| *e) {
+            if entries[lowest] < entry {
+                entries[lowest] = entry;
+            }
+        }
+        self.heap = BinaryHeap::from(entries);
+    }
+
+    /// Removes and returns the most severe entry.
+    pub fn pop(&mut self) -> Option<Entry> {
+        self.heap.pop().map(|e| e.0)
+    }
+
+    pub fn len(&self) -> usize {
+        self.heap.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
+}
+
+impl EntriesIterator {
+    /// Drains the iterator into a PriorityQueue holding at most `max_size` entries.
+    ///
+    /// Stops at, and returns, the first error the iterator produces. Both backends wait
+    /// for new entries once they've read the buffer, so this only returns on error.
+    pub fn into_priority_queue(self, max_size: usize) -> Result<PriorityQueue, RMesgError> {
+        let mut queue = PriorityQueue::new(max_size);
+        for entry in self {
+            queue.push(entry?);
+        }
+
+        Ok(queue)
+    }
+}
+
+/**********************************************************************************/
+// Tests! Tests! Tests!
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::entry::LogLevel;
+
+    #[test]
+    fn test_pops_most_severe_first() {
+        let mut queue = PriorityQueue::new(10);
+        queue.push(
+            Entry::builder()
+                .level(LogLevel::Info)
+                .message("info")
+                .build(),
+        );
+        queue.push(Entry::builder().message(" LINE2=continuation").build());
+        queue.push(
+            Entry::builder()
+                .level(LogLevel::Emergency)
+                .message("emergency")
+                .build(),
+        );
+        queue.push(
+            Entry::builder()
+                .level(LogLevel::Warning)
+                .message("warning")
+                .build(),
+        );
+        queue.push(
+            Entry::builder()
+                .level(LogLevel::Debug)
+                .message("debug")
+                .build(),
+        );
+
+        let popped: Vec<String> = std::iter::from_fn(|| queue.pop())
+            .map(|e| e.message)
+            .collect();
+        assert_eq!(
+            popped,
+            vec![
+                "emergency",
+                "warning",
+                "info",
+                "debug",
+                " LINE2=continuation"
+            ]
+        );
+        assert!(queue.is_empty());
+    }
+
+    #[test]
+    fn test_evicts_least_severe_when_full() {
+        let mut queue = PriorityQueue::new(2);
+        queue.push(
+            Entry::builder()
+                .level(LogLevel::Info)
+                .message("info")
+                .build(),
+        );
+        queue.push(
+            Entry::builder()
+                .level(LogLevel::Error)
+                .message("error")
+                .build(),
+        );
+
+        // Less severe than anything held, so it's dropped
+        queue.push(
+            Entry::builder()
+                .level(LogLevel::Debug)
+                .message("debug")
+                .build(),
+        );
+        assert_eq!(queue.len(), 2);
+
+        // More severe than the Info entry, which is evicted
+        queue.push(
+            Entry::builder()
+                .level(LogLevel::Critical)
+                .message("critical")
+                .build(),
+        );
+        assert_eq!(queue.len(), 2);
+
+        assert_eq!(queue.pop().unwrap().message, "critical");
+        assert_eq!(queue.pop().unwrap().message, "error");
+        assert_eq!(queue.pop(), None);
+    }
+
+    #[test]
+    fn test_zero_size_queue_holds_nothing() {
+        let mut queue = PriorityQueue::new(0);
+        queue.push(
+            Entry::builder()
+                .level(LogLevel::Emergency)
+                .message("emergency")
+                .build(),
+        );
+        assert!(queue.is_empty());
+    }
+}
@@ -0,0 +1,186 @@
+use crate::entry::Entry;
+use crate::error::RMesgError;
+use crate::EntriesIterator;
+
+use regex::{NoExpand, Regex};
+use std::iter::Iterator;
+
+/// Masks sensitive information in entries, such as before sharing them in a bug report.
+///
+/// Each pattern's matches are replaced by its substitution (taken literally), in order, in
+/// the message, the raw flags and the dict values of the entry.
+#[derive(Debug, Clone)]
+pub struct Redactor {
+    pub patterns: Vec<(Regex, String)>,
+}
+
+impl Redactor {
+    /// Redacts UUIDs, MAC addresses, IPv6 and IPv4 addresses, and serial numbers (runs of at
+    /// least 8 uppercase letters and digits).
+    ///
+    /// ```
+    /// use rmesg::kmsgfile::entry_from_line;
+    /// use rmesg::redact::Redactor;
+    ///
+    /// let mut entry = entry_from_line("6,1,100,-;eth0: 10.0.0.12 from 00:1a:2b:3c:4d:5e").unwrap();
+    /// Redactor::standard().apply(&mut entry);
+    /// assert_eq!(entry.message, "eth0: [REDACTED_IP] from [REDACTED_MAC]");
+    /// ```
+    pub fn standard() -> Self {
+        // UUIDs and MAC addresses come before the patterns that would match parts of them
+        let patterns = [
+            (
+                r"(?i)\b[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12}\b",
+                "[REDACTED_UUID]",
+            ),
+            (r"(?i)\b[0-9a-f]{2}(?::[0-9a-f]{2}){5}\b", "[REDACTED_MAC]"),
+            (
+                r"(?i)\b(?:[0-9a-f]{1,4}:){7}[0-9a-f]{1,4}\b|\b(?:[0-9a-f]{1,4}:){1,7}:(?:[0-9a-f]{1,4}(?::[0-9a-f]{1,4}){0,6}\b)?",
+                "[REDACTED_IP]",
+            ),
+            (r"\b(?:\d{1,3}\.){3}\d{1,3}\b", "[REDACTED_IP]"),
+            (r"\b[A-Z0-9]{8,}\b", "[REDACTED_SERIAL]"),
+        ];
+
+        Redactor {
+            patterns: patterns
+                .iter()
+                .map(|(pattern, substitution)| {
+                    (
+                        Regex::new(pattern).expect("Standard redaction patterns are valid"),
+                        substitution.to_string(),
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    pub fn apply(&self, entry: &mut Entry) {
+        self.redact(&mut entry.message);
+        if let Some(raw_flags) = &mut entry.raw_flags {
+            self.redact(raw_flags);
+        }
+        for value in entry.dict.values_mut() {
+            self.redact(value);
+        }
+    }
+
+    fn redact(&self, s: &mut String) {
+        for (re, substitution) in &self.patterns {
+            if re.is_match(s) {
+                *s = re.replace_all(s, NoExpand(substitution)).into_owned();
+            }
+        }
+    }
+}
+
+/// See `EntriesIterator::redacted`.
+pub struct RedactedEntriesIter {
+    inner: EntriesIterator,
+    redactor: Redactor,
+}
+
+impl EntriesIterator {
+    /// Redacts each entry with `redactor` as it's read. Errors are passed through.
+    pub fn redacted(self, redactor: Redactor) -> RedactedEntriesIter {
+        RedactedEntriesIter {
+            inner: self,
+            redactor,
+        }
+    }
+}
+
+impl Iterator for RedactedEntriesIter {
+    type Item = Result<Entry, RMesgError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|result| {
+            result.map(|mut entry| {
+                self.redactor.apply(&mut entry);
+                entry
+            })
+        })
+    }
+}
+
+/**********************************************************************************/
+// Tests! Tests! Tests!
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::kmsgfile::entry_from_line;
+
+    fn redacted(message: &str) -> String {
+        let mut entry = Entry::builder().message(message).build();
+        Redactor::standard().apply(&mut entry);
+        entry.message
+    }
+
+    #[test]
+    fn test_standard() {
+        assert_eq!(
+            redacted("IPv4: martian source 192.168.1.255 from 10.0.0.1, on dev eth0"),
+            "IPv4: martian source [REDACTED_IP] from [REDACTED_IP], on dev eth0"
+        );
+        assert_eq!(
+            redacted("IPv6: eth0: link becomes ready: fe80::1c2b:3dff:fe4e:5f60 and 2001:db8:0:0:0:0:2:1 up"),
+            "IPv6: eth0: link becomes ready: [REDACTED_IP] and [REDACTED_IP] up"
+        );
+        assert_eq!(
+            redacted("e1000e 0000:00:19.0 eth0: (PCI Express:2.5GT/s:Width x1) 00:1a:2b:3c:4d:5e"),
+            "e1000e 0000:00:19.0 eth0: (PCI Express:2.5GT/s:Width x1) [REDACTED_MAC]"
+        );
+        assert_eq!(
+            redacted("usb 1-1: SerialNumber: 4C530001230812116383"),
+            "usb 1-1: SerialNumber: [REDACTED_SERIAL]"
+        );
+        assert_eq!(
+            redacted("EXT4-fs (sda1): mounted filesystem 3e6b8c1a-9f4d-4b2e-8a7c-1d2e3f4a5b6c r/w"),
+            "EXT4-fs (sda1): mounted filesystem [REDACTED_UUID] r/w"
+        );
+
+        // Nothing sensitive, nothing redacted
+        let message = "docker0: port 2(veth98d5024) entered disabled state";
+        assert_eq!(redacted(message), message);
+    }
+
+    #[test]
+    fn test_apply_redacts_flags() {
+        let mut entry =
+            entry_from_line("6,1,100,-,SUBSYSTEM=usb,DEVICE=+usb:4C530001230812116383;usb ready")
+                .unwrap();
+        Redactor::standard().apply(&mut entry);
+
+        assert_eq!(entry.dict["DEVICE"], "+usb:[REDACTED_SERIAL]");
+        assert!(!entry.raw_flags.unwrap().contains("4C530001230812116383"));
+    }
+
+    #[test]
+    fn test_custom_substitution_taken_literally() {
+        let redactor = Redactor {
+            patterns: vec![(Regex::new(r"pid \d+").unwrap(), "pid $0".to_owned())],
+        };
+        let mut entry = Entry::builder().message("killed pid 4054").build();
+        redactor.apply(&mut entry);
+        assert_eq!(entry.message, "killed pid $0");
+    }
+
+    #[test]
+    fn test_redacted() {
+        let entries = vec![
+            Ok(Entry::builder().message("link up on 10.1.2.3").build()),
+            Err(RMesgError::InternalError("broken".to_owned())),
+        ];
+
+        let redacted: Vec<Result<Entry, RMesgError>> =
+            EntriesIterator::from_dyn(Box::new(entries.into_iter()))
+                .redacted(Redactor::standard())
+                .collect();
+        assert_eq!(
+            redacted[0].as_ref().unwrap().message,
+            "link up on [REDACTED_IP]"
+        );
+        assert!(redacted[1].is_err());
+    }
+}
@@ -0,0 +1,98 @@
+use crate::entry::Entry;
+use crate::error::RMesgError;
+
+use std::iter::Iterator;
+
+/// Feeds a sequence of entries through a handler that updates some state,
+/// and returns the state once every entry is consumed.
+///
+/// This is the fold/reduce pattern given a name of its own, which is useful when
+/// simulating a state machine driven by kernel log entries: the same entries replayed
+/// through the same handler always produce the same final state.
+///
+pub struct EntryReplayer<S, F>
+where
+    F: FnMut(&mut S, &Entry),
+{
+    pub state: S,
+    pub handler: F,
+}
+
+impl<S, F> EntryReplayer<S, F>
+where
+    F: FnMut(&mut S, &Entry),
+{
+    /// Create a new EntryReplayer starting from `state`, which `handler` updates for each entry.
+    pub fn new(state: S, handler: F) -> EntryReplayer<S, F> {
+        EntryReplayer { state, handler }
+    }
+
+    /// Feeds every entry from `iter` to the handler, in order, and returns the final state.
+    ///
+    /// Stops at, and returns, the first error the iterator produces.
+    pub fn replay<I>(mut self, iter: I) -> Result<S, RMesgError>
+    where
+        I: Iterator<Item = Result<Entry, RMesgError>>,
+    {
+        for entry in iter {
+            (self.handler)(&mut self.state, &entry?);
+        }
+
+        Ok(self.state)
+    }
+}
+
+/**********************************************************************************/
+// Tests! Tests! Tests!
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_count_oom_kills_by_pid() {
+        let entries = vec![
+            Ok(Entry::builder()
+                .message("Out of memory: Killed process 4054 (a.out)")
+                .build()),
+            Ok(Entry::builder()
+                .message("docker0: port 2(veth98d5024) entered disabled state")
+                .build()),
+            Ok(Entry::builder()
+                .message("Out of memory: Killed process 4054 (a.out)")
+                .build()),
+            Ok(Entry::builder()
+                .message("Out of memory: Killed process 17 (b.out)")
+                .build()),
+        ];
+
+        let replayer = EntryReplayer::new(
+            HashMap::<u32, u32>::new(),
+            |oom_kills: &mut HashMap<u32, u32>, entry: &Entry| {
+                if let Some(rest) = entry.message.strip_prefix("Out of memory: Killed process ") {
+                    if let Some(pid) = rest.split_whitespace().next() {
+                        *oom_kills.entry(pid.parse().unwrap()).or_insert(0) += 1;
+                    }
+                }
+            },
+        );
+
+        let oom_kills = replayer.replay(entries.into_iter()).unwrap();
+        assert_eq!(oom_kills.len(), 2);
+        assert_eq!(oom_kills[&4054], 2);
+        assert_eq!(oom_kills[&17], 1);
+    }
+
+    #[test]
+    fn test_replay_stops_at_error() {
+        let entries = vec![
+            Ok(Entry::builder().message("first").build()),
+            Err(RMesgError::InternalError("broken".to_owned())),
+            Ok(Entry::builder().message("never seen").build()),
+        ];
+
+        let replayer = EntryReplayer::new(0usize, |count: &mut usize, _: &Entry| *count += 1);
+        assert!(replayer.replay(entries.into_iter()).is_err());
+    }
+}
@@ -0,0 +1,238 @@
+use crate::entry::{Entry, LogFacility, LogLevel};
+
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+/// Summary statistics over a collection of entries, see `collect`.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct EntryStats {
+    pub total: usize,
+    /// Entries per level value, from 0 (Emergency) to 7 (Debug). Entries without a level aren't counted.
+    pub by_level: HashMap<u8, usize>,
+    /// Entries per facility value, from 0 (kern) to 255. Entries without a facility aren't counted.
+    pub by_facility: HashMap<u8, usize>,
+    /// The first and last sequence number missing from each gap, see `count_sequence_gaps`.
+    pub sequence_gaps: Vec<(u64, u64)>,
+    /// In bytes
+    pub max_message_len: usize,
+    /// The total divided by the time between the first and last timestamped entries,
+    /// or `None` without two distinct timestamps to go by.
+    pub entries_per_second: Option<f64>,
+}
+
+/// Gathers EntryStats over the entries, in the order given (which matters for sequence gaps).
+pub fn collect(entries: &[Entry]) -> EntryStats {
+    let mut stats = EntryStats {
+        total: entries.len(),
+        sequence_gaps: sequence_gaps(entries),
+        ..EntryStats::default()
+    };
+
+    for entry in entries {
+        if let Some(level) = entry.level {
+            *stats.by_level.entry(level.as_u8()).or_insert(0) += 1;
+        }
+        if let Some(facility) = entry.facility {
+            *stats.by_facility.entry(facility.as_u8()).or_insert(0) += 1;
+        }
+        stats.max_message_len = stats.max_message_len.max(entry.message.len());
+    }
+
+    let first = entries.iter().find_map(|e| e.timestamp_from_system_start);
+    let last = entries
+        .iter()
+        .rev()
+        .find_map(|e| e.timestamp_from_system_start);
+    if let (Some(first), Some(last)) = (first, last) {
+        if last > first {
+            stats.entries_per_second = Some(entries.len() as f64 / (last - first).as_secs_f64());
+        }
+    }
+
+    stats
+}
+
+/// A table of the statistics, with one row per level and facility seen.
+impl Display for EntryStats {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        let missing: u64 = self
+            .sequence_gaps
+            .iter()
+            .map(|(first, last)| last - first + 1)
+            .sum();
+
+        writeln!(f, "{:<20} {}", "Entries", self.total)?;
+        writeln!(f, "{:<20} {}", "Max message length", self.max_message_len)?;
+        match self.entries_per_second {
+            Some(rate) => writeln!(f, "{:<20} {:.2}", "Entries per second", rate)?,
+            None => writeln!(f, "{:<20} -", "Entries per second")?,
+        }
+        writeln!(
+            f,
+            "{:<20} {} ({} entries missing)",
+            "Sequence gaps",
+            self.sequence_gaps.len(),
+            missing
+        )?;
+
+        writeln!(f, "\n{:<20} Entries", "Level")?;
+        for (level, count) in sorted(&self.by_level) {
+            let name = LogLevel::from_u8(level).map_or(level.to_string(), |l| l.to_string());
+            writeln!(f, "{:<20} {}", name, count)?;
+        }
+
+        writeln!(f, "\n{:<20} Entries", "Facility")?;
+        for (facility, count) in sorted(&self.by_facility) {
+            writeln!(f, "{:<20} {}", LogFacility::from_u8(facility), count)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn sorted(counts: &HashMap<u8, usize>) -> Vec<(u8, usize)> {
+    let mut sorted: Vec<(u8, usize)> = counts.iter().map(|(k, v)| (*k, *v)).collect();
+    sorted.sort_unstable();
+    sorted
+}
+
+// The first and last sequence number missing from each gap between consecutive entries
+fn sequence_gaps(entries: &[Entry]) -> Vec<(u64, u64)> {
+    let mut gaps = Vec::new();
+    let mut previous: Option<u64> = None;
+
+    for sequence_num in entries.iter().filter_map(|e| e.sequence_num) {
+        if let Some(previous) = previous {
+            if sequence_num > previous + 1 {
+                gaps.push((previous + 1, sequence_num - 1));
+            }
+        }
+
+        previous = Some(sequence_num);
+    }
+
+    gaps
+}
+
+/// Counts the gaps in sequence numbers between consecutive entries (in the order given, which
+/// should be the order they were read from /dev/kmsg), returning the number of gaps and the total
+/// number of entries missing from them. Entries the reader never saw - because the ring buffer
+/// overflowed and they were overwritten before being read - show up as such gaps.
+///
+/// Entries without sequence numbers are skipped. A sequence number that goes backwards
+/// (such as after seeking to the start of the buffer) is not a gap.
+pub fn count_sequence_gaps(entries: &[Entry]) -> (u64, u64) {
+    let gaps = sequence_gaps(entries);
+    let total_missing = gaps.iter().map(|(first, last)| last - first + 1).sum();
+
+    (gaps.len() as u64, total_missing)
+}
+
+/// Estimated memory taken up by the entries, see `Entry::estimated_size_bytes`.
+pub fn total_memory_bytes(entries: &[Entry]) -> usize {
+    entries.iter().map(Entry::estimated_size_bytes).sum()
+}
+
+/**********************************************************************************/
+// Tests! Tests! Tests!
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_count_sequence_gaps() {
+        assert_eq!(count_sequence_gaps(&[]), (0, 0));
+
+        let contiguous: Vec<Entry> = [Some(5), None, Some(6), Some(7)]
+            .iter()
+            .map(|&n| Entry::builder().maybe_sequence_num(n).build())
+            .collect();
+        assert_eq!(count_sequence_gaps(&contiguous), (0, 0));
+
+        let gappy: Vec<Entry> = [Some(5), Some(7), None, Some(8), Some(20)]
+            .iter()
+            .map(|&n| Entry::builder().maybe_sequence_num(n).build())
+            .collect();
+        assert_eq!(count_sequence_gaps(&gappy), (2, 12));
+
+        let restarted: Vec<Entry> = [Some(100), Some(0), Some(1)]
+            .iter()
+            .map(|&n| Entry::builder().maybe_sequence_num(n).build())
+            .collect();
+        assert_eq!(count_sequence_gaps(&restarted), (0, 0));
+    }
+
+    #[test]
+    fn test_collect() {
+        let entry = |level: LogLevel, seq: u64, secs: u64, message: &str| {
+            Entry::builder()
+                .facility(LogFacility::Kern)
+                .level(level)
+                .sequence_num(seq)
+                .timestamp(Duration::from_secs(secs))
+                .message(message)
+                .build()
+        };
+        let entries = vec![
+            entry(LogLevel::Info, 1, 10, "first"),
+            entry(LogLevel::Info, 2, 11, "second"),
+            Entry::builder().message(" LINE2=continuation").build(),
+            entry(LogLevel::Error, 5, 12, "the longest message of them all"),
+            entry(LogLevel::Warning, 6, 14, "last"),
+        ];
+
+        let stats = collect(&entries);
+        assert_eq!(stats.total, 5);
+        assert_eq!(stats.by_level.get(&(LogLevel::Info as u8)), Some(&2));
+        assert_eq!(stats.by_level.get(&(LogLevel::Error as u8)), Some(&1));
+        assert_eq!(stats.by_level.values().sum::<usize>(), 4);
+        assert_eq!(
+            stats.by_facility.get(&(LogFacility::Kern.as_u8())),
+            Some(&4)
+        );
+        assert_eq!(stats.sequence_gaps, vec![(3, 4)]);
+        assert_eq!(
+            stats.max_message_len,
+            "the longest message of them all".len()
+        );
+        assert_eq!(stats.entries_per_second, Some(5.0 / 4.0));
+
+        let table = stats.to_string();
+        assert!(table.contains("Entries              5\n"));
+        assert!(table.contains("Entries per second   1.25\n"));
+        assert!(table.contains("Sequence gaps        1 (2 entries missing)\n"));
+        assert!(table.contains("err                  1\n"));
+        assert!(table.contains("kern                 4\n"));
+
+        // No rate without two distinct timestamps
+        assert_eq!(collect(&entries[..1]).entries_per_second, None);
+        assert_eq!(collect(&[]), EntryStats::default());
+    }
+
+    #[test]
+    fn test_total_memory_bytes() {
+        assert_eq!(total_memory_bytes(&[]), 0);
+
+        let entries = vec![
+            Entry::builder().sequence_num(1).message("message").build(),
+            Entry::builder().message("message").build(),
+        ];
+        assert_eq!(
+            total_memory_bytes(&entries),
+            2 * (std::mem::size_of::<Entry>() + "message".len())
+        );
+
+        // The flags and dict count too
+        let with_dict: Entry = "6,1,100,-,SUBSYSTEM=usb;message".parse().unwrap();
+        assert_eq!(
+            total_memory_bytes(&[with_dict]),
+            std::mem::size_of::<Entry>()
+                + "message".len()
+                + "-,SUBSYSTEM=usb".len()
+                + "SUBSYSTEM".len()
+                + "usb".len()
+        );
+    }
+}
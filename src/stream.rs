@@ -0,0 +1,123 @@
+use crate::entry::Entry;
+use crate::error::RMesgError;
+use crate::{logs_iter, Backend, EntriesIterator};
+
+use futures_core::Stream;
+use std::future::Future;
+use std::iter::Iterator;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::task::{self, JoinHandle};
+
+type Reading = JoinHandle<(EntriesIterator, Option<Result<Entry, RMesgError>>)>;
+
+enum StreamState {
+    Idle(Box<EntriesIterator>),
+    Reading(Reading),
+    Done,
+}
+
+/// Streams entries from an EntriesIterator, reading each one on tokio's blocking thread pool
+/// (see `tokio::task::spawn_blocking`), so waiting for new entries never blocks the executor.
+/// A read that panics is returned as `RMesgError::TaskPanicked`.
+///
+/// IMPORTANT NOTE: It must be polled from within a tokio runtime.
+///
+/// This is a separate type, rather than `Stream` implemented for `EntriesIterator`, since the
+/// iterator is moved onto the blocking thread pool during each read. Keeping the read in flight
+/// in EntriesIterator would take a variant only the `tokio` feature has, and a feature adding
+/// an enum variant breaks exhaustive matches in crates that don't enable it.
+pub struct EntriesStream {
+    state: StreamState,
+}
+
+impl EntriesIterator {
+    pub fn into_stream(self) -> EntriesStream {
+        EntriesStream {
+            state: StreamState::Idle(Box::new(self)),
+        }
+    }
+}
+
+impl Stream for EntriesStream {
+    type Item = Result<Entry, RMesgError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            match std::mem::replace(&mut self.state, StreamState::Done) {
+                StreamState::Idle(mut iter) => {
+                    self.state = StreamState::Reading(task::spawn_blocking(move || {
+                        let next = iter.next();
+                        (*iter, next)
+                    }));
+                }
+                StreamState::Reading(mut reading) => {
+                    return match Pin::new(&mut reading).poll(cx) {
+                        Poll::Pending => {
+                            self.state = StreamState::Reading(reading);
+                            Poll::Pending
+                        }
+                        Poll::Ready(Ok((iter, next))) => {
+                            if next.is_some() {
+                                self.state = StreamState::Idle(Box::new(iter));
+                            }
+                            Poll::Ready(next)
+                        }
+                        Poll::Ready(Err(e)) => {
+                            Poll::Ready(Some(Err(RMesgError::TaskPanicked(e.to_string()))))
+                        }
+                    };
+                }
+                StreamState::Done => return Poll::Ready(None),
+            }
+        }
+    }
+}
+
+/// Same as `logs_iter`, but streams the entries. Opening the backend happens on tokio's
+/// blocking thread pool too.
+pub async fn logs_stream(b: Backend, clear: bool, raw: bool) -> Result<EntriesStream, RMesgError> {
+    Ok(crate::run_blocking(move || logs_iter(b, clear, raw))
+        .await?
+        .into_stream())
+}
+
+/**********************************************************************************/
+// Tests! Tests! Tests!
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::future;
+    use std::thread;
+    use tokio::sync::mpsc;
+
+    #[test]
+    fn test_into_stream() {
+        let (tx, rx) = mpsc::channel(1);
+        let sender = thread::spawn(move || {
+            for message in ["first", "second"] {
+                let entry = Entry::builder().message(message).build();
+                tx.blocking_send(entry).unwrap();
+            }
+        });
+
+        let mut stream = EntriesIterator::from_mpsc_receiver(rx).into_stream();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+
+        let messages = runtime.block_on(async {
+            let mut messages = vec![];
+            while let Some(entry) = future::poll_fn(|cx| Pin::new(&mut stream).poll_next(cx)).await
+            {
+                messages.push(entry.unwrap().message);
+            }
+            messages
+        });
+        assert_eq!(messages, vec!["first", "second"]);
+
+        sender.join().unwrap();
+    }
+}
@@ -0,0 +1,59 @@
+use crate::error::RMesgError;
+use crate::EntriesIterator;
+
+use sd_notify::NotifyState;
+use std::iter::Peekable;
+
+/// Tells systemd the service is ready (`READY=1`, for `Type=notify` services) once the first
+/// entry has been read successfully from `iter`.
+///
+/// The first entry is only peeked at, so it is still the next one `iter` returns. When reading
+/// it fails, the error is taken off the iterator and returned, and systemd isn't notified.
+/// Not running under systemd is not an error: there is nobody to notify.
+pub fn notify_ready_after_first_entry(
+    iter: &mut Peekable<EntriesIterator>,
+) -> Result<(), RMesgError> {
+    match iter.peek() {
+        Some(Ok(_)) => {}
+        Some(Err(_)) => {
+            if let Some(Err(e)) = iter.next() {
+                return Err(e);
+            }
+        }
+        None => {
+            return Err(RMesgError::InternalError(
+                "No entries to read before notifying systemd".to_owned(),
+            ))
+        }
+    }
+
+    sd_notify::notify(false, &[NotifyState::Ready])?;
+    Ok(())
+}
+
+/**********************************************************************************/
+// Tests! Tests! Tests!
+
+#[cfg(all(test, target_os = "linux"))]
+mod test {
+    use super::*;
+    use crate::common::TempFile;
+    use crate::kmsgfile::KMsgEntriesIter;
+
+    #[test]
+    fn test_first_entry_is_kept() {
+        let path = TempFile::with_contents("systemd", "6,1,100,-;first\n");
+
+        let mut iterator = EntriesIterator::DevKMsg(
+            KMsgEntriesIter::with_path(Some(path.as_path()), false).unwrap(),
+        )
+        .peekable();
+
+        // Not running under systemd in tests, so this notifies nobody
+        assert!(notify_ready_after_first_entry(&mut iterator).is_ok());
+        assert_eq!(iterator.next().unwrap().unwrap().message, "first");
+
+        // Nothing left to wait for
+        assert!(notify_ready_after_first_entry(&mut iterator).is_err());
+    }
+}
@@ -0,0 +1,297 @@
+use crate::entry::{classify, Entry, EntryCategory, LogFacility, LogLevel};
+use crate::error::RMesgError;
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::time::Duration;
+
+// Every record is laid out as follows (all integers little-endian):
+//
+//   4 bytes  total record length in bytes (u32, includes this prefix)
+//   1 byte   level (u8, NONE_U8 when absent)
+//   1 byte   facility (u8, NONE_U8 when absent)
+//   8 bytes  sequence number (u64, NONE_U64 when absent)
+//   8 bytes  timestamp from system start in microseconds (u64, NONE_U64 when absent)
+//   4 bytes  message length in bytes (u32)
+//   n bytes  message (UTF-8)
+//
+// The category isn't encoded: decoding classifies the message again.
+
+/// Marker for an absent level or facility
+pub const NONE_U8: u8 = u8::MAX;
+
+/// Marker for an absent sequence number or timestamp
+pub const NONE_U64: u64 = u64::MAX;
+
+/// Size of the fixed-width portion of a record (everything before the message bytes)
+pub const HEADER_LEN: usize = 4 + 1 + 1 + 8 + 8 + 4;
+
+/// Appends the binary encoding of `entry` to `buf`.
+///
+/// Returns `RMesgError::WireFormatError`, leaving `buf` as it was, when the entry doesn't fit
+/// the format: a record over 4 GiB, facility 255 (`NONE_U8`), or a sequence number or timestamp
+/// (in microseconds) that doesn't fit below `NONE_U64`.
+pub fn encode(entry: &Entry, buf: &mut Vec<u8>) -> Result<(), RMesgError> {
+    let message = entry.message.as_bytes();
+    let total_len = u32::try_from(HEADER_LEN + message.len()).map_err(|_| {
+        RMesgError::WireFormatError(format!(
+            "Message of {} bytes is too long for a record",
+            message.len()
+        ))
+    })?;
+
+    let facility = match entry.facility {
+        Some(facility) if facility.as_u8() == NONE_U8 => {
+            return Err(RMesgError::WireFormatError(format!(
+                "Facility {} can't be encoded",
+                NONE_U8
+            )))
+        }
+        Some(facility) => facility.as_u8(),
+        None => NONE_U8,
+    };
+
+    let sequence_num = match entry.sequence_num {
+        Some(NONE_U64) => {
+            return Err(RMesgError::WireFormatError(format!(
+                "Sequence number {} can't be encoded",
+                NONE_U64
+            )))
+        }
+        Some(s) => s,
+        None => NONE_U64,
+    };
+
+    let timestamp = match entry.timestamp_from_system_start {
+        Some(ts) => match u64::try_from(ts.as_micros()) {
+            Ok(micros) if micros != NONE_U64 => micros,
+            _ => {
+                return Err(RMesgError::WireFormatError(format!(
+                    "Timestamp {:?} can't be encoded",
+                    ts
+                )))
+            }
+        },
+        None => NONE_U64,
+    };
+
+    buf.reserve(total_len as usize);
+    buf.extend_from_slice(&total_len.to_le_bytes());
+    buf.push(entry.level.map_or(NONE_U8, |l| l as u8));
+    buf.push(facility);
+    buf.extend_from_slice(&sequence_num.to_le_bytes());
+    buf.extend_from_slice(&timestamp.to_le_bytes());
+    // Can't overflow, since the whole record fits in a u32
+    buf.extend_from_slice(&(message.len() as u32).to_le_bytes());
+    buf.extend_from_slice(message);
+
+    Ok(())
+}
+
+/// Decodes one entry from the front of `buf`.
+///
+/// Returns the entry along with the number of bytes consumed, so that a stream carrying
+/// several back-to-back records can be demultiplexed by advancing past each one in turn.
+pub fn decode(buf: &[u8]) -> Result<(Entry, usize), RMesgError> {
+    if buf.len() < HEADER_LEN {
+        return Err(RMesgError::WireFormatError(format!(
+            "Buffer of {} bytes is shorter than the {} byte record header",
+            buf.len(),
+            HEADER_LEN
+        )));
+    }
+
+    let total_len = read_u32(buf, 0) as usize;
+    let message_len = read_u32(buf, 22) as usize;
+
+    if total_len != HEADER_LEN + message_len {
+        return Err(RMesgError::WireFormatError(format!(
+            "Record length {} does not match header length {} plus message length {}",
+            total_len, HEADER_LEN, message_len
+        )));
+    }
+
+    if buf.len() < total_len {
+        return Err(RMesgError::WireFormatError(format!(
+            "Buffer of {} bytes is shorter than the {} byte record",
+            buf.len(),
+            total_len
+        )));
+    }
+
+    let level = match buf[4] {
+        NONE_U8 => None,
+        l => Some(
+            LogLevel::from_u8(l)
+                .ok_or_else(|| RMesgError::WireFormatError(format!("Invalid log level {}", l)))?,
+        ),
+    };
+
+    let facility = match buf[5] {
+        NONE_U8 => None,
+        f => Some(LogFacility::from_u8(f)),
+    };
+
+    let sequence_num = match read_u64(buf, 6) {
+        NONE_U64 => None,
+        s => Some(s),
+    };
+
+    let timestamp_from_system_start = match read_u64(buf, 14) {
+        NONE_U64 => None,
+        ts => Some(Duration::from_micros(ts)),
+    };
+
+    let message = String::from_utf8(buf[HEADER_LEN..total_len].to_vec())?;
+
+    let mut entry = Entry {
+        facility,
+        level,
+        sequence_num,
+        timestamp_from_system_start,
+        message,
+        category: EntryCategory::Normal,
+        // The flags aren't encoded
+        raw_flags: None,
+        dict: HashMap::new(),
+        source_backend: None,
+    };
+    entry.category = classify(&entry);
+
+    Ok((entry, total_len))
+}
+
+fn read_u32(buf: &[u8], offset: usize) -> u32 {
+    let mut bytes = [0u8; 4];
+    bytes.copy_from_slice(&buf[offset..offset + 4]);
+    u32::from_le_bytes(bytes)
+}
+
+fn read_u64(buf: &[u8], offset: usize) -> u64 {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&buf[offset..offset + 8]);
+    u64::from_le_bytes(bytes)
+}
+
+/**********************************************************************************/
+// Tests! Tests! Tests!
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::entry::classify_message;
+
+    #[test]
+    fn test_round_trip() {
+        let message = "Out of memory: Killed process 4054 (a.out)";
+        let entry = Entry::builder()
+            .timestamp(Duration::from_micros(24241325252))
+            .facility(LogFacility::Kern)
+            .level(LogLevel::Info)
+            .sequence_num(10)
+            .message(message)
+            .category(classify_message(message))
+            .build();
+
+        let mut buf = Vec::new();
+        encode(&entry, &mut buf).unwrap();
+        assert_eq!(buf.len(), HEADER_LEN + entry.message.len());
+
+        let (decoded, consumed) = decode(&buf).unwrap();
+        assert_eq!(consumed, buf.len());
+        assert_eq!(decoded, entry);
+    }
+
+    #[test]
+    fn test_round_trip_unnamed_facility() {
+        let entry = Entry::builder()
+            .facility(LogFacility::Other(128))
+            .level(LogLevel::Info)
+            .message("no name")
+            .build();
+
+        let mut buf = Vec::new();
+        encode(&entry, &mut buf).unwrap();
+
+        let (decoded, _) = decode(&buf).unwrap();
+        assert_eq!(decoded, entry);
+    }
+
+    #[test]
+    fn test_round_trip_absent_fields() {
+        let entry = Entry::builder().message(" LINE2=foobar").build();
+
+        let mut buf = Vec::new();
+        encode(&entry, &mut buf).unwrap();
+
+        let (decoded, _) = decode(&buf).unwrap();
+        assert_eq!(decoded, entry);
+    }
+
+    #[test]
+    fn test_stream_demultiplex() {
+        let first = Entry::builder()
+            .timestamp(Duration::from_micros(1))
+            .facility(LogFacility::User)
+            .level(LogLevel::Warning)
+            .sequence_num(1)
+            .message("first")
+            .build();
+        let second = Entry::builder()
+            .timestamp(Duration::from_micros(1))
+            .facility(LogFacility::User)
+            .level(LogLevel::Warning)
+            .sequence_num(2)
+            .message("second")
+            .build();
+
+        let mut buf = Vec::new();
+        encode(&first, &mut buf).unwrap();
+        encode(&second, &mut buf).unwrap();
+
+        let (decoded_first, consumed) = decode(&buf).unwrap();
+        assert_eq!(decoded_first, first);
+
+        let (decoded_second, consumed_second) = decode(&buf[consumed..]).unwrap();
+        assert_eq!(decoded_second, second);
+        assert_eq!(consumed + consumed_second, buf.len());
+    }
+
+    #[test]
+    fn test_truncated() {
+        let entry = Entry::builder().message("truncated").build();
+
+        let mut buf = Vec::new();
+        encode(&entry, &mut buf).unwrap();
+
+        assert!(decode(&buf[..HEADER_LEN - 1]).is_err());
+        assert!(decode(&buf[..buf.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_encode_out_of_range() {
+        let mut buf = Vec::new();
+
+        let entry = Entry::builder().sequence_num(NONE_U64).build();
+        assert!(matches!(
+            encode(&entry, &mut buf),
+            Err(RMesgError::WireFormatError(_))
+        ));
+
+        let entry = Entry::builder().timestamp(Duration::MAX).build();
+        assert!(matches!(
+            encode(&entry, &mut buf),
+            Err(RMesgError::WireFormatError(_))
+        ));
+
+        let entry = Entry::builder()
+            .facility(LogFacility::Other(NONE_U8))
+            .build();
+        assert!(matches!(
+            encode(&entry, &mut buf),
+            Err(RMesgError::WireFormatError(_))
+        ));
+
+        assert!(buf.is_empty());
+    }
+}
@@ -0,0 +1,77 @@
+// Compile-time checks that the public types and functions downstream users depend on
+// are still there, with the traits they rely on. Nothing runs: if this file builds, it passes.
+
+use rmesg::entry::{
+    DisplayOptions, Entry, EntryBuilder, EntryDisplay, EntryParsingError, LogFacility, LogLevel,
+};
+use rmesg::error::RMesgError;
+use rmesg::{klogctl, kmsgfile, Backend, EntriesIterator, FilteredEntriesIterator};
+
+use static_assertions::assert_impl_all;
+use std::error::Error;
+use std::fmt::{Debug, Display};
+use std::path::Path;
+use std::str::FromStr;
+
+type EntryResult = Result<Entry, RMesgError>;
+
+// Entries and their fields
+assert_impl_all!(Entry: Clone, Debug, PartialEq, Eq, PartialOrd, Ord, std::hash::Hash, Display, FromStr, Send, Sync);
+assert_impl_all!(DisplayOptions: Copy, Clone, Debug, Default, PartialEq);
+assert_impl_all!(EntryDisplay<'static>: Display);
+assert_impl_all!(LogFacility: Copy, Clone, Debug, PartialEq, Display, FromStr, num_traits::FromPrimitive);
+assert_impl_all!(LogLevel: Copy, Clone, Debug, PartialEq, Display, FromStr, num_traits::FromPrimitive);
+
+// Errors
+assert_impl_all!(EntryParsingError: Debug, Display, Error);
+assert_impl_all!(
+    RMesgError: Debug,
+    Display,
+    Error,
+    Send,
+    Sync,
+    From<std::io::Error>,
+    From<std::string::FromUtf8Error>,
+    From<EntryParsingError>
+);
+
+// Backends and iterators
+assert_impl_all!(Backend: Copy, Clone, Debug);
+assert_impl_all!(EntriesIterator: Iterator<Item = EntryResult>);
+assert_impl_all!(FilteredEntriesIterator: Iterator<Item = EntryResult>);
+assert_impl_all!(klogctl::KLogEntries: Iterator<Item = EntryResult>);
+assert_impl_all!(klogctl::KLogEntriesNoTimestamp: Iterator<Item = EntryResult>);
+assert_impl_all!(kmsgfile::KMsgEntriesIter: Iterator<Item = EntryResult>);
+assert_impl_all!(kmsgfile::EpollKMsgEntriesIter: Iterator<Item = EntryResult>);
+assert_impl_all!(rmesg::aggregate::AggregatedReader: Iterator<Item = EntryResult>);
+
+// Guards, which only work if they do something when dropped
+const _: () = assert!(std::mem::needs_drop::<klogctl::KLogConsoleGuard>());
+
+// Free functions, by signature
+const _: fn(Backend, bool) -> Result<Vec<Entry>, RMesgError> = rmesg::log_entries;
+const _: fn(Backend, bool) -> Result<String, RMesgError> = rmesg::logs_raw;
+const _: fn(Backend, bool, bool) -> Result<EntriesIterator, RMesgError> = rmesg::logs_iter;
+const _: fn(Backend, bool, bool, u8) -> Result<FilteredEntriesIterator, RMesgError> =
+    rmesg::logs_iter_filtered;
+const _: fn(bool) -> Result<Vec<Entry>, RMesgError> = klogctl::klog;
+const _: fn(bool) -> Result<String, RMesgError> = klogctl::klog_raw;
+const _: fn() -> Result<Vec<Entry>, RMesgError> = klogctl::klog_read_clear;
+const _: fn() -> Result<(), RMesgError> = klogctl::klog_clear;
+const _: fn() -> Result<(), RMesgError> = klogctl::klog_console_on;
+const _: fn() -> Result<(), RMesgError> = klogctl::klog_console_off;
+const _: fn() -> Result<bool, RMesgError> = klogctl::klog_timestamps_enabled;
+const _: fn(&str) -> Result<Entry, EntryParsingError> = klogctl::entry_from_line;
+#[allow(deprecated)]
+const _: fn(Option<String>) -> Result<Vec<Entry>, RMesgError> = kmsgfile::kmsg;
+#[allow(deprecated)]
+const _: fn(Option<String>) -> Result<String, RMesgError> = kmsgfile::kmsg_raw;
+const _: fn(Option<&Path>) -> Result<Vec<Entry>, RMesgError> = kmsgfile::kmsg_from_path;
+const _: fn(Option<&Path>) -> Result<String, RMesgError> = kmsgfile::kmsg_raw_from_path;
+const _: fn(&str) -> Result<Entry, EntryParsingError> = kmsgfile::entry_from_line;
+const _: fn(&str) -> Result<Entry, EntryParsingError> = rmesg::entry_from_line;
+const _: fn(&str) -> Result<Entry, EntryParsingError> = Entry::from_kmsg_str;
+const _: fn() -> EntryBuilder = Entry::builder;
+const _: fn(EntryBuilder) -> Entry = EntryBuilder::build;
+const _: for<'a> fn(&'a Entry, &DisplayOptions) -> EntryDisplay<'a> = Entry::display_with;
+const _: fn(Option<&Path>) -> Result<Vec<u8>, RMesgError> = kmsgfile::kmsg_raw_bytes;
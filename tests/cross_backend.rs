@@ -0,0 +1,132 @@
+// Reads the kernel log buffer through both backends and checks that they agree on
+// every entry they have in common. Needs permission to use both backends, and kernel
+// log timestamps enabled (the only field both backends carry that identifies an entry).
+#![cfg(target_os = "linux")]
+
+use rmesg::entry::Entry;
+use rmesg::error::RMesgError;
+use rmesg::{klogctl, kmsgfile};
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// /dev/kmsg escapes non-printable characters in a message as `\xNN`, whereas klogctl
+/// passes them through as-is.
+fn unescape(message: &str) -> String {
+    let mut unescaped = String::with_capacity(message.len());
+    let mut rest = message;
+
+    while let Some(start) = rest.find("\\x") {
+        unescaped.push_str(&rest[..start]);
+        let escape = rest.get(start + 2..start + 4).unwrap_or_default();
+        match u8::from_str_radix(escape, 16) {
+            Ok(byte) => {
+                unescaped.push(byte as char);
+                rest = &rest[start + 4..];
+            }
+            Err(_) => {
+                unescaped.push_str("\\x");
+                rest = &rest[start + 2..];
+            }
+        }
+    }
+
+    unescaped.push_str(rest);
+    unescaped
+}
+
+/// Keys entries by their timestamp, plus their position amongst entries sharing that timestamp.
+/// Entries without a timestamp (continuation lines) can't be matched up and are skipped.
+fn by_timestamp(entries: Vec<Entry>) -> HashMap<(Duration, usize), Entry> {
+    let mut occurrences: HashMap<Duration, usize> = HashMap::new();
+    let mut keyed = HashMap::new();
+
+    for entry in entries {
+        if let Some(timestamp) = entry.timestamp_from_system_start {
+            let occurrence = occurrences.entry(timestamp).or_insert(0);
+            keyed.insert((timestamp, *occurrence), entry);
+            *occurrence += 1;
+        }
+    }
+
+    keyed
+}
+
+fn skip_if_unavailable<T>(result: Result<T, RMesgError>) -> Option<T> {
+    match result {
+        Ok(t) => Some(t),
+        Err(RMesgError::OperationNotPermitted(s)) => {
+            eprintln!("Skipping cross-backend test, not permitted: {}", s);
+            None
+        }
+        Err(RMesgError::DevKMsgFileOpenError(s)) => {
+            eprintln!("Skipping cross-backend test, /dev/kmsg unavailable: {}", s);
+            None
+        }
+        Err(e) => panic!("Unable to read kernel log: {}", e),
+    }
+}
+
+#[test]
+fn test_backends_agree() {
+    match klogctl::klog_timestamps_enabled() {
+        Ok(true) => {}
+        _ => {
+            eprintln!("Skipping cross-backend test, kernel log timestamps are not enabled");
+            return;
+        }
+    }
+
+    let klog_entries = match skip_if_unavailable(klogctl::klog(false)) {
+        Some(e) => e,
+        None => return,
+    };
+    let mut kmsg_entries = match skip_if_unavailable(kmsgfile::kmsg_from_path(None)) {
+        Some(e) => e,
+        None => return,
+    };
+    kmsg_entries.sort_by_key(|e| e.sequence_num);
+
+    let klog_entries = by_timestamp(klog_entries);
+    let kmsg_entries = by_timestamp(kmsg_entries);
+
+    let mut in_both = 0;
+    let mut only_in_one = 0;
+
+    for (key, kmsg_entry) in kmsg_entries.iter() {
+        let klog_entry = match klog_entries.get(key) {
+            Some(e) => e,
+            None => {
+                only_in_one += 1;
+                continue;
+            }
+        };
+        in_both += 1;
+
+        assert_eq!(klog_entry.facility, kmsg_entry.facility, "{:?}", key);
+        assert_eq!(klog_entry.level, kmsg_entry.level, "{:?}", key);
+
+        // klogctl keeps the space that separates the timestamp from the message
+        let klog_message = klog_entry
+            .message
+            .strip_prefix(' ')
+            .unwrap_or(&klog_entry.message);
+        // klogctl breaks a multi-line message across lines, only the first of which has a prefix
+        let kmsg_message = unescape(&kmsg_entry.message);
+        let kmsg_message = kmsg_message.split('\n').next().unwrap_or_default();
+        assert_eq!(klog_message, kmsg_message, "{:?}", key);
+    }
+
+    only_in_one += klog_entries
+        .keys()
+        .filter(|key| !kmsg_entries.contains_key(key))
+        .count();
+
+    if only_in_one > 0 {
+        eprintln!(
+            "{} entries were only present in one of the backends (the ring buffer moved between reads)",
+            only_in_one
+        );
+    }
+    eprintln!("{} entries compared across both backends", in_both);
+}
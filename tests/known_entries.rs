@@ -0,0 +1,294 @@
+// Hand-verified /dev/kmsg lines and the exact entries they are expected to parse into.
+
+use rmesg::entry::{Entry, EntryBuilder, EntryCategory, EntryParsingError, LogFacility, LogLevel};
+use rmesg::kmsgfile::{entry_from_line, entry_from_line_ref};
+use std::time::Duration;
+
+fn builder(
+    facility: LogFacility,
+    level: LogLevel,
+    sequence_num: u64,
+    timestamp_micros: u64,
+    message: &str,
+) -> EntryBuilder {
+    Entry::builder()
+        .facility(facility)
+        .level(level)
+        .sequence_num(sequence_num)
+        .timestamp(Duration::from_micros(timestamp_micros))
+        .message(message)
+        .raw_flags("-")
+}
+
+fn entry(
+    facility: LogFacility,
+    level: LogLevel,
+    sequence_num: u64,
+    timestamp_micros: u64,
+    message: &str,
+) -> Entry {
+    builder(facility, level, sequence_num, timestamp_micros, message).build()
+}
+
+// For lines with flags other than the usual "-"
+fn with_flags(builder: EntryBuilder, raw_flags: &str, dict: &[(&str, &str)]) -> Entry {
+    dict.iter()
+        .fold(builder.raw_flags(raw_flags), |builder, (key, value)| {
+            builder.dict_entry(key, value)
+        })
+        .build()
+}
+
+fn message_only(message: &str) -> Entry {
+    Entry::builder().message(message).build()
+}
+
+fn known_entries() -> Vec<(&'static str, Entry)> {
+    use LogFacility::*;
+    use LogLevel::*;
+
+    vec![
+        // x86
+        (
+            "5,0,0,-;Linux version 4.14.131-linuxkit (root@6d384074ad24) (gcc version 8.3.0 (Alpine 8.3.0)) #1 SMP Fri Jul 19 12:31:17 UTC 2019",
+            entry(Kern, Notice, 0, 0, "Linux version 4.14.131-linuxkit (root@6d384074ad24) (gcc version 8.3.0 (Alpine 8.3.0)) #1 SMP Fri Jul 19 12:31:17 UTC 2019"),
+        ),
+        (
+            "6,1,0,-;Command, line: BOOT_IMAGE=/boot/kernel console=ttyS0 console=ttyS1 page_poison=1 vsyscall=emulate panic=1 root=/dev/sr0 text",
+            entry(Kern, Info, 1, 0, "Command, line: BOOT_IMAGE=/boot/kernel console=ttyS0 console=ttyS1 page_poison=1 vsyscall=emulate panic=1 root=/dev/sr0 text"),
+        ),
+        (
+            "6,2,0,-;x86/fpu: Supporting XSAVE feature 0x001: 'x87 floating point registers'",
+            entry(Kern, Info, 2, 0, "x86/fpu: Supporting XSAVE feature 0x001: 'x87 floating point registers'"),
+        ),
+        (
+            "6,779,91650777797,-;docker0: port 2(veth98d5024) entered disabled state",
+            entry(Kern, Info, 779, 91650777797, "docker0: port 2(veth98d5024) entered disabled state"),
+        ),
+        (
+            "3,520,73251233452,-;nvme nvme0: I/O 123 QID 4 timeout, completion polled",
+            entry(Kern, Error, 520, 73251233452, "nvme nvme0: I/O 123 QID 4 timeout, completion polled"),
+        ),
+        // Semicolons in the message
+        (
+            "6,3,0,-,more,deets;x86/fpu: Supporting XSAVE; feature 0x002: 'SSE registers'",
+            with_flags(
+                builder(Kern, Info, 3, 0, "x86/fpu: Supporting XSAVE; feature 0x002: 'SSE registers'"),
+                "-,more,deets",
+                &[],
+            ),
+        ),
+        (
+            "4,4,1500,-;a;b;c;",
+            entry(Kern, Warning, 4, 1500, "a;b;c;"),
+        ),
+        // Continuation lines
+        (" LINE2=foobar", message_only(" LINE2=foobar")),
+        (
+            " LINE 3 = foobar ; with semicolon",
+            message_only(" LINE 3 = foobar ; with semicolon"),
+        ),
+        (" SUBSYSTEM=usb", message_only(" SUBSYSTEM=usb")),
+        (" DEVICE=+usb:1-1", message_only(" DEVICE=+usb:1-1")),
+        // Metadata fields between the timestamp and the semicolon
+        (
+            "6,780,91650777800,-,caller=T123;usb 1-1: new high-speed USB device number 2 using xhci_hcd",
+            with_flags(
+                builder(Kern, Info, 780, 91650777800, "usb 1-1: new high-speed USB device number 2 using xhci_hcd"),
+                "-,caller=T123",
+                &[("caller", "T123")],
+            ),
+        ),
+        (
+            "6,3,0,-,SUBSYSTEM=usb,DEVICE=+usb:1-1;message",
+            with_flags(
+                builder(Kern, Info, 3, 0, "message"),
+                "-,SUBSYSTEM=usb,DEVICE=+usb:1-1",
+                &[("SUBSYSTEM", "usb"), ("DEVICE", "+usb:1-1")],
+            ),
+        ),
+        (
+            "4,44,5000001,c;continued line",
+            with_flags(builder(Kern, Warning, 44, 5000001, "continued line"), "c", &[]),
+        ),
+        // Whitespace
+        (
+            "4,12,1234567,-;trailing spaces   ",
+            entry(Kern, Warning, 12, 1234567, "trailing spaces   "),
+        ),
+        (
+            " 6 , 13 , 1234568 , - ;padded fields",
+            with_flags(builder(Kern, Info, 13, 1234568, "padded fields"), " - ", &[]),
+        ),
+        (
+            "6,14,1234569,-; leading space in message",
+            entry(Kern, Info, 14, 1234569, " leading space in message"),
+        ),
+        ("6,43,5000000,-;", entry(Kern, Info, 43, 5000000, "")),
+        // ARM
+        (
+            "6,0,0,-;Booting Linux on physical CPU 0x0000000000 [0x410fd083]",
+            entry(Kern, Info, 0, 0, "Booting Linux on physical CPU 0x0000000000 [0x410fd083]"),
+        ),
+        (
+            "5,1,0,-;Linux version 5.10.17-v8+ (dom@buildbot) (aarch64-linux-gnu-gcc-8 (Ubuntu/Linaro 8.4.0-3ubuntu1) 8.4.0, GNU ld (GNU Binutils for Ubuntu) 2.34) #1403 SMP PREEMPT Mon Feb 22 11:37:54 GMT 2021",
+            entry(Kern, Notice, 1, 0, "Linux version 5.10.17-v8+ (dom@buildbot) (aarch64-linux-gnu-gcc-8 (Ubuntu/Linaro 8.4.0-3ubuntu1) 8.4.0, GNU ld (GNU Binutils for Ubuntu) 2.34) #1403 SMP PREEMPT Mon Feb 22 11:37:54 GMT 2021"),
+        ),
+        (
+            "6,2,0,-;Machine model: Raspberry Pi 4 Model B Rev 1.4",
+            entry(Kern, Info, 2, 0, "Machine model: Raspberry Pi 4 Model B Rev 1.4"),
+        ),
+        (
+            "4,215,2841265,-;vc4-drm gpu: [drm] Cannot find any crtc or sizes",
+            entry(Kern, Warning, 215, 2841265, "vc4-drm gpu: [drm] Cannot find any crtc or sizes"),
+        ),
+        // MIPS
+        (
+            "5,0,0,-;Linux version 4.14.221 (builder@buildhost) (gcc version 7.5.0 (OpenWrt GCC 7.5.0 r11306-c4a6851c72)) #0 Mon Feb 15 15:22:37 2021",
+            entry(Kern, Notice, 0, 0, "Linux version 4.14.221 (builder@buildhost) (gcc version 7.5.0 (OpenWrt GCC 7.5.0 r11306-c4a6851c72)) #0 Mon Feb 15 15:22:37 2021"),
+        ),
+        (
+            "6,1,0,-;SoC Type: MediaTek MT7621 ver:1 eco:3",
+            entry(Kern, Info, 1, 0, "SoC Type: MediaTek MT7621 ver:1 eco:3"),
+        ),
+        (
+            "6,2,0,-;CPU0 revision is: 0001992f (MIPS 1004Kc)",
+            entry(Kern, Info, 2, 0, "CPU0 revision is: 0001992f (MIPS 1004Kc)"),
+        ),
+        // Levels
+        (
+            "0,1000,100000000,-;Kernel panic - not syncing: Fatal exception",
+            builder(Kern, Emergency, 1000, 100000000, "Kernel panic - not syncing: Fatal exception")
+                .category(EntryCategory::KernelPanic)
+                .build(),
+        ),
+        ("1,1001,100000001,-;alert", entry(Kern, Alert, 1001, 100000001, "alert")),
+        ("2,1002,100000002,-;critical", entry(Kern, Critical, 1002, 100000002, "critical")),
+        ("7,1003,100000003,-;debug", entry(Kern, Debug, 1003, 100000003, "debug")),
+        // Facilities
+        (
+            "14,2001,50000000,-;hello from userspace",
+            entry(User, Info, 2001, 50000000, "hello from userspace"),
+        ),
+        ("30,2002,50000001,-;daemon", entry(Daemon, Info, 2002, 50000001, "daemon")),
+        ("78,2003,50000002,-;cron", entry(Cron, Info, 2003, 50000002, "cron")),
+        ("91,2004,50000003,-;ftp", entry(FTP, Error, 2004, 50000003, "ftp")),
+        ("96,2007,50000006,-;ntp", entry(Ntp, Emergency, 2007, 50000006, "ntp")),
+        ("1030,2008,50000007,-;no name", entry(Other(128), Info, 2008, 50000007, "no name")),
+        ("134,2005,50000004,-;local0", entry(Local0, Info, 2005, 50000004, "local0")),
+        ("191,2006,50000005,-;local7", entry(Local7, Debug, 2006, 50000005, "local7")),
+    ]
+}
+
+#[test]
+fn test_known_entries() {
+    for (line, expected) in known_entries() {
+        let parsed = entry_from_line(line);
+        assert!(parsed.is_ok(), "Unable to parse line: {}", line);
+        assert_eq!(parsed.unwrap(), expected, "Line: {}", line);
+    }
+}
+
+#[test]
+fn test_unrecognized_format() {
+    // Neither a header nor a continuation line, which starts with a space
+    for line in &["6,45,5000002,-", "no header at all"] {
+        let parsed = entry_from_line(line);
+        assert!(
+            matches!(parsed, Err(EntryParsingError::UnrecognizedFormat(_))),
+            "Line: {}, parsed: {:?}",
+            line,
+            parsed
+        );
+        assert!(matches!(
+            entry_from_line_ref(line),
+            Err(EntryParsingError::UnrecognizedFormat(_))
+        ));
+    }
+}
+
+#[test]
+fn test_very_long_message() {
+    let message = "x".repeat(64 * 1024);
+    let line = format!("6,5000,123456789,-;{}", message);
+
+    let parsed = entry_from_line(&line).unwrap();
+    assert_eq!(
+        parsed,
+        entry(LogFacility::Kern, LogLevel::Info, 5000, 123456789, &message)
+    );
+}
+
+#[test]
+fn test_invalid_header_fields() {
+    // Shaped like a header, so an error rather than a continuation line, whichever parser is built
+    for line in &[
+        ",100,5000000,-;empty",
+        "4294967296,100,5000000,-;too large",
+        "2048,100,5000000,-;facility above 255",
+    ] {
+        let parsed = entry_from_line(line);
+        assert!(
+            matches!(parsed, Err(EntryParsingError::InvalidFacilityLevel { .. })),
+            "Line: {}, parsed: {:?}",
+            line,
+            parsed
+        );
+        assert!(matches!(
+            entry_from_line_ref(line),
+            Err(EntryParsingError::InvalidFacilityLevel { .. })
+        ));
+    }
+
+    for line in &[
+        "6,,5000000,-;empty",
+        " 6 ,  , 5000000 ,-;padded empty",
+        "6,18446744073709551616,5000000,-;too large",
+    ] {
+        let parsed = entry_from_line(line);
+        assert!(
+            matches!(parsed, Err(EntryParsingError::InvalidSequenceNum { .. })),
+            "Line: {}, parsed: {:?}",
+            line,
+            parsed
+        );
+        assert!(matches!(
+            entry_from_line_ref(line),
+            Err(EntryParsingError::InvalidSequenceNum { .. })
+        ));
+    }
+
+    for line in &["6,100,,-;empty", "6,100,18446744073709551616,-;too large"] {
+        let parsed = entry_from_line(line);
+        assert!(
+            matches!(parsed, Err(EntryParsingError::InvalidTimestamp { .. })),
+            "Line: {}, parsed: {:?}",
+            line,
+            parsed
+        );
+        assert!(matches!(
+            entry_from_line_ref(line),
+            Err(EntryParsingError::InvalidTimestamp { .. })
+        ));
+    }
+}
+
+#[test]
+fn test_empty_line() {
+    assert!(matches!(
+        entry_from_line(""),
+        Err(EntryParsingError::EmptyLine)
+    ));
+    assert!(matches!(
+        entry_from_line_ref(""),
+        Err(EntryParsingError::EmptyLine)
+    ));
+    assert!(matches!(
+        rmesg::klogctl::entry_from_line(""),
+        Err(EntryParsingError::EmptyLine)
+    ));
+
+    // Readers of whole buffers skip empty lines
+    let entries = rmesg::klogctl::entries_from_lines("<6>first\n\n<6>second\n").unwrap();
+    assert_eq!(entries.len(), 2);
+}